@@ -1,8 +1,19 @@
 use std::collections::HashMap;
 
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-use crate::parse::{ArgList, Ident};
+use anyhow::{Result, bail};
+
+use crate::complex::Complex;
+use crate::distributions::{Distribution, DistributionFunction};
+use crate::dual::Dual;
+use crate::matrix::Matrix;
+use crate::parse::{ArgList, BinaryOp, Ident};
+use crate::rational::Rational;
+use crate::special;
+use crate::units::Quantity;
+use crate::value::Value;
 
 #[derive(Debug, Clone, Copy, EnumIter)]
 pub enum BuiltinFunction {
@@ -11,7 +22,661 @@ pub enum BuiltinFunction {
     Sin,
     Cos,
     Tan,
+    Asin,
+    Acos,
     Atan,
+    /// `atan2(y, x)`, the angle of the point `(x, y)`.
+    Atan2,
+    Exp,
+    Ln,
+    Re,
+    Im,
+    Arg,
+    Conj,
+    Gamma,
+    /// The logarithm of the absolute value of the gamma function.
+    Lgamma,
+    /// `beta(a, b)`, the beta function `Γ(a) Γ(b) / Γ(a + b)`.
+    Beta,
+    Erf,
+    /// Rewrites its argument in a simpler form, leaving its value unchanged.
+    Simplify,
+    Ode,
+    /// `sum(k, a, b, f)`, the sum of `f` for the integers `k` from `a` to `b`.
+    Sum,
+    /// `prod(k, a, b, f)`, the product of `f` for the integers `k` from `a` to `b`.
+    Prod,
+    /// `int(f, t, a, b)`, the integral of `f` for `t` from `a` to `b`.
+    Int,
+    /// `root(f, a, b)`, the first zero of the function `f` between `a` and `b`.
+    Root,
+    /// `argmin(f, a, b)`, where the function `f` is smallest between `a` and `b`.
+    Argmin,
+    /// `argmax(f, a, b)`, where the function `f` is largest between `a` and `b`.
+    Argmax,
+    /// `map(f, L)`, the list of `f` applied to each element of `L`.
+    Map,
+    /// `filter(f, L)`, the elements of `L` for which `f` is nonzero.
+    Filter,
+    /// `fold(f, x0, L)`, which combines `x0` with each element of `L` in turn by `f`.
+    Fold,
+    /// The number of elements of a list.
+    Length,
+    /// The sum of the elements of a list.
+    Total,
+    Mean,
+    Median,
+    /// The sample variance of a list, which divides by one less than its length.
+    Variance,
+    /// The sample standard deviation of a list.
+    Stdev,
+    /// `quantile(L, p)`, the value below which a fraction `p` of the elements of `L`
+    /// fall, interpolating linearly between them.
+    Quantile,
+    Min,
+    Max,
+    /// The numbers of a list from smallest to largest.
+    Sort,
+    Transpose,
+    /// The determinant of a square matrix.
+    Det,
+    Inverse,
+    /// `linsolve(A, b)`, the vector `x` with `A*x = b`.
+    Linsolve,
+    /// The real or complex eigenvalues of a matrix up to 3x3.
+    Eigenvalues,
+    /// `normpdf(x, μ, σ)`, `normcdf(x, μ, σ)` and `norminv(p, μ, σ)` of the normal
+    /// distribution.
+    NormPdf,
+    NormCdf,
+    NormInv,
+    /// `binopdf(k, n, p)`, `binocdf(k, n, p)` and `binoinv(q, n, p)` of the binomial distribution.
+    BinoPdf,
+    BinoCdf,
+    BinoInv,
+    /// `poisspdf(k, λ)`, `poisscdf(k, λ)` and `poissinv(p, λ)` of the Poisson distribution.
+    PoissPdf,
+    PoissCdf,
+    PoissInv,
+    /// `unifpdf(x, a, b)`, `unifcdf(x, a, b)` and `unifinv(p, a, b)` of the uniform distribution.
+    UnifPdf,
+    UnifCdf,
+    UnifInv,
+    /// `tpdf(x, ν)`, `tcdf(x, ν)` and `tinv(p, ν)` of Student's t distribution.
+    TPdf,
+    TCdf,
+    TInv,
+    /// `chi2pdf(x, k)`, `chi2cdf(x, k)` and `chi2inv(p, k)` of the chi-square distribution.
+    Chi2Pdf,
+    Chi2Cdf,
+    Chi2Inv,
+}
+
+#[derive(Debug, Clone, Copy, EnumIter)]
+pub enum BuiltinConstant {
+    I,
+    Inf,
+}
+
+impl BuiltinFunction {
+    pub fn arity(&self) -> usize {
+        match self {
+            _ if let Some((distribution, _)) = self.distribution() => distribution.parameters() + 1,
+            Self::Ode | Self::Sum | Self::Prod | Self::Int => 4,
+            Self::Root | Self::Argmin | Self::Argmax | Self::Fold => 3,
+            Self::Atan2
+            | Self::Beta
+            | Self::Quantile
+            | Self::Map
+            | Self::Filter
+            | Self::Linsolve => 2,
+            _ => 1,
+        }
+    }
+
+    /// The number of arguments of the function passed to a builtin that takes a
+    /// function as its first argument, like `ode(f, x0, y0, x)`.
+    pub fn function_arity(&self) -> Option<usize> {
+        match self {
+            Self::Ode | Self::Fold => Some(2),
+            Self::Root | Self::Argmin | Self::Argmax | Self::Map | Self::Filter => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Whether the builtin combines the elements of a list, rather than applying to
+    /// each of them like the other builtins of one argument.
+    pub fn takes_list(&self) -> bool {
+        matches!(
+            self,
+            Self::Length
+                | Self::Total
+                | Self::Mean
+                | Self::Median
+                | Self::Variance
+                | Self::Stdev
+                | Self::Quantile
+                | Self::Min
+                | Self::Max
+                | Self::Sort
+        )
+    }
+
+    /// Whether the builtin takes a matrix as its first argument.
+    pub fn takes_matrix(&self) -> bool {
+        matches!(
+            self,
+            Self::Transpose | Self::Det | Self::Inverse | Self::Linsolve | Self::Eigenvalues
+        )
+    }
+
+    /// The distribution and which of its functions a builtin like `normcdf` computes.
+    pub fn distribution(&self) -> Option<(Distribution, DistributionFunction)> {
+        use Distribution::*;
+        use DistributionFunction::*;
+        Some(match self {
+            Self::NormPdf => (Normal, Pdf),
+            Self::NormCdf => (Normal, Cdf),
+            Self::NormInv => (Normal, Inverse),
+            Self::BinoPdf => (Binomial, Pdf),
+            Self::BinoCdf => (Binomial, Cdf),
+            Self::BinoInv => (Binomial, Inverse),
+            Self::PoissPdf => (Poisson, Pdf),
+            Self::PoissCdf => (Poisson, Cdf),
+            Self::PoissInv => (Poisson, Inverse),
+            Self::UnifPdf => (Uniform, Pdf),
+            Self::UnifCdf => (Uniform, Cdf),
+            Self::UnifInv => (Uniform, Inverse),
+            Self::TPdf => (StudentT, Pdf),
+            Self::TCdf => (StudentT, Cdf),
+            Self::TInv => (StudentT, Inverse),
+            Self::Chi2Pdf => (ChiSquare, Pdf),
+            Self::Chi2Cdf => (ChiSquare, Cdf),
+            Self::Chi2Inv => (ChiSquare, Inverse),
+            _ => return None,
+        })
+    }
+
+    /// The builtin that computes a function of a distribution, like `normpdf` for the
+    /// density of the normal distribution.
+    pub fn of_distribution(distribution: Distribution, function: DistributionFunction) -> Self {
+        Self::iter()
+            .find(|builtin| builtin.distribution() == Some((distribution, function)))
+            .expect("every function of a distribution is a builtin")
+    }
+
+    /// Splits the arguments of a builtin that binds a variable, like `sum(k, a, b, f)`
+    /// or `int(f, t, a, b)`, into the variable, the bounds and the expression it is
+    /// bound in. Returns `None` for other builtins or the wrong number of arguments.
+    pub fn bound_args<'a, T>(&self, args: &'a [T]) -> Option<[&'a T; 4]> {
+        match (self, args) {
+            (Self::Sum | Self::Prod, [var, from, to, body]) => Some([var, from, to, body]),
+            (Self::Int, [body, var, from, to]) => Some([var, from, to, body]),
+            _ => None,
+        }
+    }
+
+    /// Applies a builtin that only depends on the values of its arguments.
+    pub fn apply(&self, args: &[Value]) -> Result<Value> {
+        match (self, args) {
+            (
+                Self::Ode
+                | Self::Root
+                | Self::Argmin
+                | Self::Argmax
+                | Self::Map
+                | Self::Filter
+                | Self::Fold,
+                _,
+            ) => {
+                unreachable!("{self} takes a function and is evaluated by the interpreter")
+            }
+            (Self::Sum | Self::Prod | Self::Int, _) => {
+                unreachable!("{self} binds an index and is evaluated by the interpreter")
+            }
+            (Self::Simplify, [x]) => Ok(x.clone()),
+            (_, [list, rest @ ..]) if self.takes_list() => self.apply_list(list, rest),
+            (_, [Value::Matrix(m), rest @ ..]) if self.takes_matrix() => self.apply_matrix(m, rest),
+            (_, [x, ..]) if self.takes_matrix() => {
+                bail!("Expected a matrix but got a {}", x.type_name())
+            }
+            (Self::Atan2, [y, x]) if y.dimension() != x.dimension() => {
+                bail!(
+                    "Cannot apply atan2 to {} and {}",
+                    y.dimension(),
+                    x.dimension()
+                )
+            }
+            (Self::Atan2, [y, x]) => Ok(Value::Number(y.as_number()?.atan2(x.as_number()?))),
+            (Self::Beta, [a, b]) => Ok(Value::Number(special::beta(
+                self.real_argument(a)?,
+                self.real_argument(b)?,
+            ))),
+            (_, [x, parameters @ ..])
+                if let Some((distribution, function)) = self.distribution() =>
+            {
+                let x = self.real_argument(x)?;
+                let parameters = parameters
+                    .iter()
+                    .map(|parameter| self.real_argument(parameter))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Value::Number(distribution.apply(function, x, &parameters)))
+            }
+            (_, [x]) => x.map_numbers(self, &|x| match x {
+                Value::Number(x) => Ok(self.apply_real(*x)),
+                Value::Rational(x) => Ok(self.apply_rational(x)),
+                Value::Complex(_) if matches!(self, Self::Gamma | Self::Lgamma | Self::Erf) => {
+                    bail!("Cannot apply {self} to a complex number")
+                }
+                Value::Complex(z) => Ok(self.apply_complex(*z)),
+                Value::Quantity(x) => self.apply_quantity(x),
+                _ => unreachable!(),
+            }),
+            _ => unreachable!("arity is checked before applying a builtin"),
+        }
+    }
+
+    /// Applies a builtin that combines the elements of a list, with the rest of its
+    /// arguments. The mean, median, quantiles, minimum and maximum of an empty list
+    /// are undefined, as are the variance and standard deviation of fewer than two
+    /// elements.
+    fn apply_list(&self, list: &Value, rest: &[Value]) -> Result<Value> {
+        let Value::List(elements) = list else {
+            bail!("Expected a list but got a {}", list.type_name());
+        };
+        let total = || {
+            let mut elements = elements.iter();
+            let Some(first) = elements.next() else {
+                return Ok(Value::Number(0.0));
+            };
+            elements.try_fold(first.clone(), |total, element| {
+                Value::binary_op(BinaryOp::Add, &total, element)
+            })
+        };
+        // the statistics of exact numbers stay exact
+        let exact = elements
+            .iter()
+            .all(|element| matches!(element, Value::Rational(_)));
+        let count = |n: usize| match exact {
+            true => Value::Rational(Rational::from_integer(n as i64)),
+            false => Value::Number(n as f64),
+        };
+        let mean = || Value::binary_op(BinaryOp::Divide, &total()?, &count(elements.len()));
+        let sorted = || -> Result<Vec<&Value>> {
+            let keys = Self::sort_keys(self, elements)?;
+            let mut order: Vec<usize> = (0..elements.len()).collect();
+            order.sort_by(|&i, &j| keys[i].total_cmp(&keys[j]));
+            Ok(order.iter().map(|&i| &elements[i]).collect())
+        };
+        Ok(match (self, rest) {
+            (Self::Length, []) => Value::Number(elements.len() as f64),
+            (Self::Total, []) => total()?,
+            (Self::Mean | Self::Median | Self::Quantile, _) if elements.is_empty() => {
+                Value::Number(f64::NAN)
+            }
+            (Self::Mean, []) => mean()?,
+            (Self::Median, []) => {
+                let half = match exact {
+                    true => Value::Rational(Rational::new(1, 2)),
+                    false => Value::Number(0.5),
+                };
+                Self::quantile(&sorted()?, &half, count)?
+            }
+            (Self::Quantile, [p]) => match p.as_number()? {
+                q if (0.0..=1.0).contains(&q) => Self::quantile(&sorted()?, p, count)?,
+                q => bail!("Quantiles must be between 0 and 1, not {q}"),
+            },
+            (Self::Variance | Self::Stdev, []) => {
+                // checks that the elements are real numbers of the same dimension
+                Self::sort_keys(self, elements)?;
+                if elements.len() < 2 {
+                    return Ok(Value::Number(f64::NAN));
+                }
+                let mean = mean()?;
+                let squares = elements.iter().map(|element| {
+                    let deviation = Value::binary_op(BinaryOp::Subtract, element, &mean)?;
+                    Value::binary_op(BinaryOp::DotProduct, &deviation, &deviation)
+                });
+                let sum =
+                    squares.reduce(|sum, square| Value::binary_op(BinaryOp::Add, &sum?, &square?));
+                let variance =
+                    Value::binary_op(BinaryOp::Divide, &sum.unwrap()?, &count(elements.len() - 1))?;
+                match self {
+                    Self::Stdev => Self::Sqrt.apply(&[variance])?,
+                    _ => variance,
+                }
+            }
+            (Self::Min, []) => sorted()?
+                .first()
+                .map_or(Value::Number(f64::NAN), |&x| x.clone()),
+            (Self::Max, []) => sorted()?
+                .last()
+                .map_or(Value::Number(f64::NAN), |&x| x.clone()),
+            (Self::Sort, []) => Value::List(sorted()?.into_iter().cloned().collect()),
+            _ => unreachable!("only called for builtins that take a list"),
+        })
+    }
+
+    /// The value a fraction `p` of the way through a sorted list, interpolating
+    /// linearly between neighbouring elements.
+    fn quantile(sorted: &[&Value], p: &Value, count: impl Fn(usize) -> Value) -> Result<Value> {
+        let position = Value::binary_op(BinaryOp::DotProduct, &count(sorted.len() - 1), p)?;
+        let below = position.as_number()?.floor() as usize;
+        let fraction = Value::binary_op(BinaryOp::Subtract, &position, &count(below))?;
+        if fraction.as_number()? == 0.0 {
+            return Ok(sorted[below].clone());
+        }
+        let gap = Value::binary_op(BinaryOp::Subtract, sorted[below + 1], sorted[below])?;
+        let step = Value::binary_op(BinaryOp::DotProduct, &fraction, &gap)?;
+        Value::binary_op(BinaryOp::Add, sorted[below], &step)
+    }
+
+    /// Applies a builtin to a matrix and the rest of its arguments.
+    fn apply_matrix(&self, m: &Matrix, rest: &[Value]) -> Result<Value> {
+        let list = |x: Vec<f64>| Value::List(x.into_iter().map(Value::Number).collect());
+        Ok(match (self, rest) {
+            (Self::Transpose, []) => Value::Matrix(m.transpose()),
+            (Self::Det, []) => Value::Number(m.determinant()?),
+            (Self::Inverse, []) => Value::Matrix(m.inverse()?),
+            (Self::Linsolve, [Value::List(b)]) => {
+                let b = b.iter().map(Value::as_number).collect::<Result<Vec<_>>>()?;
+                list(m.solve(&b)?)
+            }
+            (Self::Linsolve, [b]) => bail!("Expected a list but got a {}", b.type_name()),
+            (Self::Eigenvalues, []) => {
+                Value::List(m.eigenvalues()?.into_iter().map(Value::complex).collect())
+            }
+            _ => unreachable!("arity is checked before applying a builtin"),
+        })
+    }
+
+    /// The real numbers to order the elements of a list by, which must all be real
+    /// numbers of the same dimension.
+    fn sort_keys(&self, elements: &[Value]) -> Result<Vec<f64>> {
+        let dimension = elements.first().map(Value::dimension).unwrap_or_default();
+        elements
+            .iter()
+            .map(|element| match element {
+                Value::Complex(_) => bail!("Cannot take the {self} of complex numbers"),
+                Value::Point(..) => bail!("Cannot take the {self} of points"),
+                _ if element.dimension() != dimension => {
+                    bail!("Cannot compare {} and {}", dimension, element.dimension())
+                }
+                _ => element.as_number(),
+            })
+            .collect()
+    }
+
+    /// Reads an argument of a builtin that is only defined for real numbers without units.
+    fn real_argument(&self, x: &Value) -> Result<f64> {
+        match x {
+            Value::Complex(_) => bail!("Cannot apply {self} to a complex number"),
+            Value::Quantity(x) => bail!("Cannot apply {self} to a number in {}", x.dimension),
+            _ => x.as_number(),
+        }
+    }
+
+    /// Applies a builtin to a real number, which gives a complex result
+    /// where the real function is undefined.
+    fn apply_real(&self, x: f64) -> Value {
+        Value::Number(match self {
+            Self::Sqrt if x < 0.0 => return Value::complex(Complex::new(0.0, (-x).sqrt())),
+            Self::Ln if x < 0.0 => return self.apply_complex(Complex::from(x)),
+            // the same values as C's `casin` and `cacos` on the branch cuts
+            Self::Asin | Self::Acos if x.abs() > 1.0 => {
+                let asin = Complex::new(
+                    x.signum() * std::f64::consts::FRAC_PI_2,
+                    (x.abs() + (x * x - 1.0).sqrt()).ln(),
+                );
+                return Value::complex(match self {
+                    Self::Asin => asin,
+                    _ => Complex::from(std::f64::consts::FRAC_PI_2) - asin,
+                });
+            }
+            Self::Sqrt => x.sqrt(),
+            Self::Abs => x.abs(),
+            Self::Sin => x.sin(),
+            Self::Cos => x.cos(),
+            Self::Tan => x.tan(),
+            Self::Asin => x.asin(),
+            Self::Acos => x.acos(),
+            Self::Atan => x.atan(),
+            Self::Exp => x.exp(),
+            Self::Ln => x.ln(),
+            Self::Re | Self::Conj => x,
+            Self::Im => 0.0,
+            Self::Arg => Complex::from(x).arg(),
+            Self::Gamma => special::gamma(x),
+            Self::Lgamma => special::lgamma(x),
+            Self::Erf => special::erf(x),
+            Self::Atan2
+            | Self::Simplify
+            | Self::Ode
+            | Self::Sum
+            | Self::Prod
+            | Self::Int
+            | Self::Root
+            | Self::Argmin
+            | Self::Argmax
+            | Self::Map
+            | Self::Filter
+            | Self::Fold
+            | Self::Length
+            | Self::Total
+            | Self::Mean
+            | Self::Median
+            | Self::Variance
+            | Self::Stdev
+            | Self::Quantile
+            | Self::Min
+            | Self::Max
+            | Self::Sort
+            | Self::Transpose
+            | Self::Det
+            | Self::Inverse
+            | Self::Linsolve
+            | Self::Eigenvalues
+            | Self::Beta
+            | Self::NormPdf
+            | Self::NormCdf
+            | Self::NormInv
+            | Self::BinoPdf
+            | Self::BinoCdf
+            | Self::BinoInv
+            | Self::PoissPdf
+            | Self::PoissCdf
+            | Self::PoissInv
+            | Self::UnifPdf
+            | Self::UnifCdf
+            | Self::UnifInv
+            | Self::TPdf
+            | Self::TCdf
+            | Self::TInv
+            | Self::Chi2Pdf
+            | Self::Chi2Cdf
+            | Self::Chi2Inv => unreachable!(),
+        })
+    }
+
+    /// Applies a builtin to an exact number, falling back to floats when the
+    /// result is irrational.
+    fn apply_rational(&self, x: &Rational) -> Value {
+        match self {
+            Self::Sqrt => match x.sqrt() {
+                Some(root) => Value::Rational(root),
+                None => self.apply_real(x.to_f64()),
+            },
+            Self::Abs => Value::Rational(x.abs()),
+            Self::Re | Self::Conj => Value::Rational(x.clone()),
+            Self::Im => Value::Rational(Rational::from_integer(0)),
+            _ => self.apply_real(x.to_f64()),
+        }
+    }
+
+    /// Applies a builtin to a number with units, where only functions that
+    /// make sense for any unit are allowed.
+    fn apply_quantity(&self, x: &Quantity) -> Result<Value> {
+        let Quantity {
+            value,
+            dimension,
+            unit,
+        } = x.clone();
+        Ok(match self {
+            Self::Sqrt => match dimension.root(2) {
+                Some(root) => Value::quantity(value.sqrt(), root, None),
+                None => bail!("Cannot take the square root of {dimension}"),
+            },
+            Self::Abs => Value::quantity(value.abs(), dimension, unit),
+            Self::Re | Self::Conj => Value::Quantity(x.clone()),
+            Self::Im => Value::quantity(0.0, dimension, unit),
+            _ => bail!("Cannot apply {self} to a number in {dimension}"),
+        })
+    }
+
+    /// Applies a builtin to real dual numbers, carrying along the exact derivative.
+    pub fn apply_dual(&self, args: &[Dual]) -> Dual {
+        let x = args[0];
+        match self {
+            Self::Sqrt => x.sqrt(),
+            Self::Abs => x.abs(),
+            Self::Sin => x.sin(),
+            Self::Cos => x.cos(),
+            Self::Tan => x.tan(),
+            Self::Asin => x.asin(),
+            Self::Acos => x.acos(),
+            Self::Atan => x.atan(),
+            Self::Atan2 => x.atan2(args[1]),
+            Self::Exp => x.exp(),
+            Self::Ln => x.ln(),
+            Self::Re | Self::Conj | Self::Simplify => x,
+            Self::Im => Dual::constant(0.0),
+            Self::Arg => Dual::constant(Complex::from(x.value).arg()),
+            Self::Gamma => x.gamma(),
+            Self::Lgamma => x.lgamma(),
+            Self::Beta => x.beta(args[1]),
+            Self::Erf => x.erf(),
+            Self::Ode
+            | Self::Sum
+            | Self::Prod
+            | Self::Int
+            | Self::Root
+            | Self::Argmin
+            | Self::Argmax
+            | Self::Map
+            | Self::Filter
+            | Self::Fold
+            | Self::Length
+            | Self::Total
+            | Self::Mean
+            | Self::Median
+            | Self::Variance
+            | Self::Stdev
+            | Self::Quantile
+            | Self::Min
+            | Self::Max
+            | Self::Sort
+            | Self::Transpose
+            | Self::Det
+            | Self::Inverse
+            | Self::Linsolve
+            | Self::Eigenvalues
+            | Self::NormPdf
+            | Self::NormCdf
+            | Self::NormInv
+            | Self::BinoPdf
+            | Self::BinoCdf
+            | Self::BinoInv
+            | Self::PoissPdf
+            | Self::PoissCdf
+            | Self::PoissInv
+            | Self::UnifPdf
+            | Self::UnifCdf
+            | Self::UnifInv
+            | Self::TPdf
+            | Self::TCdf
+            | Self::TInv
+            | Self::Chi2Pdf
+            | Self::Chi2Cdf
+            | Self::Chi2Inv => unreachable!(),
+        }
+    }
+
+    fn apply_complex(&self, z: Complex) -> Value {
+        Value::complex(match self {
+            Self::Sqrt => z.sqrt(),
+            Self::Abs => return Value::Number(z.abs()),
+            Self::Sin => z.sin(),
+            Self::Cos => z.cos(),
+            Self::Tan => z.tan(),
+            Self::Asin => z.asin(),
+            Self::Acos => z.acos(),
+            Self::Atan => z.atan(),
+            Self::Exp => z.exp(),
+            Self::Ln => z.ln(),
+            Self::Re => return Value::Number(z.re),
+            Self::Im => return Value::Number(z.im),
+            Self::Arg => return Value::Number(z.arg()),
+            Self::Conj => z.conj(),
+            Self::Gamma
+            | Self::Lgamma
+            | Self::Beta
+            | Self::Erf
+            | Self::Atan2
+            | Self::Simplify
+            | Self::Ode
+            | Self::Sum
+            | Self::Prod
+            | Self::Int
+            | Self::Root
+            | Self::Argmin
+            | Self::Argmax
+            | Self::Map
+            | Self::Filter
+            | Self::Fold
+            | Self::Length
+            | Self::Total
+            | Self::Mean
+            | Self::Median
+            | Self::Variance
+            | Self::Stdev
+            | Self::Quantile
+            | Self::Min
+            | Self::Max
+            | Self::Sort
+            | Self::Transpose
+            | Self::Det
+            | Self::Inverse
+            | Self::Linsolve
+            | Self::Eigenvalues
+            | Self::NormPdf
+            | Self::NormCdf
+            | Self::NormInv
+            | Self::BinoPdf
+            | Self::BinoCdf
+            | Self::BinoInv
+            | Self::PoissPdf
+            | Self::PoissCdf
+            | Self::PoissInv
+            | Self::UnifPdf
+            | Self::UnifCdf
+            | Self::UnifInv
+            | Self::TPdf
+            | Self::TCdf
+            | Self::TInv
+            | Self::Chi2Pdf
+            | Self::Chi2Cdf
+            | Self::Chi2Inv => unreachable!(),
+        })
+    }
+}
+
+impl BuiltinConstant {
+    pub fn value(&self) -> Value {
+        match self {
+            Self::I => Value::Complex(Complex::I),
+            Self::Inf => Value::Number(f64::INFINITY),
+        }
+    }
 }
 
 impl std::fmt::Display for BuiltinFunction {
@@ -22,7 +687,73 @@ impl std::fmt::Display for BuiltinFunction {
             Self::Sin => "sin",
             Self::Cos => "cos",
             Self::Tan => "tan",
+            Self::Asin => "asin",
+            Self::Acos => "acos",
             Self::Atan => "atan",
+            Self::Atan2 => "atan2",
+            Self::Exp => "exp",
+            Self::Ln => "ln",
+            Self::Re => "re",
+            Self::Im => "im",
+            Self::Arg => "arg",
+            Self::Conj => "conj",
+            Self::Gamma => "gamma",
+            Self::Lgamma => "lgamma",
+            Self::Beta => "beta",
+            Self::Erf => "erf",
+            Self::Simplify => "simplify",
+            Self::Ode => "ode",
+            Self::Sum => "sum",
+            Self::Prod => "prod",
+            Self::Int => "int",
+            Self::Root => "root",
+            Self::Argmin => "argmin",
+            Self::Argmax => "argmax",
+            Self::Map => "map",
+            Self::Filter => "filter",
+            Self::Fold => "fold",
+            Self::Length => "length",
+            Self::Total => "total",
+            Self::Mean => "mean",
+            Self::Median => "median",
+            Self::Variance => "variance",
+            Self::Stdev => "stdev",
+            Self::Quantile => "quantile",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Sort => "sort",
+            Self::Transpose => "transpose",
+            Self::Det => "det",
+            Self::Inverse => "inverse",
+            Self::Linsolve => "linsolve",
+            Self::Eigenvalues => "eigenvalues",
+            Self::NormPdf => "normpdf",
+            Self::NormCdf => "normcdf",
+            Self::NormInv => "norminv",
+            Self::BinoPdf => "binopdf",
+            Self::BinoCdf => "binocdf",
+            Self::BinoInv => "binoinv",
+            Self::PoissPdf => "poisspdf",
+            Self::PoissCdf => "poisscdf",
+            Self::PoissInv => "poissinv",
+            Self::UnifPdf => "unifpdf",
+            Self::UnifCdf => "unifcdf",
+            Self::UnifInv => "unifinv",
+            Self::TPdf => "tpdf",
+            Self::TCdf => "tcdf",
+            Self::TInv => "tinv",
+            Self::Chi2Pdf => "chi2pdf",
+            Self::Chi2Cdf => "chi2cdf",
+            Self::Chi2Inv => "chi2inv",
+        })
+    }
+}
+
+impl std::fmt::Display for BuiltinConstant {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::I => "i",
+            Self::Inf => "inf",
         })
     }
 }
@@ -0,0 +1,942 @@
+use std::{cell::RefCell, collections::HashMap, fmt};
+
+use anyhow::{Result, bail};
+use ecow::EcoString;
+use strum::IntoEnumIterator;
+
+use crate::{
+    builtins::{BuiltinConstant, BuiltinFunction},
+    parse::{ArgList, BinaryOp, Expr, ExprKind, Field, Ident, TopLevelItem},
+    run::DifferentialEquation,
+    span::{Span, SpannedError, bail_at},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    /// A real or complex number.
+    Number,
+    Point,
+    List(Box<Type>),
+    Matrix,
+    /// A function value, whose result type is only known once it is called.
+    Function,
+    /// The type of a function parameter that is only known once the function is called.
+    Unknown,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Number => write!(f, "number"),
+            Self::Point => write!(f, "point"),
+            Self::List(element) => write!(f, "list of {element}s"),
+            Self::Matrix => write!(f, "matrix"),
+            Self::Function => write!(f, "function"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Binding {
+    Value(Type),
+    Function {
+        args: ArgList<Ident>,
+        body: Expr,
+    },
+    Builtin(BuiltinFunction),
+    /// A sequence of numbers, with the indices of its base cases so far and whether
+    /// its recursive definition was given yet.
+    Sequence {
+        base_cases: Vec<i64>,
+        recursive: bool,
+    },
+}
+
+/// Infers the types of top level items before they are evaluated.
+///
+/// Functions are checked once when they are defined, with parameters of unknown
+/// type, and again for the argument types of every call.
+#[derive(Debug)]
+pub struct TypeChecker {
+    bindings: HashMap<Ident, Binding>,
+    /// Return types of functions that were already checked for some argument types.
+    instances: RefCell<HashMap<(Ident, Vec<Type>), Type>>,
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        let mut bindings = HashMap::new();
+        for builtin in BuiltinFunction::iter() {
+            bindings.insert(
+                EcoString::from(builtin.to_string()),
+                Binding::Builtin(builtin),
+            );
+        }
+        for constant in BuiltinConstant::iter() {
+            let name = EcoString::from(constant.to_string());
+            bindings.insert(name, Binding::Value(Type::Number));
+        }
+        Self {
+            bindings,
+            instances: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn check_item(&mut self, item: &TopLevelItem) -> Result<()> {
+        match item {
+            TopLevelItem::Expression(expr) => {
+                self.check(expr, &HashMap::new())?;
+            }
+            TopLevelItem::Assignment { name, body } => {
+                if self.bindings.contains_key(name) {
+                    bail!("Cannot define variable '{name}' as this name is already bound");
+                }
+                let ty = self.check(body, &HashMap::new())?;
+                self.bindings.insert(name.clone(), Binding::Value(ty));
+            }
+            // a function calling itself is a sequence, like `a(n) := a(n-1) + a(n-2)`
+            TopLevelItem::FunctionDef { name, args, body }
+                if self.is_sequence(name)
+                    || body.any(
+                        &|expr| matches!(&expr.kind, ExprKind::Call { func, .. } if func == name),
+                    ) =>
+            {
+                self.check_recursive_definition(name, args, body)?;
+            }
+            TopLevelItem::FunctionDef { name, args, body } => {
+                if self.bindings.contains_key(name) {
+                    bail!("Cannot define function '{name}' as this name is already bound");
+                }
+                for arg in args.iter() {
+                    if self.bindings.contains_key(arg) {
+                        bail!("Cannot use argument '{arg}' as this name is already bound")
+                    }
+                }
+                let env = args.iter().map(|arg| (arg.clone(), Type::Unknown));
+                self.check(body, &env.collect())?;
+                let (args, body) = (args.clone(), body.clone());
+                self.bindings
+                    .insert(name.clone(), Binding::Function { args, body });
+            }
+            TopLevelItem::BaseCase { name, index, body } => {
+                match self.check(body, &HashMap::new())? {
+                    Type::Number | Type::Unknown => {}
+                    ty => bail_at!(body.span, "Terms of a sequence must be numbers, not {ty}s"),
+                }
+                match self.bindings.get_mut(name) {
+                    Some(Binding::Sequence { base_cases, .. }) if base_cases.contains(index) => {
+                        bail!("Base case {name}({index}) is already defined")
+                    }
+                    Some(Binding::Sequence { base_cases, .. }) => base_cases.push(*index),
+                    Some(_) => {
+                        bail!("Cannot define sequence '{name}' as this name is already bound")
+                    }
+                    None => {
+                        let sequence = Binding::Sequence {
+                            base_cases: vec![*index],
+                            recursive: false,
+                        };
+                        self.bindings.insert(name.clone(), sequence);
+                    }
+                }
+            }
+            TopLevelItem::DifferentialEquation {
+                equations,
+                initial_points,
+            } => {
+                let independent = match equations.len() {
+                    1 => DifferentialEquation::INDEPENDENT_SCALAR,
+                    2 => DifferentialEquation::INDEPENDENT_SYSTEM,
+                    _ => bail!("Only systems of up to two differential equations are supported"),
+                };
+                for (name, _) in equations.iter() {
+                    if name == independent {
+                        bail!("Cannot use '{name}' as both the independent and dependent variable");
+                    }
+                    if equations.iter().filter(|(other, _)| other == name).count() > 1 {
+                        bail!("Variable '{name}' has more than one differential equation");
+                    }
+                }
+                for name in equations
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .chain([independent])
+                {
+                    if self.bindings.contains_key(name) {
+                        bail!("Cannot use variable '{name}' as this name is already bound")
+                    }
+                }
+                let mut env = HashMap::new();
+                env.insert(Ident::from(independent), Type::Number);
+                for (name, _) in equations.iter() {
+                    env.insert(name.clone(), Type::Number);
+                }
+                for (name, body) in equations.iter() {
+                    match self.check(body, &env)? {
+                        Type::Number | Type::Unknown => {}
+                        ty => bail_at!(body.span, "{name}' must be a number but is a {ty}"),
+                    }
+                }
+                for point in initial_points.iter() {
+                    match self.check(point, &HashMap::new())? {
+                        Type::Point => {}
+                        Type::List(element) if matches!(*element, Type::Point | Type::Unknown) => {}
+                        ty => bail_at!(point.span, "Initial condition must be a point, not a {ty}"),
+                    }
+                }
+            }
+            TopLevelItem::Solve {
+                name,
+                equations,
+                unknowns,
+                start,
+            } => {
+                let ty = self.check_solve(equations, unknowns, start.as_ref())?;
+                if let Some(name) = name {
+                    if self.bindings.contains_key(name) {
+                        bail!("Cannot define variable '{name}' as this name is already bound");
+                    }
+                    self.bindings.insert(name.clone(), Binding::Value(ty));
+                }
+            }
+            TopLevelItem::Fit { data, model } => {
+                for parameter in self.check_fit(data, model)? {
+                    self.bindings
+                        .insert(parameter, Binding::Value(Type::Number));
+                }
+            }
+            // settings only affect evaluation, so they are handled by the interpreter
+            TopLevelItem::Settings(_) => {}
+            TopLevelItem::WithSettings { item, .. } => self.check_item(item)?,
+        }
+        Ok(())
+    }
+
+    /// Whether `name` is bound to a sequence, which is defined by base cases and a
+    /// recursive definition rather than like other functions.
+    pub fn is_sequence(&self, name: &Ident) -> bool {
+        matches!(self.bindings.get(name), Some(Binding::Sequence { .. }))
+    }
+
+    /// Checks `a(n) := ...`, the recursive definition of a sequence whose base cases
+    /// may be given before or after it.
+    fn check_recursive_definition(
+        &mut self,
+        name: &Ident,
+        args: &ArgList<Ident>,
+        body: &Expr,
+    ) -> Result<()> {
+        let base_cases = match self.bindings.get(name) {
+            Some(Binding::Sequence {
+                base_cases,
+                recursive: false,
+            }) => base_cases.clone(),
+            Some(_) => bail!("Cannot define function '{name}' as this name is already bound"),
+            None => Vec::new(),
+        };
+        let [index] = args.as_slice() else {
+            bail!("Only sequences of one index can be defined recursively, like a(n) := 2*a(n-1)");
+        };
+        if self.bindings.contains_key(index) {
+            bail!("Cannot use argument '{index}' as this name is already bound")
+        }
+        // the definition refers to the sequence itself
+        let sequence = Binding::Sequence {
+            base_cases,
+            recursive: true,
+        };
+        self.bindings.insert(name.clone(), sequence);
+        match self.check(body, &HashMap::from([(index.clone(), Type::Number)]))? {
+            Type::Number | Type::Unknown => Ok(()),
+            ty => bail_at!(body.span, "Terms of a sequence must be numbers, not {ty}s"),
+        }
+    }
+
+    /// Infers the type of calling the function bound to `func` with arguments of the given types.
+    pub fn call_type(&self, func: &Ident, args: &[Type]) -> Result<Type> {
+        let key = (func.clone(), args.to_vec());
+        if let Some(ty) = self.instances.borrow().get(&key) {
+            return Ok(ty.clone());
+        }
+        let ty = match self.bindings.get(func) {
+            Some(Binding::Builtin(builtin)) if builtin.function_arity().is_some() => {
+                bail!("'{builtin}' takes a function as its first argument")
+            }
+            Some(Binding::Builtin(
+                builtin @ (BuiltinFunction::Sum | BuiltinFunction::Prod | BuiltinFunction::Int),
+            )) => bail!("'{builtin}' binds a variable and cannot be called with values"),
+            Some(Binding::Builtin(BuiltinFunction::Simplify)) if args.len() == 1 => args[0].clone(),
+            Some(Binding::Builtin(builtin)) if args.contains(&Type::Function) => {
+                bail!("Cannot apply {builtin} to a function")
+            }
+            Some(Binding::Builtin(builtin)) if builtin.takes_list() => {
+                if builtin.arity() != args.len() {
+                    bail!(
+                        "Cannot pass {} arguments to a function taking {} arguments",
+                        args.len(),
+                        builtin.arity(),
+                    );
+                }
+                let element = match &args[0] {
+                    Type::List(element) => (**element).clone(),
+                    Type::Unknown => Type::Unknown,
+                    ty => bail!("Expected a list but got a {ty}"),
+                };
+                // like `quantile(L, p)`, whose other arguments are numbers
+                if let Some(ty) = args[1..]
+                    .iter()
+                    .find(|ty| !matches!(ty, Type::Number | Type::Unknown))
+                {
+                    bail!("Cannot apply {builtin} to a {ty}")
+                }
+                match builtin {
+                    BuiltinFunction::Length => Type::Number,
+                    BuiltinFunction::Total | BuiltinFunction::Mean => element,
+                    _ if element == Type::Point => {
+                        bail!("Cannot apply {builtin} to a list of points")
+                    }
+                    BuiltinFunction::Sort => Type::List(Box::new(Type::Number)),
+                    _ => Type::Number,
+                }
+            }
+            Some(Binding::Builtin(builtin)) if builtin.takes_matrix() => {
+                if builtin.arity() != args.len() {
+                    bail!(
+                        "Cannot pass {} arguments to a function taking {} arguments",
+                        args.len(),
+                        builtin.arity(),
+                    );
+                }
+                match args {
+                    [Type::Matrix | Type::Unknown, ..] => {}
+                    [ty, ..] => bail!("Expected a matrix but got a {ty}"),
+                    [] => unreachable!(),
+                }
+                match (builtin, args) {
+                    (BuiltinFunction::Det, _) => Type::Number,
+                    (BuiltinFunction::Transpose | BuiltinFunction::Inverse, _) => Type::Matrix,
+                    (BuiltinFunction::Linsolve, [_, b]) => match b {
+                        Type::List(element)
+                            if matches!(**element, Type::Number | Type::Unknown) =>
+                        {
+                            Type::List(Box::new(Type::Number))
+                        }
+                        Type::Unknown => Type::List(Box::new(Type::Number)),
+                        ty => bail!("Expected a list of numbers but got a {ty}"),
+                    },
+                    _ => Type::List(Box::new(Type::Number)),
+                }
+            }
+            Some(Binding::Builtin(builtin)) => {
+                if builtin.arity() != args.len() {
+                    bail!(
+                        "Cannot pass {} arguments to a function taking {} arguments",
+                        args.len(),
+                        builtin.arity(),
+                    );
+                }
+                match args {
+                    [Type::Point] => bail!("Cannot apply {builtin} to a point"),
+                    [Type::Matrix] => bail!("Cannot apply {builtin} to a matrix"),
+                    [Type::List(element)] if **element == Type::Point => {
+                        bail!("Cannot apply {builtin} to a list of points")
+                    }
+                    [ty] => ty.clone(),
+                    // builtins of several arguments only take numbers
+                    _ => match args
+                        .iter()
+                        .find(|ty| !matches!(ty, Type::Number | Type::Unknown))
+                    {
+                        Some(ty) => bail!("Cannot apply {builtin} to a {ty}"),
+                        None => Type::Number,
+                    },
+                }
+            }
+            Some(Binding::Function {
+                args: arg_names,
+                body,
+            }) => {
+                if arg_names.len() != args.len() {
+                    bail!(
+                        "Cannot pass {} arguments to a function taking {} arguments",
+                        args.len(),
+                        arg_names.len(),
+                    );
+                }
+                let env = std::iter::zip(arg_names.iter().cloned(), args.iter().cloned());
+                self.check(body, &env.collect())?
+            }
+            Some(Binding::Sequence { .. }) => match args {
+                [Type::Number | Type::Unknown] => Type::Number,
+                [ty] => bail!("The index of sequence '{func}' must be a number, not a {ty}"),
+                _ => bail!(
+                    "Cannot pass {} arguments to a sequence, which takes an index",
+                    args.len()
+                ),
+            },
+            // what a function value returns is only known once it is evaluated
+            Some(Binding::Value(Type::Function)) => Type::Unknown,
+            Some(Binding::Value(_)) => bail!("Cannot call '{func}' as it is not a function"),
+            None => bail!("Function '{func}' not defined"),
+        };
+        self.instances.borrow_mut().insert(key, ty.clone());
+        Ok(ty)
+    }
+
+    pub fn check(&self, expr: &Expr, env: &HashMap<Ident, Type>) -> Result<Type> {
+        Ok(match &expr.kind {
+            ExprKind::Lit(_) | ExprKind::Rational(_) | ExprKind::Quantity(..) => Type::Number,
+            ExprKind::Variable(name) => match env.get(name) {
+                Some(ty) => ty.clone(),
+                None => match self.bindings.get(name) {
+                    Some(Binding::Value(ty)) => ty.clone(),
+                    Some(Binding::Builtin(
+                        builtin @ (BuiltinFunction::Sum
+                        | BuiltinFunction::Prod
+                        | BuiltinFunction::Int),
+                    )) => bail_at!(
+                        expr.span,
+                        "'{builtin}' binds a variable and cannot be passed as a function"
+                    ),
+                    Some(Binding::Builtin(builtin)) if builtin.function_arity().is_some() => {
+                        bail_at!(
+                            expr.span,
+                            "'{builtin}' takes a function and cannot be passed as one"
+                        )
+                    }
+                    Some(
+                        Binding::Function { .. } | Binding::Builtin(_) | Binding::Sequence { .. },
+                    ) => Type::Function,
+                    None => bail_at!(expr.span, "Binding '{name}' not defined"),
+                },
+            },
+            ExprKind::Call { func, args } => {
+                match self.bindings.get(func) {
+                    Some(Binding::Builtin(builtin)) if builtin.function_arity().is_some() => {
+                        return self.check_function_builtin(expr, *builtin, args, env);
+                    }
+                    Some(Binding::Builtin(
+                        builtin @ (BuiltinFunction::Sum
+                        | BuiltinFunction::Prod
+                        | BuiltinFunction::Int),
+                    )) => return self.check_big_operator(expr, *builtin, args, env),
+                    _ => {}
+                }
+                let arg_exprs = args;
+                let args = args
+                    .iter()
+                    .map(|arg| self.check(arg, env))
+                    .collect::<Result<Vec<_>>>()?;
+                match env.get(func) {
+                    Some(Type::Function | Type::Unknown) => return Ok(Type::Unknown),
+                    Some(_) => bail_at!(expr.span, "Cannot call '{func}' as it is not a function"),
+                    None => {}
+                }
+                if let Some(Binding::Function { args: names, body }) = self.bindings.get(func)
+                    && names.len() == args.len()
+                {
+                    self.check_function_arguments(names, body, arg_exprs, &args, env)?;
+                }
+                match self.call_type(func, &args) {
+                    Ok(ty) => ty,
+                    Err(err) if err.is::<SpannedError>() => {
+                        bail_at!(
+                            expr.span,
+                            "Calling '{func}' with these arguments fails: {err}"
+                        )
+                    }
+                    Err(err) => bail_at!(expr.span, "{err}"),
+                }
+            }
+            ExprKind::UnOp { arg, .. } => match self.check(arg, env)? {
+                Type::Function => bail_at!(expr.span, "Expected a number but got a function"),
+                ty => ty,
+            },
+            ExprKind::BinOp { op, left, right } => {
+                let left = self.check(left, env)?;
+                let right = self.check(right, env)?;
+                match binary_op_type(*op, &left, &right) {
+                    Some(ty) => ty,
+                    None => {
+                        let op = match op {
+                            BinaryOp::Add => "add",
+                            BinaryOp::Subtract => "subtract",
+                            BinaryOp::DotProduct => "multiply",
+                            BinaryOp::Divide => "divide",
+                            BinaryOp::Power => "exponentiate",
+                        };
+                        bail_at!(expr.span, "Cannot {op} a {left} and a {right}")
+                    }
+                }
+            }
+            ExprKind::Point(x, y) => {
+                for coordinate in [x, y] {
+                    match self.check(coordinate, env)? {
+                        Type::Number | Type::Unknown => {}
+                        ty => bail_at!(
+                            coordinate.span,
+                            "Coordinates of a point must be numbers, not {ty}s"
+                        ),
+                    }
+                }
+                Type::Point
+            }
+            ExprKind::List(elements) => {
+                let mut element_type = Type::Unknown;
+                for element in elements {
+                    match (
+                        list_element_type(self.check(element, env)?, element.span)?,
+                        &element_type,
+                    ) {
+                        (Type::Unknown, _) => {}
+                        (ty, Type::Unknown) => element_type = ty,
+                        (ty, expected) if ty != *expected => {
+                            bail_at!(element.span, "Cannot put a {ty} in a list of {expected}s")
+                        }
+                        _ => {}
+                    }
+                }
+                match element_type {
+                    // a list of rows is a matrix
+                    Type::List(_) => Type::Matrix,
+                    ty => Type::List(Box::new(ty)),
+                }
+            }
+            ExprKind::Range {
+                first,
+                second,
+                last,
+            } => {
+                for bound in [Some(first), second.as_ref(), Some(last)]
+                    .into_iter()
+                    .flatten()
+                {
+                    match self.check(bound, env)? {
+                        Type::Number | Type::Unknown => {}
+                        ty => bail_at!(bound.span, "Bounds of a range must be numbers, not {ty}s"),
+                    }
+                }
+                Type::List(Box::new(Type::Number))
+            }
+            ExprKind::Comprehension { body, var, list } => {
+                if env.contains_key(var) || self.bindings.contains_key(var) {
+                    bail_at!(
+                        expr.span,
+                        "Cannot use variable '{var}' as this name is already bound"
+                    );
+                }
+                let element = match self.check(list, env)? {
+                    Type::List(element) => *element,
+                    Type::Matrix => Type::List(Box::new(Type::Number)),
+                    Type::Unknown => Type::Unknown,
+                    ty => bail_at!(list.span, "Expected a list but got a {ty}"),
+                };
+                let mut env = env.clone();
+                env.insert(var.clone(), element);
+                match list_element_type(self.check(body, &env)?, body.span)? {
+                    Type::List(_) => Type::Matrix,
+                    ty => Type::List(Box::new(ty)),
+                }
+            }
+            ExprKind::Index { list, index } => {
+                match self.check(index, env)? {
+                    Type::Number | Type::Unknown => {}
+                    ty => bail_at!(index.span, "Indices must be numbers, not {ty}s"),
+                }
+                match self.check(list, env)? {
+                    Type::List(element) => *element,
+                    // the elements of a matrix are its rows
+                    Type::Matrix => Type::List(Box::new(Type::Number)),
+                    Type::Unknown => Type::Unknown,
+                    ty => bail_at!(list.span, "Cannot index into a {ty}"),
+                }
+            }
+            ExprKind::Access { arg, field } => match self.check(arg, env)? {
+                Type::Point => Type::Number,
+                Type::List(element) if matches!(*element, Type::Point | Type::Unknown) => {
+                    Type::List(Box::new(Type::Number))
+                }
+                Type::Unknown => Type::Unknown,
+                ty => bail_at!(expr.span, "Cannot access .{field} of a {ty}"),
+            },
+            ExprKind::Derivative { .. } => {
+                unreachable!("derivatives are expanded before checking")
+            }
+            ExprKind::Convert { arg, unit } => match self.check(arg, env)? {
+                ty @ (Type::Number | Type::Unknown) => ty,
+                Type::List(element) if matches!(*element, Type::Number | Type::Unknown) => {
+                    Type::List(element)
+                }
+                ty => bail_at!(expr.span, "Cannot convert a {ty} to {unit}"),
+            },
+            ExprKind::Lambda { args, body } => {
+                for arg in args.iter() {
+                    if env.contains_key(arg) || self.bindings.contains_key(arg) {
+                        bail_at!(
+                            expr.span,
+                            "Cannot use argument '{arg}' as this name is already bound"
+                        );
+                    }
+                }
+                let mut env = env.clone();
+                env.extend(args.iter().map(|arg| (arg.clone(), Type::Unknown)));
+                self.check(body, &env)?;
+                Type::Function
+            }
+            ExprKind::Let { bindings, body } => {
+                let mut env = env.clone();
+                for (name, value) in bindings.iter() {
+                    if env.contains_key(name) || self.bindings.contains_key(name) {
+                        bail_at!(
+                            expr.span,
+                            "Cannot use variable '{name}' as this name is already bound"
+                        );
+                    }
+                    let ty = self.check(value, &env)?;
+                    env.insert(name.clone(), ty);
+                }
+                self.check(body, &env)?
+            }
+        })
+    }
+
+    /// Infers the type of calling the function that `f` evaluates to with arguments of
+    /// the given types, which is unknown unless `f` is a lambda or names a function.
+    fn function_call_type(
+        &self,
+        f: &Expr,
+        args: &[Type],
+        env: &HashMap<Ident, Type>,
+    ) -> Result<Type> {
+        match &f.kind {
+            ExprKind::Lambda {
+                args: arg_names,
+                body,
+            } => {
+                self.check(f, env)?;
+                if arg_names.len() != args.len() {
+                    bail_at!(
+                        f.span,
+                        "Cannot pass {} arguments to a function taking {} arguments",
+                        args.len(),
+                        arg_names.len(),
+                    );
+                }
+                let mut env = env.clone();
+                env.extend(std::iter::zip(
+                    arg_names.iter().cloned(),
+                    args.iter().cloned(),
+                ));
+                self.check(body, &env)
+            }
+            ExprKind::Variable(name)
+                if !env.contains_key(name)
+                    && matches!(
+                        self.bindings.get(name),
+                        Some(
+                            Binding::Function { .. }
+                                | Binding::Builtin(_)
+                                | Binding::Sequence { .. }
+                        )
+                    ) =>
+            {
+                match self.call_type(name, args) {
+                    Ok(ty) => Ok(ty),
+                    Err(err) if err.is::<SpannedError>() => {
+                        bail_at!(f.span, "Calling '{name}' with these arguments fails: {err}")
+                    }
+                    Err(err) => bail_at!(f.span, "{err}"),
+                }
+            }
+            _ => match self.check(f, env)? {
+                Type::Function | Type::Unknown => Ok(Type::Unknown),
+                ty => bail_at!(f.span, "Expected a function but got a {ty}"),
+            },
+        }
+    }
+
+    /// Checks how the body of a function calls the parameters it is passed functions
+    /// for, which their type alone doesn't tell, like `f(g) := g(1, 2)` called as
+    /// `f(x -> x)`.
+    fn check_function_arguments(
+        &self,
+        names: &ArgList<Ident>,
+        body: &Expr,
+        args: &ArgList<Expr>,
+        types: &[Type],
+        env: &HashMap<Ident, Type>,
+    ) -> Result<()> {
+        let body_env: HashMap<_, _> =
+            std::iter::zip(names.iter().cloned(), types.iter().cloned()).collect();
+        let calls = RefCell::new(Vec::new());
+        body.any(&|expr| {
+            if let ExprKind::Call { func, args } = &expr.kind {
+                calls.borrow_mut().push((func.clone(), args.clone()));
+            }
+            false
+        });
+        for ((name, f), ty) in std::iter::zip(names.iter(), args.iter()).zip(types) {
+            if *ty != Type::Function {
+                continue;
+            }
+            for (_, call_args) in calls.borrow().iter().filter(|(func, _)| func == name) {
+                // arguments that depend on variables bound inside the body are unknown here
+                let call_types: Vec<_> = call_args
+                    .iter()
+                    .map(|arg| self.check(arg, &body_env).unwrap_or(Type::Unknown))
+                    .collect();
+                self.function_call_type(f, &call_types, env)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a builtin taking a function as its first argument. `map(f, L)`,
+    /// `filter(f, L)` and `fold(f, x0, L)` call it with the elements of a list, and
+    /// the others with numbers, like `ode(f, x0, y0, x)` where `f` must take two
+    /// numbers and return a number.
+    fn check_function_builtin(
+        &self,
+        expr: &Expr,
+        builtin: BuiltinFunction,
+        args: &ArgList<Expr>,
+        env: &HashMap<Ident, Type>,
+    ) -> Result<Type> {
+        let [f, rest @ ..] = args.as_slice() else {
+            unreachable!("argument lists are never empty")
+        };
+        if args.len() != builtin.arity() {
+            bail_at!(
+                expr.span,
+                "Cannot pass {} arguments to a function taking {} arguments",
+                args.len(),
+                builtin.arity(),
+            );
+        }
+        let types = rest
+            .iter()
+            .map(|arg| self.check(arg, env))
+            .collect::<Result<Vec<_>>>()?;
+        // the type of the elements of the list argument of `map`, `filter` and `fold`
+        let element = |i: usize| -> Result<Type> {
+            match &types[i] {
+                Type::List(element) => Ok(element.as_ref().clone()),
+                Type::Unknown => Ok(Type::Unknown),
+                ty => bail_at!(rest[i].span, "Expected a list but got a {ty}"),
+            }
+        };
+        match builtin {
+            BuiltinFunction::Map => match self.function_call_type(f, &[element(0)?], env)? {
+                ty @ (Type::List(_) | Type::Function) => {
+                    bail_at!(
+                        f.span,
+                        "Cannot map to a {ty}, as lists only contain numbers or points"
+                    )
+                }
+                ty => Ok(Type::List(Box::new(ty))),
+            },
+            BuiltinFunction::Filter => match self.function_call_type(f, &[element(0)?], env)? {
+                Type::Number | Type::Unknown => Ok(types[0].clone()),
+                ty => bail_at!(f.span, "'{f}' must return a number but returns a {ty}"),
+            },
+            BuiltinFunction::Fold => {
+                self.function_call_type(f, &[types[0].clone(), element(1)?], env)
+            }
+            _ => {
+                for (arg, ty) in std::iter::zip(rest, &types) {
+                    if !matches!(ty, Type::Number | Type::Unknown) {
+                        bail_at!(arg.span, "Expected a number but got a {ty}");
+                    }
+                }
+                let arity = builtin.function_arity().expect("takes a function");
+                match self.function_call_type(f, &vec![Type::Number; arity], env)? {
+                    Type::Number | Type::Unknown => Ok(Type::Number),
+                    ty => bail_at!(f.span, "'{f}' must return a number but returns a {ty}"),
+                }
+            }
+        }
+    }
+
+    /// Checks the equations of `solve(...)`, where the unknowns are numbers, and infers the
+    /// type of its result: a number or a point for each solution, in a list unless a
+    /// single start is given.
+    fn check_solve(
+        &self,
+        equations: &ArgList<(Expr, Expr)>,
+        unknowns: &ArgList<Ident>,
+        start: Option<&Expr>,
+    ) -> Result<Type> {
+        let solution = match (equations.len(), unknowns.len()) {
+            (1, 1) => Type::Number,
+            (2, 2) => Type::Point,
+            (n, m) if n != m => bail!("Cannot solve {n} equations for {m} unknowns"),
+            _ => bail!("Only systems of up to two equations can be solved"),
+        };
+        for (i, unknown) in unknowns.iter().enumerate() {
+            if self.bindings.contains_key(unknown) {
+                bail!("Cannot use variable '{unknown}' as this name is already bound");
+            }
+            if unknowns[..i].contains(unknown) {
+                bail!("Cannot solve for '{unknown}' twice");
+            }
+        }
+        let env = unknowns
+            .iter()
+            .map(|unknown| (unknown.clone(), Type::Number))
+            .collect();
+        for side in equations.iter().flat_map(|(left, right)| [left, right]) {
+            match self.check(side, &env)? {
+                Type::Number | Type::Unknown => {}
+                ty => bail_at!(side.span, "Expected a number but got a {ty}"),
+            }
+        }
+        let Some(start) = start else {
+            return Ok(Type::List(Box::new(solution)));
+        };
+        Ok(match self.check(start, &HashMap::new())? {
+            Type::Unknown => solution,
+            ty if ty == solution => solution,
+            Type::List(element) if matches!(*element, Type::Unknown) || *element == solution => {
+                Type::List(Box::new(solution))
+            }
+            ty => bail_at!(
+                start.span,
+                "Expected a {solution} or a list of them to start from, but got a {ty}"
+            ),
+        })
+    }
+
+    /// Checks `data ~ model`, where the parameters to fit are the variables of the
+    /// model that are not bound yet, and returns them.
+    fn check_fit(&self, data: &Expr, model: &Expr) -> Result<Vec<Ident>> {
+        match self.check(data, &HashMap::new())? {
+            Type::List(element) if matches!(*element, Type::Number | Type::Unknown) => {}
+            Type::Unknown => {}
+            ty => bail_at!(
+                data.span,
+                "Expected a list of numbers to fit but got a {ty}"
+            ),
+        }
+        let parameters: Vec<Ident> = model
+            .variables()
+            .into_iter()
+            .filter(|name| !self.bindings.contains_key(name))
+            .collect();
+        if parameters.is_empty() {
+            bail_at!(model.span, "Expected unknown parameters to fit in {model}");
+        }
+        let env = parameters
+            .iter()
+            .map(|parameter| (parameter.clone(), Type::Number))
+            .collect();
+        match self.check(model, &env)? {
+            Type::Number | Type::Unknown => {}
+            Type::List(element) if matches!(*element, Type::Number | Type::Unknown) => {}
+            ty => bail_at!(model.span, "Expected a model giving numbers but got a {ty}"),
+        }
+        Ok(parameters)
+    }
+
+    /// Checks `sum(k, a, b, f)`, `prod(k, a, b, f)` or `int(f, t, a, b)`, where the
+    /// bound variable is a number in `f`.
+    fn check_big_operator(
+        &self,
+        expr: &Expr,
+        builtin: BuiltinFunction,
+        args: &ArgList<Expr>,
+        env: &HashMap<Ident, Type>,
+    ) -> Result<Type> {
+        let Some([var, from, to, body]) = builtin.bound_args(args) else {
+            bail_at!(
+                expr.span,
+                "Cannot pass {} arguments to a function taking 4 arguments",
+                args.len()
+            );
+        };
+        let ExprKind::Variable(name) = &var.kind else {
+            bail_at!(var.span, "Expected the name of a variable but got {var}");
+        };
+        if env.contains_key(name) || self.bindings.contains_key(name) {
+            bail_at!(
+                var.span,
+                "Cannot use variable '{name}' as this name is already bound"
+            );
+        }
+        for bound in [from, to] {
+            match self.check(bound, env)? {
+                Type::Number | Type::Unknown => {}
+                ty => bail_at!(bound.span, "Expected a number but got a {ty}"),
+            }
+        }
+        let mut env = env.clone();
+        env.insert(name.clone(), Type::Number);
+        match (builtin, self.check(body, &env)?) {
+            (BuiltinFunction::Prod, ty @ (Type::Point | Type::List(_))) => {
+                bail_at!(body.span, "Cannot take the product of a {ty}")
+            }
+            (BuiltinFunction::Int, ty @ (Type::Point | Type::List(_))) => {
+                bail_at!(body.span, "Cannot integrate a {ty}")
+            }
+            (_, ty) => Ok(ty),
+        }
+    }
+}
+
+/// The result type of a binary operator, mirroring `Value::binary_op`.
+/// Checks the type of an element of a list, which is a row of numbers for a matrix.
+fn list_element_type(ty: Type, span: Span) -> Result<Type> {
+    match ty {
+        Type::List(row) if !matches!(*row, Type::Number | Type::Unknown) => {
+            bail_at!(
+                span,
+                "Rows of a matrix must be lists of numbers, not {row}s"
+            )
+        }
+        Type::List(_) => Ok(Type::List(Box::new(Type::Number))),
+        Type::Matrix => bail_at!(span, "Lists cannot contain matrices"),
+        Type::Function => bail_at!(span, "Lists cannot contain functions"),
+        ty => Ok(ty),
+    }
+}
+
+fn binary_op_type(op: BinaryOp, left: &Type, right: &Type) -> Option<Type> {
+    use Type::*;
+    Some(match (left, right) {
+        (Matrix, Matrix) => match op {
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::DotProduct => Matrix,
+            BinaryOp::Divide | BinaryOp::Power => return None,
+        },
+        // matrices multiply points and lists of points or numbers
+        (Matrix, ty @ (Point | List(_))) if op == BinaryOp::DotProduct => ty.clone(),
+        (Matrix, Number) => match op {
+            BinaryOp::DotProduct | BinaryOp::Divide | BinaryOp::Power => Matrix,
+            BinaryOp::Add | BinaryOp::Subtract => return None,
+        },
+        (Number, Matrix) if op == BinaryOp::DotProduct => Matrix,
+        (Matrix, Unknown) | (Unknown, Matrix) => Unknown,
+        (Matrix, _) | (_, Matrix) => return None,
+        (List(left), List(right)) => List(Box::new(binary_op_type(op, left, right)?)),
+        (List(left), right) => List(Box::new(binary_op_type(op, left, right)?)),
+        (left, List(right)) => List(Box::new(binary_op_type(op, left, right)?)),
+        (Function, _) | (_, Function) => return None,
+        (Unknown, _) | (_, Unknown) => Unknown,
+
+        (Number, Number) => Number,
+        (Point, Point) => match op {
+            BinaryOp::Add | BinaryOp::Subtract => Point,
+            BinaryOp::DotProduct => Number,
+            BinaryOp::Divide | BinaryOp::Power => return None,
+        },
+        (Point, Number) => match op {
+            BinaryOp::DotProduct | BinaryOp::Divide => Point,
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Power => return None,
+        },
+        (Number, Point) => match op {
+            BinaryOp::DotProduct => Point,
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Divide | BinaryOp::Power => {
+                return None;
+            }
+        },
+    })
+}
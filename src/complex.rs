@@ -0,0 +1,170 @@
+use std::{fmt, ops};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const I: Self = Self::new(0.0, 1.0);
+
+    pub const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn exp(self) -> Self {
+        let r = self.re.exp();
+        Self::new(r * self.im.cos(), r * self.im.sin())
+    }
+
+    /// Principal branch of the natural logarithm.
+    pub fn ln(self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    /// Principal value of `self^exponent`, taking `0^w` to be 1 for `w = 0` and 0 otherwise.
+    pub fn pow(self, exponent: Self) -> Self {
+        // whole powers by repeated multiplication, so that `i^2` is exactly -1
+        if exponent.im == 0.0 && exponent.re.fract() == 0.0 && exponent.re.abs() <= 64.0 {
+            let mut result = Self::from(1.0);
+            for _ in 0..exponent.re.abs() as u32 {
+                result = result * self;
+            }
+            return match exponent.re < 0.0 {
+                true => Self::from(1.0) / result,
+                false => result,
+            };
+        }
+        if self == Self::from(0.0) {
+            return Self::from(if exponent == Self::from(0.0) {
+                1.0
+            } else {
+                0.0
+            });
+        }
+        (exponent * self.ln()).exp()
+    }
+
+    /// Principal square root, with a non-negative real part.
+    pub fn sqrt(self) -> Self {
+        let r = self.abs();
+        let re = ((r + self.re) / 2.0).sqrt();
+        let im = ((r - self.re) / 2.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    pub fn sin(self) -> Self {
+        Self::new(
+            self.re.sin() * self.im.cosh(),
+            self.re.cos() * self.im.sinh(),
+        )
+    }
+
+    pub fn cos(self) -> Self {
+        Self::new(
+            self.re.cos() * self.im.cosh(),
+            -self.re.sin() * self.im.sinh(),
+        )
+    }
+
+    pub fn tan(self) -> Self {
+        self.sin() / self.cos()
+    }
+
+    /// Principal branch of the inverse sine, `-i ln(iz + sqrt(1 - z^2))`.
+    pub fn asin(self) -> Self {
+        let one = Self::from(1.0);
+        -Self::I * (Self::I * self + (one - self * self).sqrt()).ln()
+    }
+
+    /// Principal branch of the inverse cosine, `π/2 - asin(z)`.
+    pub fn acos(self) -> Self {
+        Self::from(std::f64::consts::FRAC_PI_2) - self.asin()
+    }
+
+    /// Principal branch of the inverse tangent, `(ln(1 - iz) - ln(1 + iz)) * i/2`.
+    pub fn atan(self) -> Self {
+        let iz = Self::I * self;
+        let one = Self::from(1.0);
+        ((one - iz).ln() - (one + iz).ln()) * Self::new(0.0, 0.5)
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+}
+
+impl ops::Add for Complex {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl ops::Sub for Complex {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl ops::Mul for Complex {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl ops::Div for Complex {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        let denominator = rhs.re * rhs.re + rhs.im * rhs.im;
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / denominator,
+            (self.im * rhs.re - self.re * rhs.im) / denominator,
+        )
+    }
+}
+
+impl ops::Neg for Complex {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Self { re, im } = *self;
+        let imaginary = |f: &mut fmt::Formatter, im: f64| match im {
+            1.0 => write!(f, "i"),
+            im => write!(f, "{im}i"),
+        };
+        if re == 0.0 {
+            if im < 0.0 {
+                write!(f, "-")?;
+            }
+            return imaginary(f, im.abs());
+        }
+        write!(f, "{re} {} ", if im < 0.0 { '-' } else { '+' })?;
+        imaginary(f, im.abs())
+    }
+}
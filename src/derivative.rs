@@ -0,0 +1,662 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::{
+    builtins::BuiltinFunction,
+    distributions::{Distribution, DistributionFunction},
+    parse::{ArgList, BinaryOp, Expr, ExprKind, Ident, UnaryOp},
+    simplify::fold_constants,
+    span::{Span, bail_at},
+    value::Value,
+};
+
+/// What a name is bound to, as far as rewriting expressions is concerned.
+pub enum Definition<'a> {
+    Value(&'a Value),
+    Builtin(BuiltinFunction),
+    Function {
+        args: &'a ArgList<Ident>,
+        body: &'a Expr,
+    },
+    Sequence,
+}
+
+/// Looks up what a name is bound to.
+pub type Definitions<'a> = dyn Fn(&Ident) -> Option<Definition<'a>> + 'a;
+
+/// Replaces every `d/dx` and `f'(x)` in `expr` by the derivative it stands for.
+pub fn expand_derivatives(expr: &Expr, definitions: &Definitions) -> Result<Expr> {
+    let expanded = expr.try_map_children(|expr| expand_derivatives(expr, definitions))?;
+    match &expanded.kind {
+        ExprKind::Derivative { arg, var } => derivative(arg, var, definitions),
+        ExprKind::Call { func, args } if func.ends_with('\'') => {
+            derivative_of_function(func, args, expr.span, definitions)
+        }
+        _ => Ok(expanded),
+    }
+}
+
+/// Differentiates `expr` with respect to `var`, which must not contain `d/dx` or `f'(x)`.
+///
+/// Calls to user functions are differentiated through their bodies, so the
+/// result only refers to builtins and the functions' parameters.
+pub fn derivative(expr: &Expr, var: &str, definitions: &Definitions) -> Result<Expr> {
+    let d = |expr: &Expr| derivative(expr, var, definitions);
+    let span = expr.span;
+    Ok(match &expr.kind {
+        ExprKind::Lit(_) | ExprKind::Rational(_) | ExprKind::Quantity(..) => lit(0.0, span),
+        ExprKind::Variable(name) => lit(if name == var { 1.0 } else { 0.0 }, span),
+        ExprKind::UnOp {
+            op: UnaryOp::Plus,
+            arg,
+        } => d(arg)?,
+        ExprKind::UnOp {
+            op: UnaryOp::Negate,
+            arg,
+        } => neg(d(arg)?),
+        ExprKind::UnOp {
+            op: UnaryOp::Degrees,
+            arg,
+        } => match d(arg)? {
+            inner if is_lit(&inner, 0.0) => inner,
+            inner => Expr::un_op(UnaryOp::Degrees, inner),
+        },
+        ExprKind::BinOp { op, left, right } => {
+            let (l, r) = (left.as_ref().clone(), right.as_ref().clone());
+            match op {
+                BinaryOp::Add => add(d(left)?, d(right)?),
+                BinaryOp::Subtract => sub(d(left)?, d(right)?),
+                BinaryOp::DotProduct => add(mul(d(left)?, r), mul(l, d(right)?)),
+                BinaryOp::Divide => div(
+                    sub(mul(d(left)?, r.clone()), mul(l, d(right)?)),
+                    pow(r, lit(2.0, span)),
+                ),
+                BinaryOp::Power if !depends_on(right, var) => {
+                    let exponent_minus_one = sub(r.clone(), lit(1.0, span));
+                    mul(mul(r, pow(l, exponent_minus_one)), d(left)?)
+                }
+                BinaryOp::Power if !depends_on(left, var) => {
+                    mul(mul(expr.clone(), call(BuiltinFunction::Ln, l)), d(right)?)
+                }
+                // d/dx u^v = u^v * (v' ln(u) + v u' / u)
+                BinaryOp::Power => mul(
+                    expr.clone(),
+                    add(
+                        mul(d(right)?, call(BuiltinFunction::Ln, l.clone())),
+                        div(mul(r, d(left)?), l),
+                    ),
+                ),
+            }
+        }
+        // constant even if the function can't be differentiated, like `fold(f, 0, [1, 2])`
+        ExprKind::Call { .. } if !depends_on(expr, var) => lit(0.0, span),
+        ExprKind::Call { func, .. } if let Some(builtin) = big_operator(func, definitions) => {
+            big_operator_derivative(expr, builtin, var, definitions)?
+        }
+        ExprKind::Call { func, args } => {
+            let mut result = lit(0.0, span);
+            // chain rule, one term per argument
+            for (i, arg) in args.iter().enumerate() {
+                let inner = d(arg)?;
+                if is_lit(&inner, 0.0) {
+                    continue;
+                }
+                let outer = partial_derivative(func, args, i, span, definitions)?;
+                result = add(result, mul(outer, inner));
+            }
+            result
+        }
+        ExprKind::Point(x, y) => Expr::point(d(x)?, d(y)?),
+        ExprKind::List(elements) => Expr::new(
+            ExprKind::List(elements.iter().map(d).collect::<Result<_>>()?),
+            span,
+        ),
+        ExprKind::Range { .. } if !depends_on(expr, var) => lit(0.0, span),
+        ExprKind::Range { .. } => bail_at!(span, "Cannot differentiate a range depending on {var}"),
+        ExprKind::Comprehension { list, .. } if depends_on(list, var) => bail_at!(
+            span,
+            "Cannot differentiate a comprehension over a list depending on {var}"
+        ),
+        // the variable of a comprehension is bound inside it
+        ExprKind::Comprehension { var: bound, .. } if bound == var => lit(0.0, span),
+        ExprKind::Comprehension { body, var, list } => Expr::new(
+            ExprKind::Comprehension {
+                body: Box::new(d(body)?),
+                var: var.clone(),
+                list: list.clone(),
+            },
+            span,
+        ),
+        ExprKind::Access { arg, field } => Expr::new(
+            ExprKind::Access {
+                arg: Box::new(d(arg)?),
+                field: *field,
+            },
+            span,
+        ),
+        ExprKind::Index { index, .. } if depends_on(index, var) => {
+            bail_at!(
+                span,
+                "Cannot differentiate {expr}, as its index depends on {var}"
+            )
+        }
+        ExprKind::Index { list, .. } if !depends_on(list, var) => lit(0.0, span),
+        ExprKind::Index { list, index } => Expr::new(
+            ExprKind::Index {
+                list: Box::new(d(list)?),
+                index: index.clone(),
+            },
+            span,
+        ),
+        ExprKind::Derivative { .. } => unreachable!("derivatives are expanded from the inside out"),
+        // the derivative generally has a different dimension than `arg`
+        ExprKind::Convert { arg, .. } => d(arg)?,
+        ExprKind::Lambda { .. } => bail_at!(span, "Cannot differentiate a function"),
+        // each binding may refer to the ones before it, so they are substituted last to first
+        ExprKind::Let { bindings, body } => {
+            let mut body = body.as_ref().clone();
+            for (name, value) in bindings.iter().rev() {
+                body = substitute(&body, &HashMap::from([(name.clone(), value.clone())]));
+            }
+            d(&body)?
+        }
+    })
+}
+
+/// The builtin called, if it binds a variable like `sum` and `int`.
+fn big_operator(func: &Ident, definitions: &Definitions) -> Option<BuiltinFunction> {
+    match definitions(func) {
+        Some(Definition::Builtin(
+            builtin @ (BuiltinFunction::Sum | BuiltinFunction::Prod | BuiltinFunction::Int),
+        )) => Some(builtin),
+        _ => None,
+    }
+}
+
+/// `d/dx sum(k, a, b, f) = sum(k, a, b, d/dx f)`, the product rule for `prod`,
+/// and the Leibniz integral rule for `int`.
+fn big_operator_derivative(
+    expr: &Expr,
+    builtin: BuiltinFunction,
+    var: &str,
+    definitions: &Definitions,
+) -> Result<Expr> {
+    let span = expr.span;
+    let ExprKind::Call { func, args } = &expr.kind else {
+        unreachable!()
+    };
+    let Some([bound_var, from, to, body]) = builtin.bound_args(args) else {
+        bail_at!(
+            span,
+            "Cannot pass {} arguments to a function taking 4 arguments",
+            args.len()
+        );
+    };
+    // the variable is bound inside, so the result does not depend on an outer variable of that name
+    if matches!(&bound_var.kind, ExprKind::Variable(name) if name == var) {
+        return Ok(lit(0.0, span));
+    }
+    let call = |builtin: BuiltinFunction, body: Expr| {
+        let args = match builtin {
+            BuiltinFunction::Int => vec![body, bound_var.clone(), from.clone(), to.clone()],
+            _ => vec![bound_var.clone(), from.clone(), to.clone(), body],
+        };
+        let kind = ExprKind::Call {
+            func: builtin.to_string().into(),
+            args: ArgList::from_vec(args),
+        };
+        Expr::new(kind, span)
+    };
+    if let BuiltinFunction::Int = builtin {
+        let ExprKind::Variable(name) = &bound_var.kind else {
+            bail_at!(
+                bound_var.span,
+                "Expected the name of a variable but got {bound_var}"
+            );
+        };
+        // d/dx int(f, t, a, b) = f(b) b' - f(a) a' + int(d/dx f, t, a, b)
+        let at = |bound: &Expr| {
+            let substitutions = HashMap::from([(name.clone(), bound.clone())]);
+            substitute(body, &substitutions)
+        };
+        let mut result = match derivative(body, var, definitions)? {
+            inner if is_lit(&inner, 0.0) => inner,
+            inner => call(BuiltinFunction::Int, inner),
+        };
+        let (d_from, d_to) = (
+            derivative(from, var, definitions)?,
+            derivative(to, var, definitions)?,
+        );
+        if !is_lit(&d_to, 0.0) {
+            result = add(result, mul(at(to), d_to));
+        }
+        if !is_lit(&d_from, 0.0) {
+            result = sub(result, mul(at(from), d_from));
+        }
+        return Ok(result);
+    }
+    if depends_on(from, var) || depends_on(to, var) {
+        bail_at!(
+            span,
+            "Cannot differentiate {func} with bounds depending on {var}"
+        );
+    }
+    let inner = derivative(body, var, definitions)?;
+    if is_lit(&inner, 0.0) {
+        return Ok(inner);
+    }
+    Ok(match builtin {
+        BuiltinFunction::Sum => call(BuiltinFunction::Sum, inner),
+        // d/dx prod(f) = prod(f) * sum(f' / f)
+        _ => mul(
+            expr.clone(),
+            call(BuiltinFunction::Sum, div(inner, body.clone())),
+        ),
+    })
+}
+
+/// `f'(x)`, `f''(x)` and so on, derivatives of a function of one argument.
+fn derivative_of_function(
+    func: &Ident,
+    args: &ArgList<Expr>,
+    span: Span,
+    definitions: &Definitions,
+) -> Result<Expr> {
+    let name = Ident::from(func.trim_end_matches('\''));
+    if args.len() != 1 {
+        bail_at!(
+            span,
+            "Can only take the derivative {func} of a function of one argument"
+        );
+    }
+    let (var, mut result) = match definitions(&name) {
+        Some(Definition::Function { args, body }) if args.len() == 1 => {
+            (args[0].clone(), body.clone())
+        }
+        Some(Definition::Function { .. }) => bail_at!(
+            span,
+            "Can only take the derivative {func} of a function of one argument"
+        ),
+        Some(Definition::Builtin(builtin)) if builtin.arity() != 1 => bail_at!(
+            span,
+            "Can only take the derivative {func} of a function of one argument"
+        ),
+        Some(Definition::Builtin(builtin)) => {
+            // a name that cannot appear in the input, so it never captures another variable
+            let var = Ident::from("_");
+            let variable = Expr::new(ExprKind::Variable(var.clone()), span);
+            (var, call(builtin, variable))
+        }
+        Some(Definition::Value(_)) => {
+            bail_at!(span, "Cannot call '{name}' as it is not a function")
+        }
+        Some(Definition::Sequence) => bail_at!(
+            span,
+            "Cannot differentiate the sequence '{name}', which is only defined at integers"
+        ),
+        None => bail_at!(span, "Function '{name}' not defined"),
+    };
+    for _ in 0..func.len() - name.len() {
+        result = derivative(&result, &var, definitions)?;
+    }
+    Ok(substitute(
+        &result,
+        &HashMap::from([(var, args[0].clone())]),
+    ))
+}
+
+/// The partial derivative of `func` in its `i`th argument, evaluated at `args`.
+fn partial_derivative(
+    func: &Ident,
+    args: &ArgList<Expr>,
+    i: usize,
+    span: Span,
+    definitions: &Definitions,
+) -> Result<Expr> {
+    match definitions(func) {
+        Some(Definition::Builtin(builtin)) if builtin.arity() != args.len() => bail_at!(
+            span,
+            "Cannot pass {} arguments to a function taking {} arguments",
+            args.len(),
+            builtin.arity(),
+        ),
+        Some(Definition::Builtin(builtin)) => builtin_derivative(builtin, args, i, span),
+        Some(Definition::Function {
+            args: arg_names,
+            body,
+        }) => {
+            if arg_names.len() != args.len() {
+                bail_at!(
+                    span,
+                    "Cannot pass {} arguments to a function taking {} arguments",
+                    args.len(),
+                    arg_names.len(),
+                );
+            }
+            let partial = derivative(body, &arg_names[i], definitions)?;
+            let substitutions = std::iter::zip(arg_names.iter().cloned(), args.iter().cloned());
+            Ok(substitute(&partial, &substitutions.collect()))
+        }
+        Some(Definition::Value(_)) => {
+            bail_at!(span, "Cannot call '{func}' as it is not a function")
+        }
+        Some(Definition::Sequence) => bail_at!(
+            span,
+            "Cannot differentiate the sequence '{func}', which is only defined at integers"
+        ),
+        None => bail_at!(span, "Function '{func}' not defined"),
+    }
+}
+
+/// The partial derivative of a builtin in its `i`th argument, evaluated at `args`.
+fn builtin_derivative(
+    builtin: BuiltinFunction,
+    args: &ArgList<Expr>,
+    i: usize,
+    span: Span,
+) -> Result<Expr> {
+    use BuiltinFunction::*;
+    let one = || lit(1.0, span);
+    let u = args[0].clone();
+    // 1 / sqrt(1 - u^2)
+    let inverse_sine = || {
+        let one_minus_square = sub(one(), pow(u.clone(), lit(2.0, span)));
+        div(one(), call(Sqrt, one_minus_square))
+    };
+    Ok(match builtin {
+        Sqrt => div(one(), mul(lit(2.0, span), call(Sqrt, u))),
+        Abs => div(u.clone(), call(Abs, u)),
+        Sin => call(Cos, u),
+        Cos => neg(call(Sin, u)),
+        Tan => div(one(), pow(call(Cos, u), lit(2.0, span))),
+        Asin => inverse_sine(),
+        Acos => neg(inverse_sine()),
+        Atan => div(one(), add(one(), pow(u, lit(2.0, span)))),
+        // d/dy atan2(y, x) = x / (x^2 + y^2) and d/dx atan2(y, x) = -y / (x^2 + y^2)
+        Atan2 => {
+            let (y, x) = (u, args[1].clone());
+            let squared_norm = add(
+                pow(x.clone(), lit(2.0, span)),
+                pow(y.clone(), lit(2.0, span)),
+            );
+            match i {
+                0 => div(x, squared_norm),
+                _ => neg(div(y, squared_norm)),
+            }
+        }
+        Exp => call(Exp, u),
+        Ln => div(one(), u),
+        // 2/sqrt(pi) exp(-u^2)
+        Erf => mul(
+            lit(2.0 / std::f64::consts::PI.sqrt(), span),
+            call(Exp, neg(pow(u, lit(2.0, span)))),
+        ),
+        Simplify => one(),
+        _ if i == 0
+            && let Some((distribution, function)) = builtin.distribution() =>
+        {
+            distribution_derivative(distribution, function, args, span)?
+        }
+        Re | Im | Arg | Conj | Gamma | Lgamma | Beta | Ode | Sum | Prod | Int | Root | Argmin
+        | Argmax | Map | Filter | Fold | Length | Total | Mean | Median | Variance | Stdev
+        | Quantile | Min | Max | Sort | Transpose | Det | Inverse | Linsolve | Eigenvalues
+        | NormPdf | NormCdf | NormInv | BinoPdf | BinoCdf | BinoInv | PoissPdf | PoissCdf
+        | PoissInv | UnifPdf | UnifCdf | UnifInv | TPdf | TCdf | TInv | Chi2Pdf | Chi2Cdf
+        | Chi2Inv => {
+            bail_at!(span, "Cannot differentiate {builtin}")
+        }
+    })
+}
+
+/// The derivative of a function of a distribution in its first argument, which is
+/// zero for the steps of discrete distributions.
+fn distribution_derivative(
+    distribution: Distribution,
+    function: DistributionFunction,
+    args: &ArgList<Expr>,
+    span: Span,
+) -> Result<Expr> {
+    let pdf = |x: Expr| {
+        let mut pdf_args = ArgList::from_head(x);
+        args[1..].iter().for_each(|arg| pdf_args.push(arg.clone()));
+        call_with(
+            BuiltinFunction::of_distribution(distribution, DistributionFunction::Pdf),
+            pdf_args,
+            span,
+        )
+    };
+    let u = args[0].clone();
+    Ok(match (distribution, function) {
+        (Distribution::Binomial | Distribution::Poisson, _) => lit(0.0, span),
+        (_, DistributionFunction::Cdf) => pdf(u),
+        // the inverse function rule, 1 / pdf(inv(p))
+        (_, DistributionFunction::Inverse) => {
+            let inverse = BuiltinFunction::of_distribution(distribution, function);
+            div(lit(1.0, span), pdf(call_with(inverse, args.clone(), span)))
+        }
+        (Distribution::Uniform, _) => lit(0.0, span),
+        // -(u - mu) / sigma^2 normpdf(u, mu, sigma)
+        (Distribution::Normal, _) => {
+            let (mean, deviation) = (args[1].clone(), args[2].clone());
+            let slope = div(sub(mean, u.clone()), pow(deviation, lit(2.0, span)));
+            mul(slope, pdf(u))
+        }
+        (Distribution::StudentT | Distribution::ChiSquare, _) => {
+            let builtin = BuiltinFunction::of_distribution(distribution, function);
+            bail_at!(span, "Cannot differentiate {builtin}")
+        }
+    })
+}
+
+/// Replaces the variables in `substitutions` by their expressions, all at once.
+pub fn substitute(expr: &Expr, substitutions: &HashMap<Ident, Expr>) -> Expr {
+    match &expr.kind {
+        ExprKind::Variable(name) => substitutions.get(name).unwrap_or(expr).clone(),
+        // the variable of a derivative is bound inside it
+        ExprKind::Derivative { arg, var } => {
+            let (mut vars, arg) = substitute_bound(std::slice::from_ref(var), arg, substitutions);
+            let kind = ExprKind::Derivative {
+                arg: Box::new(arg),
+                var: vars.remove(0),
+            };
+            Expr::new(kind, expr.span)
+        }
+        // as are the parameters of a lambda
+        ExprKind::Lambda { args, body } => {
+            let (args, body) = substitute_bound(args, body, substitutions);
+            let kind = ExprKind::Lambda {
+                args: ArgList::from_vec(args),
+                body: Box::new(body),
+            };
+            Expr::new(kind, expr.span)
+        }
+        // only the body of a comprehension is in the scope of its variable
+        ExprKind::Comprehension { body, var, list } => {
+            let (mut vars, body) = substitute_bound(std::slice::from_ref(var), body, substitutions);
+            let kind = ExprKind::Comprehension {
+                body: Box::new(body),
+                var: vars.remove(0),
+                list: Box::new(substitute(list, substitutions)),
+            };
+            Expr::new(kind, expr.span)
+        }
+        // and of a sum, product or integral, whose bounds are outside it
+        ExprKind::Call { func, args }
+            if let Some(builtin) = [
+                BuiltinFunction::Sum,
+                BuiltinFunction::Prod,
+                BuiltinFunction::Int,
+            ]
+            .into_iter()
+            .find(|builtin| builtin.to_string() == *func)
+                && let Some([var, _, _, body]) = builtin.bound_args(args)
+                && let ExprKind::Variable(name) = &var.kind =>
+        {
+            let (mut names, new_body) =
+                substitute_bound(std::slice::from_ref(name), body, substitutions);
+            let args = args.iter().map(|arg| match arg {
+                _ if std::ptr::eq(arg, var) => {
+                    Expr::new(ExprKind::Variable(names.remove(0)), var.span)
+                }
+                _ if std::ptr::eq(arg, body) => new_body.clone(),
+                _ => substitute(arg, substitutions),
+            });
+            let kind = ExprKind::Call {
+                func: func.clone(),
+                args: ArgList::from_vec(args.collect()),
+            };
+            Expr::new(kind, expr.span)
+        }
+        ExprKind::Let { bindings, .. }
+            if bindings
+                .iter()
+                .any(|(name, _)| substitutions.contains_key(name)) =>
+        {
+            let mut substitutions = substitutions.clone();
+            substitutions.retain(|name, _| bindings.iter().all(|(bound, _)| bound != name));
+            expr.map_children(|expr| substitute(expr, &substitutions))
+        }
+        _ => expr.map_children(|expr| substitute(expr, substitutions)),
+    }
+}
+
+/// Substitutes into `body`, in which `names` are bound, and returns the names it
+/// binds them as. The bound names are not substituted, and one that a substituted
+/// expression mentions is renamed, so that it doesn't capture that mention.
+fn substitute_bound(
+    names: &[Ident],
+    body: &Expr,
+    substitutions: &HashMap<Ident, Expr>,
+) -> (Vec<Ident>, Expr) {
+    let mut inner = substitutions.clone();
+    inner.retain(|name, _| !names.contains(name));
+    let mut used = body.variables();
+    used.extend(inner.values().flat_map(Expr::variables));
+    let mut renamed = Vec::new();
+    for name in names {
+        if !inner.values().any(|value| depends_on(value, name)) {
+            renamed.push(name.clone());
+            continue;
+        }
+        // users cannot write names with underscores, so the new name is never bound elsewhere
+        let fresh = (1..)
+            .map(|i| Ident::from(format!("{name}_{i}")))
+            .find(|fresh| !used.contains(fresh))
+            .expect("there are infinitely many names");
+        used.push(fresh.clone());
+        renamed.push(fresh.clone());
+        inner.insert(
+            name.clone(),
+            Expr::new(ExprKind::Variable(fresh), body.span),
+        );
+    }
+    let body = substitute(body, &inner);
+    (renamed, body)
+}
+
+/// Whether `expr` mentions `var`. Function bodies cannot refer to the caller's
+/// variables, so calls only depend on `var` through their arguments.
+fn depends_on(expr: &Expr, var: &str) -> bool {
+    expr.any(&|expr| matches!(&expr.kind, ExprKind::Variable(name) if name == var))
+}
+
+// Constructors that skip trivial operations, so derivatives don't fill up with
+// terms like `0 * x` and `1 * x`.
+
+fn lit(x: f64, span: Span) -> Expr {
+    Expr::new(ExprKind::Lit(x), span)
+}
+
+fn is_lit(expr: &Expr, value: f64) -> bool {
+    expr.as_lit() == Some(value)
+}
+
+/// `left op right` folded into one literal if both are literals, exactly if either is exact.
+fn fold(op: BinaryOp, left: &Expr, right: &Expr) -> Option<Expr> {
+    fold_constants(&Expr::bin_op(op, left.clone(), right.clone()))
+}
+
+fn call(builtin: BuiltinFunction, arg: Expr) -> Expr {
+    let span = arg.span;
+    call_with(builtin, ArgList::from_head(arg), span)
+}
+
+fn call_with(builtin: BuiltinFunction, args: ArgList<Expr>, span: Span) -> Expr {
+    let kind = ExprKind::Call {
+        func: Ident::from(builtin.to_string()),
+        args,
+    };
+    Expr::new(kind, span)
+}
+
+fn neg(arg: Expr) -> Expr {
+    match arg.kind {
+        ExprKind::Lit(x) => lit(-x, arg.span),
+        ExprKind::Rational(x) => Expr::new(ExprKind::Rational(-x), arg.span),
+        ExprKind::UnOp {
+            op: UnaryOp::Negate,
+            arg,
+        } => *arg,
+        _ => Expr::un_op(UnaryOp::Negate, arg),
+    }
+}
+
+fn add(left: Expr, right: Expr) -> Expr {
+    match (left.as_lit(), right.as_lit()) {
+        (Some(_), Some(_)) if let Some(sum) = fold(BinaryOp::Add, &left, &right) => sum,
+        (Some(0.0), _) => right,
+        (_, Some(0.0)) => left,
+        _ => match right.kind {
+            ExprKind::UnOp {
+                op: UnaryOp::Negate,
+                arg,
+            } => Expr::bin_op(BinaryOp::Subtract, left, *arg),
+            _ => Expr::bin_op(BinaryOp::Add, left, right),
+        },
+    }
+}
+
+fn sub(left: Expr, right: Expr) -> Expr {
+    match (left.as_lit(), right.as_lit()) {
+        (Some(_), Some(_)) if let Some(difference) = fold(BinaryOp::Subtract, &left, &right) => {
+            difference
+        }
+        (Some(0.0), _) => neg(right),
+        (_, Some(0.0)) => left,
+        _ => Expr::bin_op(BinaryOp::Subtract, left, right),
+    }
+}
+
+fn mul(left: Expr, right: Expr) -> Expr {
+    match (left.as_lit(), right.as_lit()) {
+        (Some(_), Some(_)) if let Some(product) = fold(BinaryOp::DotProduct, &left, &right) => {
+            product
+        }
+        (Some(0.0), _) => left,
+        (_, Some(0.0)) => right,
+        (Some(1.0), _) => right,
+        (_, Some(1.0)) => left,
+        (Some(-1.0), _) => neg(right),
+        (_, Some(-1.0)) => neg(left),
+        _ => Expr::bin_op(BinaryOp::DotProduct, left, right),
+    }
+}
+
+fn div(left: Expr, right: Expr) -> Expr {
+    match (left.as_lit(), right.as_lit()) {
+        (Some(0.0), _) => left,
+        (_, Some(1.0)) => left,
+        _ => Expr::bin_op(BinaryOp::Divide, left, right),
+    }
+}
+
+fn pow(base: Expr, exponent: Expr) -> Expr {
+    match (base.as_lit(), exponent.as_lit()) {
+        // a negative base to a fraction is complex, so that stays unfolded
+        (Some(_), Some(_)) if let Some(power) = fold(BinaryOp::Power, &base, &exponent) => power,
+        (_, Some(0.0)) => lit(1.0, base.span),
+        (_, Some(1.0)) => base,
+        _ => Expr::bin_op(BinaryOp::Power, base, exponent),
+    }
+}
@@ -0,0 +1,350 @@
+use std::f64::consts::PI;
+
+use crate::roots::Brent;
+use crate::special::{
+    binomial_term, erfc, incomplete_beta, incomplete_gamma, lgamma, poisson_term,
+};
+
+/// A probability distribution, whose pdf, cdf and inverse cdf are builtins like
+/// `normpdf(x, μ, σ)`, `normcdf(x, μ, σ)` and `norminv(p, μ, σ)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    /// The normal distribution with a mean and a standard deviation.
+    Normal,
+    /// The number of successes in `n` trials that each succeed with probability `p`.
+    Binomial,
+    /// The number of events that happen at a rate `λ`.
+    Poisson,
+    /// The uniform distribution between `a` and `b`.
+    Uniform,
+    /// Student's t distribution with `ν` degrees of freedom.
+    StudentT,
+    /// The chi-square distribution with `k` degrees of freedom.
+    ChiSquare,
+}
+
+/// Which function of a distribution a builtin computes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DistributionFunction {
+    /// The probability density, or the probability of a value for discrete distributions.
+    Pdf,
+    /// The probability of a value of at most `x`.
+    Cdf,
+    /// The smallest value whose cdf is at least `p`.
+    Inverse,
+}
+
+/// Discrete distributions add up their probabilities directly, which is exact for
+/// small arguments, up to this many terms.
+const MAX_DIRECT_TERMS: f64 = 1000.0;
+
+/// How far the cdf of a discrete distribution may fall short of `p` from rounding
+/// and still count as reaching it.
+const DISCRETE_TOLERANCE: f64 = 64.0 * f64::EPSILON;
+
+impl Distribution {
+    /// The number of parameters after the argument, like the mean and standard
+    /// deviation of a normal distribution.
+    pub fn parameters(self) -> usize {
+        match self {
+            Self::Poisson | Self::StudentT | Self::ChiSquare => 1,
+            Self::Normal | Self::Binomial | Self::Uniform => 2,
+        }
+    }
+
+    /// Computes the pdf or cdf at `x`, or the inverse cdf at `x = p`, which are
+    /// undefined for invalid parameters or probabilities.
+    pub fn apply(self, function: DistributionFunction, x: f64, parameters: &[f64]) -> f64 {
+        if !self.is_valid(parameters) || x.is_nan() {
+            return f64::NAN;
+        }
+        match function {
+            DistributionFunction::Pdf => self.pdf(x, parameters),
+            DistributionFunction::Cdf => self.cdf(x, parameters),
+            DistributionFunction::Inverse if !(0.0..=1.0).contains(&x) => f64::NAN,
+            DistributionFunction::Inverse => self.inverse(x, parameters),
+        }
+    }
+
+    /// The derivative of a function of the distribution with respect to `x`, which is
+    /// zero for the steps of discrete distributions.
+    pub fn slope(self, function: DistributionFunction, x: f64, parameters: &[f64]) -> f64 {
+        let pdf = self.apply(DistributionFunction::Pdf, x, parameters);
+        match (self, function, parameters) {
+            (Self::Binomial | Self::Poisson, _, _) => 0.0,
+            (_, DistributionFunction::Cdf, _) => pdf,
+            (_, DistributionFunction::Inverse, _) => {
+                let inverse = self.apply(function, x, parameters);
+                1.0 / self.apply(DistributionFunction::Pdf, inverse, parameters)
+            }
+            (Self::Uniform, _, _) => 0.0,
+            (Self::Normal, _, &[mean, deviation]) => -pdf * (x - mean) / (deviation * deviation),
+            (Self::StudentT, _, &[dof]) => -pdf * (dof + 1.0) * x / (dof + x * x),
+            (Self::ChiSquare, _, &[k]) => pdf * ((0.5 * k - 1.0) / x - 0.5),
+            _ => f64::NAN,
+        }
+    }
+
+    fn is_valid(self, parameters: &[f64]) -> bool {
+        match (self, parameters) {
+            (Self::Normal, &[mean, deviation]) => {
+                mean.is_finite() && deviation > 0.0 && deviation.is_finite()
+            }
+            (Self::Binomial, &[n, p]) => {
+                n >= 0.0 && n.fract() == 0.0 && n.is_finite() && (0.0..=1.0).contains(&p)
+            }
+            (Self::Poisson, &[rate]) => rate >= 0.0 && rate.is_finite(),
+            (Self::Uniform, &[a, b]) => a < b && a.is_finite() && b.is_finite(),
+            (Self::StudentT | Self::ChiSquare, &[k]) => k > 0.0 && k.is_finite(),
+            _ => false,
+        }
+    }
+
+    fn pdf(self, x: f64, parameters: &[f64]) -> f64 {
+        let is_count = x >= 0.0 && x.fract() == 0.0;
+        match (self, parameters) {
+            (Self::Normal, &[mean, deviation]) => {
+                let z = (x - mean) / deviation;
+                (-0.5 * z * z).exp() / (deviation * (2.0 * PI).sqrt())
+            }
+            (Self::Binomial, _) | (Self::Poisson, _) if !is_count => 0.0,
+            (Self::Binomial, &[n, _]) if x > n => 0.0,
+            (Self::Binomial, &[n, p]) if p == 0.0 || p == 1.0 => {
+                f64::from(x == n * p).max(f64::from(p == 0.0 && x == 0.0))
+            }
+            (Self::Binomial, &[n, p]) if n <= MAX_DIRECT_TERMS => {
+                binomial_coefficient(n, x) * p.powf(x) * (1.0 - p).powf(n - x)
+            }
+            (Self::Binomial, &[n, p]) => binomial_term(x, n, p),
+            (Self::Poisson, &[rate]) => poisson_term(x, rate),
+            (Self::Uniform, &[a, b]) => match (a..=b).contains(&x) {
+                true => 1.0 / (b - a),
+                false => 0.0,
+            },
+            (Self::StudentT, &[dof]) => {
+                let log_scale = lgamma(0.5 * (dof + 1.0)) - lgamma(0.5 * dof);
+                let log_density = -0.5 * (dof + 1.0) * (x * x / dof).ln_1p();
+                (log_scale + log_density).exp() / (dof * PI).sqrt()
+            }
+            (Self::ChiSquare, _) if x < 0.0 => 0.0,
+            (Self::ChiSquare, &[k]) if x == 0.0 => match k {
+                _ if k < 2.0 => f64::INFINITY,
+                2.0 => 0.5,
+                _ => 0.0,
+            },
+            (Self::ChiSquare, &[k]) if k >= 2.0 => 0.5 * poisson_term(0.5 * k - 1.0, 0.5 * x),
+            (Self::ChiSquare, &[k]) => {
+                let half = 0.5 * k;
+                ((half - 1.0) * x.ln() - 0.5 * x - half * 2f64.ln() - lgamma(half)).exp()
+            }
+            _ => unreachable!("parameters are checked before applying a distribution"),
+        }
+    }
+
+    fn cdf(self, x: f64, parameters: &[f64]) -> f64 {
+        let count = x.floor();
+        match (self, parameters) {
+            (Self::Normal, &[mean, deviation]) => {
+                0.5 * erfc(-(x - mean) / (deviation * std::f64::consts::SQRT_2))
+            }
+            (Self::Binomial | Self::Poisson, _) if count < 0.0 => 0.0,
+            (Self::Binomial, &[n, _]) if count >= n => 1.0,
+            (Self::Binomial, &[n, p]) if n <= MAX_DIRECT_TERMS => (0..=count as u32)
+                .map(|k| self.pdf(k.into(), &[n, p]))
+                .sum(),
+            (Self::Binomial, &[n, p]) => incomplete_beta(1.0 - p, n - count, count + 1.0),
+            (Self::Poisson, &[0.0]) => 1.0,
+            (Self::Poisson, &[rate]) if count <= MAX_DIRECT_TERMS => (0..=count as u32)
+                .map(|k| self.pdf(k.into(), &[rate]))
+                .sum::<f64>()
+                .min(1.0),
+            (Self::Poisson, &[rate]) => incomplete_gamma(count + 1.0, rate).1,
+            (Self::Uniform, &[a, b]) => ((x - a) / (b - a)).clamp(0.0, 1.0),
+            (Self::StudentT, _) if x == 0.0 => 0.5,
+            (Self::StudentT, &[dof]) => {
+                // the probability of a value at least as far from 0 as `x`
+                let tails = incomplete_beta(dof / (dof + x * x), 0.5 * dof, 0.5);
+                match x > 0.0 {
+                    true => 1.0 - 0.5 * tails,
+                    false => 0.5 * tails,
+                }
+            }
+            (Self::ChiSquare, _) if x <= 0.0 => 0.0,
+            (Self::ChiSquare, &[k]) => incomplete_gamma(0.5 * k, 0.5 * x).0,
+            _ => unreachable!("parameters are checked before applying a distribution"),
+        }
+    }
+
+    fn inverse(self, p: f64, parameters: &[f64]) -> f64 {
+        let cdf = |x| self.cdf(x, parameters);
+        match (self, parameters) {
+            // the normal and t distributions are symmetric, and the lower tail is
+            // where the cdf is precise
+            (Self::Normal, &[mean, deviation]) => {
+                let standard = |x: f64| 0.5 * erfc(-x / std::f64::consts::SQRT_2);
+                mean + deviation * symmetric_inverse(standard, p)
+            }
+            (Self::StudentT, _) => symmetric_inverse(cdf, p),
+            (Self::Uniform, &[a, b]) => a + p * (b - a),
+            (Self::ChiSquare, _) if p == 1.0 => f64::INFINITY,
+            (Self::ChiSquare, &[k]) => continuous_inverse(cdf, p, k),
+            (Self::Binomial, &[n, _]) => discrete_inverse(cdf, p, n),
+            (Self::Poisson, _) if p == 1.0 => f64::INFINITY,
+            (Self::Poisson, &[rate]) => discrete_inverse(cdf, p, rate.ceil()),
+            _ => unreachable!("parameters are checked before applying a distribution"),
+        }
+    }
+}
+
+/// `n! / (k! (n - k)!)`, which is exact while it fits in the mantissa of a float.
+fn binomial_coefficient(n: f64, k: f64) -> f64 {
+    let k = k.min(n - k);
+    // each partial product is itself a binomial coefficient, so the division is exact
+    (1..=k as u32).fold(1.0, |product, i| {
+        product * (n - k + f64::from(i)) / f64::from(i)
+    })
+}
+
+/// Inverts the cdf of a distribution that is symmetric about 0.
+fn symmetric_inverse(cdf: impl Fn(f64) -> f64, p: f64) -> f64 {
+    match p {
+        0.0 => f64::NEG_INFINITY,
+        1.0 => f64::INFINITY,
+        0.5 => 0.0,
+        _ if p > 0.5 => -continuous_inverse(cdf, 1.0 - p, 0.0),
+        _ => continuous_inverse(cdf, p, 0.0),
+    }
+}
+
+/// The `x` where an increasing continuous cdf reaches `p`, bracketed by stepping
+/// away from `start` in doubling steps.
+fn continuous_inverse(cdf: impl Fn(f64) -> f64, p: f64, start: f64) -> f64 {
+    let (mut low, mut high) = (start, start);
+    let mut step = start.abs().max(1.0);
+    if cdf(start) < p {
+        while cdf(high) < p && high.is_finite() {
+            (low, high, step) = (high, high + step, 2.0 * step);
+        }
+    } else {
+        while cdf(low) > p && low.is_finite() {
+            (low, high, step) = (low - step, low, 2.0 * step);
+        }
+    }
+    let root = Brent::default().root(|x| Ok(cdf(x) - p), low, high);
+    root.ok().flatten().unwrap_or(f64::NAN)
+}
+
+/// The smallest count where an increasing discrete cdf reaches `p`, searching
+/// upwards from `high` for a bound and then bisecting.
+fn discrete_inverse(cdf: impl Fn(f64) -> f64, p: f64, mut high: f64) -> f64 {
+    let reaches = |k| cdf(k) >= p * (1.0 - DISCRETE_TOLERANCE);
+    while !reaches(high) {
+        high = 2.0 * high + 1.0;
+    }
+    let mut low = -1.0;
+    while high - low > 1.0 {
+        let middle = ((low + high) / 2.0).floor();
+        match reaches(middle) {
+            true => high = middle,
+            false => low = middle,
+        }
+    }
+    high
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DistributionFunction::{Cdf, Inverse, Pdf};
+
+    fn assert_close(actual: f64, expected: f64) {
+        let error = ((actual - expected) / expected).abs();
+        assert!(
+            error < 1e-14,
+            "{actual} differs from {expected} by {error:.1e}"
+        );
+    }
+
+    #[test]
+    fn normal() {
+        let normal = |function, x, parameters| Distribution::Normal.apply(function, x, parameters);
+        assert_close(normal(Pdf, 0.5, &[1.0, 2.0]), 0.1933340584014246);
+        assert_close(normal(Cdf, 0.5, &[1.0, 2.0]), 0.4012936743170763);
+        assert_close(normal(Cdf, -10.0, &[0.0, 1.0]), 7.619853024160525e-24);
+        assert_close(normal(Inverse, 0.975, &[0.0, 1.0]), 1.959963984540054);
+        assert_close(normal(Inverse, 0.3, &[1.0, 2.0]), -0.04880102541608163);
+        assert_close(normal(Inverse, 1e-10, &[0.0, 1.0]), -6.361340902404057);
+        assert_eq!(normal(Inverse, 0.5, &[3.0, 2.0]), 3.0);
+        assert!(normal(Pdf, 0.0, &[0.0, -1.0]).is_nan());
+    }
+
+    #[test]
+    fn binomial() {
+        let binomial =
+            |function, x, parameters| Distribution::Binomial.apply(function, x, parameters);
+        assert_close(binomial(Pdf, 3.0, &[10.0, 0.3]), 0.266827932);
+        assert_close(binomial(Cdf, 3.0, &[10.0, 0.3]), 0.6496107184000001);
+        assert_close(binomial(Cdf, 3.5, &[10.0, 0.3]), 0.6496107184000001);
+        assert_close(binomial(Pdf, 800.0, &[2000.0, 0.4]), 0.018206738073558878);
+        // the continued fraction takes a hundred steps this close to the mean, each of
+        // which rounds
+        let error = (binomial(Cdf, 790.0, &[2000.0, 0.4]) / 0.3327389100872364 - 1.0).abs();
+        assert!(error < 1e-13, "the cdf is off by {error:.1e}");
+        assert_eq!(binomial(Pdf, 2.5, &[10.0, 0.3]), 0.0);
+        assert_eq!(binomial(Inverse, 0.5, &[10.0, 0.3]), 3.0);
+        assert_eq!(binomial(Inverse, 0.9, &[10.0, 0.3]), 5.0);
+        // the cdf reaches exactly 0.6496107184 at 3, up to rounding
+        assert_eq!(binomial(Inverse, 0.6496107184, &[10.0, 0.3]), 3.0);
+        assert!(binomial(Pdf, 1.0, &[10.5, 0.3]).is_nan());
+    }
+
+    #[test]
+    fn poisson() {
+        let poisson =
+            |function, x, parameters| Distribution::Poisson.apply(function, x, parameters);
+        assert_close(poisson(Pdf, 2.0, &[4.0]), 0.14652511110987343);
+        assert_close(poisson(Cdf, 5.0, &[4.0]), 0.7851303870304052);
+        assert_close(poisson(Cdf, 1500.0, &[1500.0]), 0.5068665119847636);
+        assert_eq!(poisson(Inverse, 0.5, &[4.0]), 4.0);
+        assert_eq!(poisson(Inverse, 0.9, &[4.0]), 7.0);
+        assert_eq!(poisson(Pdf, 0.0, &[0.0]), 1.0);
+        assert_eq!(poisson(Inverse, 1.0, &[4.0]), f64::INFINITY);
+    }
+
+    #[test]
+    fn uniform() {
+        let uniform =
+            |function, x, parameters| Distribution::Uniform.apply(function, x, parameters);
+        assert_close(uniform(Pdf, 3.0, &[2.0, 5.0]), 1.0 / 3.0);
+        assert_close(uniform(Cdf, 3.0, &[2.0, 5.0]), 1.0 / 3.0);
+        assert_close(uniform(Inverse, 0.25, &[2.0, 5.0]), 2.75);
+        assert_eq!(uniform(Pdf, 6.0, &[2.0, 5.0]), 0.0);
+        assert_eq!(uniform(Cdf, 6.0, &[2.0, 5.0]), 1.0);
+        assert!(uniform(Pdf, 3.0, &[5.0, 2.0]).is_nan());
+    }
+
+    #[test]
+    fn student_t() {
+        let t = |function, x, parameters| Distribution::StudentT.apply(function, x, parameters);
+        assert_close(t(Pdf, 1.2, &[10.0]), 0.18566389362670316);
+        assert_close(t(Cdf, -1.5, &[10.0]), 0.0822536632227201);
+        assert_close(t(Cdf, 2.0, &[3.0]), 0.9303370157205784);
+        assert_close(t(Inverse, 0.975, &[10.0]), 2.228138851986274);
+        assert_close(t(Inverse, 0.1, &[3.5]), -1.576576605136405);
+        assert_eq!(t(Cdf, 0.0, &[10.0]), 0.5);
+    }
+
+    #[test]
+    fn chi_square() {
+        let chi_square =
+            |function, x, parameters| Distribution::ChiSquare.apply(function, x, parameters);
+        assert_close(chi_square(Pdf, 1.3, &[2.0]), 0.261022888380508);
+        assert_close(chi_square(Pdf, 4.2, &[7.5]), 0.10649736231362562);
+        assert_close(chi_square(Cdf, 3.0, &[2.0]), 0.7768698398515702);
+        assert_close(chi_square(Cdf, 4.2, &[7.5]), 0.1993777877685074);
+        assert_close(chi_square(Inverse, 0.95, &[2.0]), 5.991464547107979);
+        assert_close(chi_square(Inverse, 0.1, &[7.5]), 3.1582633352823213);
+        assert_eq!(chi_square(Pdf, 0.0, &[2.0]), 0.5);
+        assert_eq!(chi_square(Cdf, -1.0, &[2.0]), 0.0);
+    }
+}
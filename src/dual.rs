@@ -0,0 +1,265 @@
+use std::{fmt, ops};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    builtins::BuiltinFunction,
+    number::{Number, check_arity},
+    parse::{BinaryOp, UnaryOp},
+    special,
+    value::Value,
+};
+
+/// A dual number `value + derivative * ε` with `ε^2 = 0`.
+///
+/// Evaluating a function at `Dual::variable(x)` gives its value and its exact
+/// derivative at `x` in a single pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub derivative: f64,
+}
+
+impl Dual {
+    pub const fn new(value: f64, derivative: f64) -> Self {
+        Self { value, derivative }
+    }
+
+    /// A number that does not depend on the variable.
+    pub const fn constant(value: f64) -> Self {
+        Self::new(value, 0.0)
+    }
+
+    /// The variable itself, whose derivative is 1.
+    pub const fn variable(value: f64) -> Self {
+        Self::new(value, 1.0)
+    }
+
+    /// Applies a function with the given value and derivative at `self.value`.
+    fn chain(self, value: f64, derivative: f64) -> Self {
+        // constants stay constant even where the function's derivative is infinite
+        match self.derivative {
+            0.0 => Self::constant(value),
+            _ => Self::new(value, derivative * self.derivative),
+        }
+    }
+
+    pub fn sqrt(self) -> Self {
+        let root = self.value.sqrt();
+        self.chain(root, 0.5 / root)
+    }
+
+    pub fn abs(self) -> Self {
+        self.chain(self.value.abs(), self.value.signum())
+    }
+
+    pub fn sin(self) -> Self {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    pub fn cos(self) -> Self {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    pub fn tan(self) -> Self {
+        let tan = self.value.tan();
+        self.chain(tan, 1.0 + tan * tan)
+    }
+
+    pub fn asin(self) -> Self {
+        self.chain(
+            self.value.asin(),
+            1.0 / (1.0 - self.value * self.value).sqrt(),
+        )
+    }
+
+    pub fn acos(self) -> Self {
+        self.chain(
+            self.value.acos(),
+            -1.0 / (1.0 - self.value * self.value).sqrt(),
+        )
+    }
+
+    pub fn atan(self) -> Self {
+        self.chain(self.value.atan(), 1.0 / (1.0 + self.value * self.value))
+    }
+
+    /// The angle of the point `(x, y)`, with `self` as `y`.
+    pub fn atan2(self, x: Self) -> Self {
+        let y = self;
+        if x.derivative == 0.0 && y.derivative == 0.0 {
+            return Self::constant(y.value.atan2(x.value));
+        }
+        let r2 = x.value * x.value + y.value * y.value;
+        Self::new(
+            y.value.atan2(x.value),
+            (x.value * y.derivative - y.value * x.derivative) / r2,
+        )
+    }
+
+    pub fn exp(self) -> Self {
+        let exp = self.value.exp();
+        self.chain(exp, exp)
+    }
+
+    pub fn ln(self) -> Self {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    pub fn gamma(self) -> Self {
+        let gamma = special::gamma(self.value);
+        self.chain(gamma, gamma * special::digamma(self.value))
+    }
+
+    pub fn lgamma(self) -> Self {
+        self.chain(special::lgamma(self.value), special::digamma(self.value))
+    }
+
+    /// The beta function of `self` and `b`.
+    pub fn beta(self, b: Self) -> Self {
+        let a = self;
+        let beta = special::beta(a.value, b.value);
+        if a.derivative == 0.0 && b.derivative == 0.0 {
+            return Self::constant(beta);
+        }
+        let digamma_sum = special::digamma(a.value + b.value);
+        Self::new(
+            beta,
+            beta * ((special::digamma(a.value) - digamma_sum) * a.derivative
+                + (special::digamma(b.value) - digamma_sum) * b.derivative),
+        )
+    }
+
+    pub fn erf(self) -> Self {
+        let slope = 2.0 / std::f64::consts::PI.sqrt() * (-self.value * self.value).exp();
+        self.chain(special::erf(self.value), slope)
+    }
+
+    pub fn pow(self, exponent: Self) -> Self {
+        let value = self.value.powf(exponent.value);
+        // a constant exponent keeps the derivative defined for negative bases
+        if exponent.derivative == 0.0 {
+            let slope = exponent.value * self.value.powf(exponent.value - 1.0);
+            return self.chain(value, slope);
+        }
+        // d/dx u^v = u^v * (v' ln(u) + v u' / u)
+        Self::new(
+            value,
+            value
+                * (exponent.derivative * self.value.ln()
+                    + exponent.value * self.derivative / self.value),
+        )
+    }
+}
+
+impl ops::Add for Dual {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.value + rhs.value, self.derivative + rhs.derivative)
+    }
+}
+
+impl ops::Sub for Dual {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.value - rhs.value, self.derivative - rhs.derivative)
+    }
+}
+
+impl ops::Mul for Dual {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.value * rhs.value,
+            self.derivative * rhs.value + self.value * rhs.derivative,
+        )
+    }
+}
+
+impl ops::Div for Dual {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        Self::new(
+            self.value / rhs.value,
+            (self.derivative * rhs.value - self.value * rhs.derivative) / (rhs.value * rhs.value),
+        )
+    }
+}
+
+impl ops::Neg for Dual {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.value, -self.derivative)
+    }
+}
+
+impl fmt::Display for Dual {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} + {}ε", self.value, self.derivative)
+    }
+}
+
+/// Real numbers with their derivatives, where complex results are undefined.
+impl Number for Dual {
+    const NAME: &'static str = "dual number";
+
+    fn from_f64(x: f64) -> Self {
+        Self::constant(x)
+    }
+
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(Self::constant(value.as_number()?))
+    }
+
+    fn to_f64(&self) -> Result<f64> {
+        if self.derivative != 0.0 {
+            bail!("Cannot take the derivative through an argument that must be a float");
+        }
+        Ok(self.value)
+    }
+
+    fn unary_op(op: UnaryOp, arg: &Self) -> Result<Self> {
+        Ok(match op {
+            UnaryOp::Negate => -*arg,
+            UnaryOp::Plus => *arg,
+            UnaryOp::Degrees => *arg * Self::constant(1f64.to_radians()),
+        })
+    }
+
+    fn binary_op(op: BinaryOp, left: &Self, right: &Self) -> Result<Self> {
+        let (left, right) = (*left, *right);
+        Ok(match op {
+            BinaryOp::Add => left + right,
+            BinaryOp::Subtract => left - right,
+            BinaryOp::DotProduct => left * right,
+            BinaryOp::Divide => left / right,
+            BinaryOp::Power => left.pow(right),
+        })
+    }
+
+    fn apply(builtin: BuiltinFunction, args: &[Self]) -> Result<Self> {
+        check_arity(builtin, args)?;
+        if builtin.takes_list() {
+            bail!("Expected a list but got a {}", Self::NAME);
+        }
+        if builtin.takes_matrix() {
+            bail!("Expected a matrix but got a {}", Self::NAME);
+        }
+        if let Some((distribution, function)) = builtin.distribution() {
+            let (x, parameters) = (args[0], &args[1..]);
+            if parameters
+                .iter()
+                .any(|parameter| parameter.derivative != 0.0)
+            {
+                bail!("Cannot differentiate {builtin} with respect to its parameters");
+            }
+            let parameters = parameters.iter().map(|parameter| parameter.value);
+            let parameters = parameters.collect::<Vec<_>>();
+            return Ok(x.chain(
+                distribution.apply(function, x.value, &parameters),
+                distribution.slope(function, x.value, &parameters),
+            ));
+        }
+        Ok(builtin.apply_dual(args))
+    }
+}
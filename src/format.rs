@@ -0,0 +1,285 @@
+use std::f64::consts::PI;
+
+use anyhow::{Result, bail};
+
+use crate::complex::Complex;
+use crate::parse::SettingArg;
+use crate::rational::Rational;
+use crate::value::Value;
+
+/// How the digits of a number are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    /// Positional, unless the number is very large or very small.
+    #[default]
+    Auto,
+    Fixed,
+    Scientific,
+    /// Scientific notation with an exponent that is a multiple of three.
+    Engineering,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Significant(usize),
+    Decimals(usize),
+}
+
+/// How results are displayed, both in the printed constants and in the window title.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Format {
+    pub notation: Notation,
+    /// Without a precision, a float is displayed with the fewest digits that read
+    /// back as the same float.
+    pub precision: Option<Precision>,
+    pub thousands_separators: bool,
+    /// Displays floats that are very close to simple fractions or multiples of π as such.
+    pub recognize_fractions: bool,
+    /// Displays fractions as mixed numbers such as `3 1/2`.
+    pub mixed_numbers: bool,
+}
+
+/// The most digits a float can meaningfully be displayed with.
+const MAX_DIGITS: usize = 17;
+
+/// Auto notation uses scientific notation outside of `1e-6 <= |x| < 1e15`.
+const AUTO_EXPONENTS: std::ops::RangeInclusive<i32> = -6..=14;
+
+/// The largest denominators recognized in fractions and multiples of π.
+const MAX_DENOMINATOR: i64 = 100;
+const MAX_PI_DENOMINATOR: i64 = 12;
+
+impl Format {
+    /// Changes the format according to a setting like `#digits 4`.
+    pub fn apply_setting(&mut self, name: &str, args: &[SettingArg]) -> Result<()> {
+        use SettingArg::*;
+        let digits = |n: f64| match n {
+            n if n.fract() == 0.0 && (0.0..=MAX_DIGITS as f64).contains(&n) => Ok(n as usize),
+            n => bail!("Cannot display numbers with {n} digits"),
+        };
+        match (name, args) {
+            ("digits", []) | ("decimals", []) => self.precision = None,
+            ("digits", [Number(n)]) => match digits(*n)? {
+                0 => bail!("Cannot display numbers with no significant digits"),
+                n => self.precision = Some(Precision::Significant(n)),
+            },
+            ("decimals", [Number(n)]) => self.precision = Some(Precision::Decimals(digits(*n)?)),
+            ("notation", [Word(notation)]) => {
+                self.notation = match *notation {
+                    "auto" => Notation::Auto,
+                    "fixed" => Notation::Fixed,
+                    "scientific" => Notation::Scientific,
+                    "engineering" => Notation::Engineering,
+                    _ => bail!("Unknown notation '{notation}'"),
+                }
+            }
+            ("separators", [] | [Word("on")]) => self.thousands_separators = true,
+            ("separators", [Word("off")]) => self.thousands_separators = false,
+            ("fractions", [] | [Word("on")]) => self.recognize_fractions = true,
+            ("fractions", [Word("mixed")]) => {
+                (self.recognize_fractions, self.mixed_numbers) = (true, true)
+            }
+            ("fractions", [Word("off")]) => self.recognize_fractions = false,
+            ("digits" | "decimals" | "notation" | "separators" | "fractions", _) => {
+                bail!("Unexpected arguments to #{name}")
+            }
+            _ => bail!("Unknown setting #{name}"),
+        }
+        Ok(())
+    }
+
+    pub fn value(&self, value: &Value) -> String {
+        match value {
+            Value::Number(x) => self.number(*x),
+            Value::Rational(x) => self.rational(x),
+            Value::Complex(z) => self.complex(*z),
+            Value::Quantity(x) => match x.in_unit() {
+                (value, Some(unit)) => format!("{} {unit}", self.number(value)),
+                (value, None) => format!("{} {}", self.number(value), x.dimension),
+            },
+            Value::Point(x, y) => format!("({}, {})", self.number(*x), self.number(*y)),
+            Value::List(elements) => {
+                let elements = elements.iter().map(|element| self.value(element));
+                format!("[{}]", elements.collect::<Vec<_>>().join(", "))
+            }
+            Value::Matrix(m) => {
+                let row = |row: &[f64]| {
+                    let entries = row.iter().map(|&x| self.number(x));
+                    format!("[{}]", entries.collect::<Vec<_>>().join(", "))
+                };
+                format!("[{}]", m.rows().map(row).collect::<Vec<_>>().join(", "))
+            }
+            Value::Function(function) => function.to_string(),
+        }
+    }
+
+    pub fn number(&self, x: f64) -> String {
+        if x.is_nan() {
+            return "undefined".into();
+        }
+        if x.is_infinite() {
+            return if x > 0.0 { "∞" } else { "-∞" }.into();
+        }
+        if self.recognize_fractions
+            && let Some(recognized) = self.recognize(x)
+        {
+            return recognized;
+        }
+        let sign = if x < 0.0 { "-" } else { "" };
+        format!("{sign}{}", self.magnitude(x.abs()))
+    }
+
+    fn rational(&self, x: &Rational) -> String {
+        match self.mixed_numbers {
+            true => format!("{x:#}"),
+            false => x.to_string(),
+        }
+    }
+
+    fn complex(&self, z: Complex) -> String {
+        let imaginary = match z.im.abs() {
+            1.0 => "i".to_string(),
+            im => match self.number(im) {
+                // `π/2i` would read as a division by `2i`
+                im if im.contains('/') => format!("({im})i"),
+                im => format!("{im}i"),
+            },
+        };
+        let sign = if z.im < 0.0 { "-" } else { "+" };
+        match z.re {
+            0.0 if z.im < 0.0 => format!("-{imaginary}"),
+            0.0 => imaginary,
+            re => format!("{} {sign} {imaginary}", self.number(re)),
+        }
+    }
+
+    /// Formats a finite, non-negative number.
+    fn magnitude(&self, x: f64) -> String {
+        let (digits, exponent) = match self.precision {
+            // positional notation can round to a number of decimals directly
+            Some(Precision::Decimals(decimals))
+                if matches!(self.notation, Notation::Auto | Notation::Fixed) =>
+            {
+                let (whole, fraction) = split_point(format!("{x:.decimals$}"));
+                return self.join(&whole, &fraction);
+            }
+            Some(Precision::Decimals(decimals)) => {
+                let round = |exponent: i32| {
+                    // the mantissa has one to three digits before the point
+                    let whole = match self.notation {
+                        Notation::Engineering => exponent.rem_euclid(3) as usize + 1,
+                        _ => 1,
+                    };
+                    let significant = (whole + decimals).min(MAX_DIGITS);
+                    split_exponent(&format!("{x:.*e}", significant - 1))
+                };
+                let (_, exponent) = split_exponent(&format!("{x:e}"));
+                // rounding up to the next power of ten can change the digits before the point
+                let (digits, rounded) = round(exponent);
+                match rounded == exponent {
+                    true => (digits, rounded),
+                    false => round(rounded),
+                }
+            }
+            Some(Precision::Significant(digits)) => split_exponent(&format!("{x:.*e}", digits - 1)),
+            None => split_exponent(&format!("{x:e}")),
+        };
+
+        let scientific = match self.notation {
+            Notation::Auto => !AUTO_EXPONENTS.contains(&exponent),
+            Notation::Fixed => false,
+            Notation::Scientific | Notation::Engineering => true,
+        };
+        if !scientific {
+            let (whole, fraction) = positional(&digits, exponent);
+            return self.join(&whole, &fraction);
+        }
+        let shift = match self.notation {
+            Notation::Engineering => exponent.rem_euclid(3),
+            _ => 0,
+        };
+        let (whole, fraction) = positional(&digits, shift);
+        format!("{}e{}", self.join(&whole, &fraction), exponent - shift)
+    }
+
+    fn join(&self, whole: &str, fraction: &str) -> String {
+        let whole = match self.thousands_separators {
+            true => group_thousands(whole),
+            false => whole.to_string(),
+        };
+        match fraction {
+            "" => whole,
+            fraction => format!("{whole}.{fraction}"),
+        }
+    }
+
+    /// Writes a float that is a simple fraction or multiple of π as such.
+    fn recognize(&self, x: f64) -> Option<String> {
+        // integers are already exact, and the numerators have to fit an `i64`
+        if x.fract() == 0.0 || x.abs() > 1e9 {
+            return None;
+        }
+        if let Some((numerator, denominator)) = approximate_fraction(x, MAX_DENOMINATOR) {
+            return Some(self.rational(&Rational::new(numerator, denominator)));
+        }
+        let (numerator, denominator) = approximate_fraction(x / PI, MAX_PI_DENOMINATOR)?;
+        let multiple = match numerator {
+            1 => "π".to_string(),
+            -1 => "-π".to_string(),
+            n => format!("{n}π"),
+        };
+        Some(match denominator {
+            1 => multiple,
+            d => format!("{multiple}/{d}"),
+        })
+    }
+}
+
+/// The smallest denominator whose fraction equals `x` up to rounding errors.
+fn approximate_fraction(x: f64, max_denominator: i64) -> Option<(i64, i64)> {
+    let tolerance = 1e-12 * x.abs().max(1.0);
+    (1..=max_denominator).find_map(|denominator| {
+        let numerator = (x * denominator as f64).round();
+        let error = (numerator / denominator as f64 - x).abs();
+        (error <= tolerance).then_some((numerator as i64, denominator))
+    })
+}
+
+/// Splits Rust's `1.2345e3` into the digits `12345` and the exponent `3`.
+fn split_exponent(scientific: &str) -> (String, i32) {
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    (mantissa.replace('.', ""), exponent.parse().unwrap())
+}
+
+fn split_point(positional: String) -> (String, String) {
+    match positional.split_once('.') {
+        Some((whole, fraction)) => (whole.to_string(), fraction.to_string()),
+        None => (positional, String::new()),
+    }
+}
+
+/// Places the point in `d.ddd * 10^exponent`, giving the digits before and after it.
+fn positional(digits: &str, exponent: i32) -> (String, String) {
+    if exponent < 0 {
+        let zeros = "0".repeat((-exponent - 1) as usize);
+        return ("0".to_string(), format!("{zeros}{digits}"));
+    }
+    let point = exponent as usize + 1;
+    if digits.len() <= point {
+        let zeros = "0".repeat(point - digits.len());
+        (format!("{digits}{zeros}"), String::new())
+    } else {
+        (digits[..point].to_string(), digits[point..].to_string())
+    }
+}
+
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
@@ -0,0 +1,185 @@
+use anyhow::Result;
+
+use crate::matrix::{Matrix, norm};
+
+/// The Levenberg–Marquardt method for the `x` that minimizes the sum of squares of
+/// residuals `f(x)`, which blends Gauss–Newton steps with short steps downhill
+/// while the Gauss–Newton steps don't reduce it.
+///
+/// The Jacobian is taken from `jacobian` at every step, which gives the exact one when
+/// it comes from evaluating the residuals over dual numbers. Where that fails, it is
+/// estimated by finite differences instead.
+#[derive(Debug, Clone, Copy)]
+pub struct LevenbergMarquardt {
+    /// Iteration stops once a step is this small relative to the solution.
+    pub tolerance: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for LevenbergMarquardt {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-12,
+            max_iterations: 200,
+        }
+    }
+}
+
+/// Where the Levenberg–Marquardt method stopped, and the residuals there.
+#[derive(Debug, Clone)]
+pub struct Minimum {
+    pub x: Vec<f64>,
+    pub residuals: Vec<f64>,
+    pub converged: bool,
+}
+
+/// The damping at which the method gives up on finding a step that reduces the
+/// sum of squares.
+const MAX_DAMPING: f64 = 1e16;
+
+impl LevenbergMarquardt {
+    /// Iterates from `start` until a step is small enough, or gives up when no step
+    /// reduces the sum of squares. The columns of `jacobian(x)` are the derivatives of
+    /// the residuals with respect to each component of `x`, and once it fails it is no
+    /// longer asked for.
+    pub fn minimize(
+        &self,
+        mut f: impl FnMut(&[f64]) -> Result<Vec<f64>>,
+        mut jacobian: impl FnMut(&[f64]) -> Result<Vec<Vec<f64>>>,
+        start: Vec<f64>,
+    ) -> Result<Minimum> {
+        let mut x = start;
+        let mut residuals = f(&x)?;
+        let mut cost = sum_of_squares(&residuals);
+        let mut damping = 1e-3;
+        let mut converged = cost == 0.0;
+        let mut exact = true;
+        for _ in 0..self.max_iterations {
+            if converged || !cost.is_finite() {
+                break;
+            }
+            let derivatives = match exact.then(|| jacobian(&x)) {
+                Some(Ok(derivatives)) => derivatives,
+                _ => {
+                    exact = false;
+                    finite_differences(&mut f, &x, &residuals)?
+                }
+            };
+            let scales: Vec<f64> = derivatives.iter().map(|column| norm(column)).collect();
+            let mut accepted = false;
+            while damping <= MAX_DAMPING && !accepted {
+                // the Gauss–Newton step for `J step = -r`, with rows `sqrt(λ) |J_i| step_i = 0`
+                // that keep the step short while `λ` is large
+                let mut columns = derivatives.clone();
+                for (i, column) in columns.iter_mut().enumerate() {
+                    column.extend((0..x.len()).map(|j| match i == j {
+                        true => damping.sqrt() * scales[i].max(f64::MIN_POSITIVE),
+                        false => 0.0,
+                    }));
+                }
+                let mut b: Vec<f64> = residuals.iter().map(|r| -r).collect();
+                b.resize(b.len() + x.len(), 0.0);
+                let Some(step) = Matrix::from_columns(&columns).least_squares(&b) else {
+                    damping *= 10.0;
+                    continue;
+                };
+                let small = norm(&step) <= self.tolerance * (1.0 + norm(&x));
+                let trial: Vec<f64> = std::iter::zip(&x, &step).map(|(x, dx)| x + dx).collect();
+                let trial_residuals = f(&trial)?;
+                let trial_cost = sum_of_squares(&trial_residuals);
+                if trial_cost < cost {
+                    (x, residuals, cost) = (trial, trial_residuals, trial_cost);
+                    damping = (damping / 10.0).max(f64::EPSILON);
+                    accepted = true;
+                    converged = small;
+                } else if small {
+                    // rounding keeps the sum of squares from shrinking any further
+                    converged = true;
+                    break;
+                } else {
+                    damping *= 10.0;
+                }
+            }
+            if !accepted && !converged {
+                break;
+            }
+        }
+        Ok(Minimum {
+            x,
+            residuals,
+            converged,
+        })
+    }
+}
+
+fn sum_of_squares(x: &[f64]) -> f64 {
+    x.iter().map(|x| x * x).sum()
+}
+
+/// The columns of the Jacobian of `f` at `x` estimated by forward differences, given
+/// `residuals = f(x)`.
+fn finite_differences(
+    f: &mut impl FnMut(&[f64]) -> Result<Vec<f64>>,
+    x: &[f64],
+    residuals: &[f64],
+) -> Result<Vec<Vec<f64>>> {
+    let mut columns = Vec::new();
+    for i in 0..x.len() {
+        let h = f64::EPSILON.sqrt() * x[i].abs().max(1.0);
+        let mut shifted = x.to_vec();
+        shifted[i] += h;
+        let column = std::iter::zip(f(&shifted)?, residuals);
+        columns.push(column.map(|(shifted, r)| (shifted - r) / h).collect());
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        let error = ((actual - expected) / expected).abs();
+        assert!(
+            error < 1e-10,
+            "{actual} differs from {expected} by {error:.1e}"
+        );
+    }
+
+    const X: [f64; 5] = [0.0, 1.0, 2.0, 3.0, 4.0];
+
+    #[test]
+    fn linear_fit() {
+        // y = 2x + 1 exactly, so the fit has no residuals
+        let residuals = |p: &[f64]| {
+            Ok(X.iter()
+                .map(|x| p[0] * x + p[1] - (2.0 * x + 1.0))
+                .collect())
+        };
+        let jacobian = |_: &[f64]| Ok(vec![X.to_vec(), vec![1.0; X.len()]]);
+        let minimum = LevenbergMarquardt::default()
+            .minimize(residuals, jacobian, vec![0.0, 0.0])
+            .unwrap();
+        assert!(minimum.converged);
+        assert_close(minimum.x[0], 2.0);
+        assert_close(minimum.x[1], 1.0);
+    }
+
+    #[test]
+    fn exponential_fit_by_finite_differences() {
+        let residuals = |p: &[f64]| {
+            Ok(X.iter()
+                .map(|x| p[0] * (p[1] * x).exp() - 3.0 * (0.5 * x).exp())
+                .collect())
+        };
+        let jacobian = |_: &[f64]| bail!("no exact Jacobian");
+        let minimum = LevenbergMarquardt::default()
+            .minimize(residuals, jacobian, vec![1.0, 0.1])
+            .unwrap();
+        assert!(minimum.converged);
+        assert_close(minimum.x[0], 3.0);
+        assert_close(minimum.x[1], 0.5);
+    }
+}
@@ -3,21 +3,33 @@ use std::fmt;
 use anyhow::{Result, bail};
 use ecow::EcoString;
 
+use crate::span::Span;
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Token {
     Ident(EcoString),
     NumLit(EcoString),
     Newline,
     Assign,
+    Equals,
+    Tilde,
+    Prime,
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    Ellipsis,
+    Hash,
+    Degree,
+    Arrow,
 
     Plus,
     Minus,
     Cdot,
     Slash,
+    Caret,
 }
 
 impl fmt::Display for Token {
@@ -27,13 +39,23 @@ impl fmt::Display for Token {
             Self::Newline => "\\n",
             Self::LeftParen => "(",
             Self::RightParen => ")",
+            Self::LeftBracket => "[",
+            Self::RightBracket => "]",
             Self::Comma => ",",
             Self::Dot => ".",
+            Self::Ellipsis => "...",
+            Self::Hash => "#",
+            Self::Degree => "°",
+            Self::Arrow => "->",
             Self::Assign => ":=",
+            Self::Equals => "=",
+            Self::Tilde => "~",
+            Self::Prime => "'",
             Self::Plus => "+",
             Self::Minus => "-",
             Self::Cdot => "*",
             Self::Slash => "/",
+            Self::Caret => "^",
         })
     }
 }
@@ -41,12 +63,11 @@ impl fmt::Display for Token {
 struct Lexer<'a> {
     input: &'a str,
     pos: usize,
-    tokens: Vec<Token>,
+    tokens: Vec<(Token, Span)>,
 }
 
 impl<'a> Lexer<'a> {
     fn new(input: &'a str) -> Self {
-        assert!(input.is_ascii());
         Self {
             input,
             pos: 0,
@@ -61,7 +82,7 @@ impl<'a> Lexer<'a> {
     fn next_char_if(&mut self, f: impl Fn(&char) -> bool) -> Option<char> {
         let c = self.peek_char()?;
         if f(&c) {
-            self.pos += 1;
+            self.pos += c.len_utf8();
             Some(c)
         } else {
             None
@@ -94,8 +115,11 @@ impl<'a> Lexer<'a> {
 
     fn read_number_unsigned(&mut self) -> bool {
         if self.read_digits() {
-            self.next_char_exact('.');
-            self.read_digits();
+            // `1...10` is a range rather than `1.` followed by `..`
+            if !self.input[self.pos..].starts_with("...") {
+                self.next_char_exact('.');
+                self.read_digits();
+            }
             return true;
         }
         let start = self.pos;
@@ -111,7 +135,7 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn run(mut self) -> Result<Vec<Token>> {
+    fn run(mut self) -> Result<Vec<(Token, Span)>> {
         fn is_whitespace(c: &char) -> bool {
             c.is_ascii_whitespace() && !"\r\n".contains(*c)
         }
@@ -122,15 +146,24 @@ impl<'a> Lexer<'a> {
                 '\r' if self.next_char_exact('\n') => Token::Newline,
                 '\n' => Token::Newline,
                 ':' if self.next_char_exact('=') => Token::Assign,
+                '=' => Token::Equals,
+                '~' => Token::Tilde,
+                '\'' => Token::Prime,
                 ')' => Token::RightParen,
                 '(' => Token::LeftParen,
+                '[' => Token::LeftBracket,
+                ']' => Token::RightBracket,
                 ',' => Token::Comma,
+                '#' => Token::Hash,
+                '°' => Token::Degree,
                 '+' => Token::Plus,
+                '-' if self.next_char_exact('>') => Token::Arrow,
                 '-' => Token::Minus,
                 '*' => Token::Cdot,
                 '/' => Token::Slash,
+                '^' => Token::Caret,
                 c if c.is_ascii_alphabetic() => {
-                    self.next_char_while(char::is_ascii_alphabetic);
+                    self.next_char_while(char::is_ascii_alphanumeric);
                     Token::Ident(EcoString::from(&self.input[start..self.pos]))
                 }
                 '0'..='9' => {
@@ -138,6 +171,10 @@ impl<'a> Lexer<'a> {
                     self.read_number_unsigned();
                     Token::NumLit(EcoString::from(&self.input[start..self.pos]))
                 }
+                '.' if self.input[self.pos..].starts_with("..") => {
+                    self.pos += 2;
+                    Token::Ellipsis
+                }
                 '.' => {
                     if self.read_digits() {
                         Token::NumLit(EcoString::from(&self.input[start..self.pos]))
@@ -147,7 +184,7 @@ impl<'a> Lexer<'a> {
                 }
                 _ => bail!("Unknown character {c:?}"),
             };
-            self.tokens.push(token);
+            self.tokens.push((token, Span::new(start, self.pos)));
             self.next_char_while(is_whitespace);
         }
 
@@ -155,6 +192,6 @@ impl<'a> Lexer<'a> {
     }
 }
 
-pub fn lex(input: &str) -> Result<Vec<Token>> {
+pub fn lex(input: &str) -> Result<Vec<(Token, Span)>> {
     Lexer::new(input).run()
 }
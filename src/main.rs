@@ -1,9 +1,27 @@
 #![allow(unused)]
 mod builtins;
+mod check;
+mod complex;
+mod derivative;
+mod distributions;
+mod dual;
+mod format;
+mod least_squares;
 mod lex;
+mod matrix;
+mod number;
+mod ode;
 mod parse;
+mod quadrature;
+mod rational;
+mod roots;
 mod run;
+mod simplify;
+mod span;
+mod special;
 mod state;
+mod units;
+mod value;
 
 use crate::state::State;
 
@@ -25,6 +43,8 @@ use vello::wgpu;
 use crate::parse::parse;
 use crate::run::Interpreter;
 
+const TITLE: &str = "Vello Shapes";
+
 #[derive(Debug)]
 enum RenderState<'s> {
     /// `RenderSurface` and `Window` for active rendering.
@@ -120,6 +140,7 @@ impl ApplicationHandler for App<'_> {
 
             WindowEvent::CursorMoved { position, .. } => {
                 self.state.handle_cursor_move(position);
+                window.set_title(&format!("{TITLE} {}", self.state.status()));
             }
 
             WindowEvent::MouseInput {
@@ -128,10 +149,16 @@ impl ApplicationHandler for App<'_> {
                 ..
             } => {
                 self.state.handle_mouse_input(mouse_state);
+                window.set_title(&format!("{TITLE} {}", self.state.status()));
             }
 
             WindowEvent::MouseWheel { delta, .. } => {
                 self.state.handle_scroll(delta);
+                window.set_title(&format!("{TITLE} {}", self.state.status()));
+            }
+
+            WindowEvent::KeyboardInput { event, .. } if event.state.is_pressed() => {
+                self.state.handle_key_press(event.logical_key);
             }
 
             WindowEvent::RedrawRequested => {
@@ -207,14 +234,22 @@ fn main() -> Result<()> {
 
     let items = parse(input.as_str())?;
     let mut interpreter = Interpreter::new();
-    interpreter.run(items);
+    if let Err(err) = interpreter.run(items) {
+        eprintln!("{}", span::report(&input, &err));
+    }
 
     // println!("{interpreter:#?}");
-    for (name, value) in interpreter.constants.iter() {
+    for (name, args, body) in interpreter.rewritten_functions.iter() {
+        println!("{name}({args}) := {body}");
+    }
+    for (name, value, format) in interpreter.constants.iter() {
         if let Some(name) = name {
             print!("{name} = ");
         }
-        println!("{value}");
+        println!("{}", format.value(value));
+    }
+    for warning in interpreter.warnings() {
+        eprintln!("warning: {warning}");
     }
 
     let mut app = App {
@@ -239,7 +274,7 @@ fn create_winit_window(event_loop: &ActiveEventLoop) -> Arc<Window> {
         .with_inner_size(LogicalSize::new(1044, 800))
         .with_min_inner_size(LogicalSize::new(300, 100))
         .with_resizable(true)
-        .with_title("Vello Shapes");
+        .with_title(TITLE);
     Arc::new(event_loop.create_window(attr).unwrap())
 }
 
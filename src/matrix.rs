@@ -0,0 +1,452 @@
+use std::fmt;
+
+use anyhow::{Result, bail};
+use ecow::EcoVec;
+
+use crate::complex::Complex;
+
+/// A matrix of real numbers, written as a list of rows like `[[1, 2], [3, 4]]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    columns: usize,
+    /// The entries row by row.
+    entries: EcoVec<f64>,
+}
+
+impl Matrix {
+    /// Builds a matrix from its rows, which must all have the same nonzero length.
+    pub fn from_rows(rows: &[Vec<f64>]) -> Result<Self> {
+        let columns = rows.first().map_or(0, Vec::len);
+        if columns == 0 {
+            bail!("Rows of a matrix cannot be empty");
+        }
+        if let Some(row) = rows.iter().find(|row| row.len() != columns) {
+            bail!(
+                "Rows of a matrix must have the same length, not {columns} and {}",
+                row.len()
+            );
+        }
+        Ok(Self {
+            rows: rows.len(),
+            columns,
+            entries: rows.iter().flatten().copied().collect(),
+        })
+    }
+
+    /// Builds a matrix from its columns, which must all have the same length.
+    pub fn from_columns(columns: &[Vec<f64>]) -> Self {
+        let rows = columns.first().map_or(0, Vec::len);
+        Self::from_fn(rows, columns.len(), |i, j| columns[j][i])
+    }
+
+    fn from_fn(rows: usize, columns: usize, f: impl Fn(usize, usize) -> f64) -> Self {
+        let entries = (0..rows).flat_map(|i| (0..columns).map(move |j| (i, j)));
+        Self {
+            rows,
+            columns,
+            entries: entries.map(|(i, j)| f(i, j)).collect(),
+        }
+    }
+
+    fn identity(n: usize) -> Self {
+        Self::from_fn(n, n, |i, j| if i == j { 1.0 } else { 0.0 })
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        self.entries[i * self.columns + j]
+    }
+
+    fn column(&self, j: usize) -> Vec<f64> {
+        (0..self.rows).map(|i| self.get(i, j)).collect()
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[f64]> {
+        self.entries.chunks(self.columns)
+    }
+
+    pub fn size(&self) -> String {
+        format!("{}x{}", self.rows, self.columns)
+    }
+
+    pub fn map(&self, f: impl Fn(f64) -> f64) -> Self {
+        Self {
+            entries: self.entries.iter().map(|&x| f(x)).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Adds or subtracts matrices of the same size entry by entry.
+    pub fn zip(&self, other: &Self, f: impl Fn(f64, f64) -> f64) -> Result<Self> {
+        if (self.rows, self.columns) != (other.rows, other.columns) {
+            bail!(
+                "Cannot combine a {} and a {} matrix",
+                self.size(),
+                other.size()
+            );
+        }
+        let entries = std::iter::zip(self.entries.iter(), other.entries.iter());
+        Ok(Self {
+            entries: entries.map(|(&a, &b)| f(a, b)).collect(),
+            ..self.clone()
+        })
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.columns, self.rows, |i, j| self.get(j, i))
+    }
+
+    pub fn mul(&self, other: &Self) -> Result<Self> {
+        if self.columns != other.rows {
+            bail!(
+                "Cannot multiply a {} and a {} matrix",
+                self.size(),
+                other.size()
+            );
+        }
+        Ok(Self::from_fn(self.rows, other.columns, |i, j| {
+            (0..self.columns)
+                .map(|k| self.get(i, k) * other.get(k, j))
+                .sum()
+        }))
+    }
+
+    /// Multiplies a column vector, given as a list of numbers.
+    pub fn mul_vector(&self, vector: &[f64]) -> Result<Vec<f64>> {
+        if self.columns != vector.len() {
+            bail!(
+                "Cannot multiply a {} matrix and a list of {} numbers",
+                self.size(),
+                vector.len()
+            );
+        }
+        let dot = |row: &[f64]| std::iter::zip(row, vector).map(|(a, b)| a * b).sum();
+        Ok(self.rows().map(dot).collect())
+    }
+
+    /// Transforms a point by a 2x2 matrix, or by a 3x3 matrix in homogeneous
+    /// coordinates so that affine and projective transforms can be written too.
+    pub fn transform(&self, x: f64, y: f64) -> Result<(f64, f64)> {
+        match (self.rows, self.columns) {
+            (2, 2) => match self.mul_vector(&[x, y])?[..] {
+                [x, y] => Ok((x, y)),
+                _ => unreachable!(),
+            },
+            (3, 3) => match self.mul_vector(&[x, y, 1.0])?[..] {
+                [x, y, w] => Ok((x / w, y / w)),
+                _ => unreachable!(),
+            },
+            _ => bail!(
+                "Only 2x2 and 3x3 matrices can transform points, not {}",
+                self.size()
+            ),
+        }
+    }
+
+    /// The `n`th power of a square matrix, where negative powers are powers of the inverse.
+    pub fn pow(&self, n: f64) -> Result<Self> {
+        if n.fract() != 0.0 {
+            bail!("Matrices can only be raised to integer powers, not {n}");
+        }
+        let base = if n < 0.0 {
+            self.inverse()?
+        } else {
+            self.square()?.clone()
+        };
+        let (mut result, mut base, mut n) = (Self::identity(self.rows), base, n.abs() as u64);
+        while n > 0 {
+            if n % 2 == 1 {
+                result = result.mul(&base)?;
+            }
+            base = base.mul(&base)?;
+            n /= 2;
+        }
+        Ok(result)
+    }
+
+    fn square(&self) -> Result<&Self> {
+        match self.rows == self.columns {
+            true => Ok(self),
+            false => bail!("Expected a square matrix but got a {} matrix", self.size()),
+        }
+    }
+
+    /// Reduces `[self | right]` to `[R | right']` with Householder reflections, where
+    /// `R` is upper triangular, and returns `R`. Returns `None` if the columns of `self`
+    /// are linearly dependent, up to rounding.
+    ///
+    /// Unlike elimination on the normal equations, this doesn't square the condition
+    /// number of `self` when it has more rows than columns.
+    fn reflect(&self, right: &mut Self) -> Option<Self> {
+        let (m, n) = (self.rows, self.columns);
+        if m < n {
+            return None;
+        }
+        let largest = (0..n).map(|j| norm(&self.column(j))).fold(0.0, f64::max);
+        let mut r = self.clone();
+        let entries = r.entries.make_mut();
+        let right_columns = right.columns;
+        let right_entries = right.entries.make_mut();
+        for k in 0..n {
+            // reflect the rest of column k onto its diagonal entry
+            let mut v: Vec<f64> = (k..m).map(|i| entries[i * n + k]).collect();
+            let length = norm(&v);
+            if length <= m as f64 * f64::EPSILON * largest || !length.is_finite() {
+                return None;
+            }
+            let diagonal = -length.copysign(v[0]);
+            v[0] -= diagonal;
+            let v_squared: f64 = v.iter().map(|x| x * x).sum();
+            let reflect = |entries: &mut [f64], columns: usize, j: usize| {
+                let dot: f64 = (k..m).map(|i| v[i - k] * entries[i * columns + j]).sum();
+                for i in k..m {
+                    entries[i * columns + j] -= 2.0 * dot / v_squared * v[i - k];
+                }
+            };
+            for j in k + 1..n {
+                reflect(entries, n, j);
+            }
+            for j in 0..right_columns {
+                reflect(right_entries, right_columns, j);
+            }
+            entries[k * n + k] = diagonal;
+            for i in k + 1..m {
+                entries[i * n + k] = 0.0;
+            }
+        }
+        Some(r)
+    }
+
+    /// Solves `self x = right` for a matrix that is upper triangular in its top rows,
+    /// like the ones `reflect` leaves.
+    fn back_substitute(&self, right: &Self) -> Self {
+        let (n, right_columns) = (self.columns, right.columns);
+        let mut x = Self::from_fn(n, right_columns, |_, _| 0.0);
+        let entries = x.entries.make_mut();
+        for row in (0..n).rev() {
+            for j in 0..right_columns {
+                let known: f64 = (row + 1..n)
+                    .map(|k| self.get(row, k) * entries[k * right_columns + j])
+                    .sum();
+                entries[row * right_columns + j] = (right.get(row, j) - known) / self.get(row, row);
+            }
+        }
+        x
+    }
+
+    /// The `x` that minimizes `|self x - b|`, which solves `self x = b` for a square
+    /// matrix, or `None` if the columns are linearly dependent so that there is no
+    /// single best `x`.
+    pub fn least_squares(&self, b: &[f64]) -> Option<Vec<f64>> {
+        let mut right = Self::from_fn(b.len(), 1, |i, _| b[i]);
+        let r = self.reflect(&mut right)?;
+        Some(r.back_substitute(&right).entries.to_vec())
+    }
+
+    /// The determinant of the matrix without row `i` and column `j`, times `(-1)^(i+j)`,
+    /// for matrices up to 3x3.
+    fn cofactor(&self, i: usize, j: usize) -> f64 {
+        let skip = |k: usize, skipped: usize| k + usize::from(k >= skipped);
+        let minor = |k, l| self.get(skip(k, i), skip(l, j));
+        let determinant = match self.rows {
+            1 => 1.0,
+            2 => minor(0, 0),
+            _ => minor(0, 0) * minor(1, 1) - minor(0, 1) * minor(1, 0),
+        };
+        if (i + j).is_multiple_of(2) {
+            determinant
+        } else {
+            -determinant
+        }
+    }
+
+    /// The transpose of the matrix of cofactors, for matrices up to 3x3.
+    fn adjugate(&self) -> Self {
+        Self::from_fn(self.rows, self.columns, |i, j| self.cofactor(j, i))
+    }
+
+    /// Whether a determinant is negligible compared to the entries of the matrix.
+    fn is_singular(&self, determinant: f64) -> bool {
+        let scale = self
+            .entries
+            .iter()
+            .fold(0.0, |scale: f64, x| scale.max(x.abs()));
+        let n = self.rows as i32;
+        determinant.abs() <= f64::from(n) * f64::EPSILON * scale.powi(n)
+    }
+
+    /// Determinants, inverses and solutions of matrices up to 3x3 are computed from
+    /// cofactors, which keeps them exact for small integer entries, and larger ones
+    /// with Householder reflections.
+    pub fn determinant(&self) -> Result<f64> {
+        if self.square()?.rows <= 3 {
+            return Ok((0..self.columns)
+                .map(|j| self.get(0, j) * self.cofactor(0, j))
+                .sum());
+        }
+        let mut right = Self::from_fn(self.rows, 0, |_, _| 0.0);
+        // each reflection has determinant -1
+        Ok(match self.reflect(&mut right) {
+            Some(r) => (0..self.rows).map(|i| -r.get(i, i)).product(),
+            None => 0.0,
+        })
+    }
+
+    pub fn inverse(&self) -> Result<Self> {
+        let determinant = self.determinant()?;
+        if self.is_singular(determinant) {
+            bail!("Cannot invert a singular matrix");
+        }
+        if self.rows <= 3 {
+            return Ok(self.adjugate().map(|x| x / determinant));
+        }
+        let mut right = Self::identity(self.rows);
+        match self.reflect(&mut right) {
+            Some(r) => Ok(r.back_substitute(&right)),
+            None => bail!("Cannot invert a singular matrix"),
+        }
+    }
+
+    /// Solves `self x = b` for the vector `x`.
+    pub fn solve(&self, b: &[f64]) -> Result<Vec<f64>> {
+        if self.square()?.rows != b.len() {
+            bail!(
+                "Cannot solve a system with a {} matrix and a list of {} numbers",
+                self.size(),
+                b.len()
+            );
+        }
+        let determinant = self.determinant()?;
+        if self.is_singular(determinant) {
+            bail!("Cannot solve a system with a singular matrix");
+        }
+        if self.rows <= 3 {
+            let x = self.adjugate().mul_vector(b)?;
+            return Ok(x.into_iter().map(|x| x / determinant).collect());
+        }
+        match self.least_squares(b) {
+            Some(x) => Ok(x),
+            None => bail!("Cannot solve a system with a singular matrix"),
+        }
+    }
+
+    /// The eigenvalues of a matrix up to 3x3, as the roots of its characteristic
+    /// polynomial, ordered by their real and then imaginary parts.
+    pub fn eigenvalues(&self) -> Result<Vec<Complex>> {
+        let trace = (0..self.square()?.rows)
+            .map(|i| self.get(i, i))
+            .sum::<f64>();
+        let mut eigenvalues = match self.rows {
+            1 => vec![Complex::from(trace)],
+            2 => quadratic_roots(-trace, self.determinant()?).to_vec(),
+            // the middle coefficient is the sum of the principal 2x2 minors
+            3 => {
+                let minors = (0..3).map(|i| self.cofactor(i, i)).sum();
+                cubic_roots(-trace, minors, -self.determinant()?).to_vec()
+            }
+            _ => bail!(
+                "Can only find the eigenvalues of matrices up to 3x3, not {}",
+                self.size()
+            ),
+        };
+        eigenvalues.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+        Ok(eigenvalues)
+    }
+}
+
+/// The Euclidean length of a vector.
+pub fn norm(x: &[f64]) -> f64 {
+    x.iter().map(|x| x * x).sum::<f64>().sqrt()
+}
+
+/// The roots of `x^2 + b x + c`.
+fn quadratic_roots(b: f64, c: f64) -> [Complex; 2] {
+    let discriminant = b * b / 4.0 - c;
+    let middle = Complex::from(-b / 2.0);
+    let offset = match discriminant < 0.0 {
+        true => Complex::new(0.0, (-discriminant).sqrt()),
+        false => Complex::from(discriminant.sqrt()),
+    };
+    [middle - offset, middle + offset]
+}
+
+/// The roots of `x^3 + a x^2 + b x + c`.
+///
+/// One real root is found by the trigonometric method when all roots are real and
+/// by Cardano's formula otherwise. The other two are the roots of the quadratic
+/// left after dividing it out, which keeps repeated roots accurate.
+fn cubic_roots(a: f64, b: f64, c: f64) -> [Complex; 3] {
+    // x = t - a/3 gives t^3 + p t + q with p = P/3 and q = Q/27, where P and Q
+    // are exact for small integer coefficients
+    let p = 3.0 * b - a * a;
+    let q = 2.0 * a * a * a - 9.0 * a * b + 27.0 * c;
+    let discriminant = q * q + 4.0 * p * p * p;
+    let all_real = discriminant <= 1e-12 * (q * q).max((4.0 * p * p * p).abs());
+    let t = if all_real && p >= 0.0 {
+        0.0
+    } else if all_real {
+        // the root farthest from the others is the best conditioned
+        let radius = 2.0 * (-p).sqrt() / 3.0;
+        let angle = (-q / (2.0 * (-p).powf(1.5))).clamp(-1.0, 1.0).acos() / 3.0;
+        let roots =
+            [0.0, 2.0].map(|k| radius * (angle - k * std::f64::consts::PI / 3.0 * 2.0).cos());
+        roots
+            .into_iter()
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap()
+    } else {
+        let root = discriminant.sqrt() / 54.0;
+        (-q / 54.0 + root).cbrt() + (-q / 54.0 - root).cbrt()
+    };
+    let x = t - a / 3.0;
+    let [y, z] = quadratic_roots(a + x, b + x * (a + x));
+    match all_real {
+        // rounding can make the discriminant of a repeated root slightly negative
+        true => [x, y.re, z.re].map(Complex::from),
+        false => [Complex::from(x), y, z],
+    }
+}
+
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let row = |row: &[f64]| {
+            let entries = row.iter().map(f64::to_string);
+            format!("[{}]", entries.collect::<Vec<_>>().join(", "))
+        };
+        write!(
+            f,
+            "[{}]",
+            self.rows().map(row).collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        let error = ((actual - expected) / expected).abs();
+        assert!(
+            error < 1e-14,
+            "{actual} differs from {expected} by {error:.1e}"
+        );
+    }
+
+    #[test]
+    fn least_squares_line() {
+        // the line through (0, 1), (1, 2), (2, 2), (3, 4) closest to them is y = 0.9x + 0.9
+        let a = Matrix::from_columns(&[vec![0.0, 1.0, 2.0, 3.0], vec![1.0; 4]]);
+        let x = a.least_squares(&[1.0, 2.0, 2.0, 4.0]).unwrap();
+        assert_close(x[0], 0.9);
+        assert_close(x[1], 0.9);
+    }
+
+    #[test]
+    fn solve_square_system() {
+        let a = Matrix::from_rows(&[vec![2.0, 1.0], vec![1.0, 3.0]]).unwrap();
+        let x = a.solve(&[3.0, 5.0]).unwrap();
+        assert_close(x[0], 0.8);
+        assert_close(x[1], 1.4);
+    }
+}
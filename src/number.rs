@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+
+use crate::{
+    builtins::BuiltinFunction,
+    parse::{ArgList, BinaryOp, Expr, Field, Ident, UnaryOp},
+    rational::Rational,
+    units::Unit,
+    value::{Function, Value},
+};
+
+/// A number system the interpreter can evaluate expressions in.
+///
+/// Literals and bound values enter through `from_f64` and `from_value`, and
+/// everything else is computed with the operators and builtins below, so the
+/// same expression can be evaluated as values, plain floats or dual numbers.
+/// Points, lists and functions are only supported by number systems that
+/// override their constructors.
+pub trait Number: Clone + Sized {
+    /// What to call these numbers in errors.
+    const NAME: &'static str;
+
+    fn from_f64(x: f64) -> Self;
+    fn from_value(value: &Value) -> Result<Self>;
+    /// Makes an exact number, for number systems that can represent one.
+    fn from_rational(x: Rational) -> Self {
+        Self::from_f64(x.to_f64())
+    }
+    /// Makes a number with units, which number systems without units read in SI units.
+    fn from_quantity(x: f64, unit: &Unit) -> Self {
+        Self::from_f64(unit.to_si(x))
+    }
+    /// Reads a real number, for arguments that are only defined for floats.
+    fn to_f64(&self) -> Result<f64>;
+
+    fn unary_op(op: UnaryOp, arg: &Self) -> Result<Self>;
+    fn binary_op(op: BinaryOp, left: &Self, right: &Self) -> Result<Self>;
+    /// Applies a builtin other than `ode`, whose arity has not been checked yet.
+    fn apply(builtin: BuiltinFunction, args: &[Self]) -> Result<Self>;
+
+    fn point(x: Self, y: Self) -> Result<Self> {
+        let _ = (x, y);
+        bail!("Points cannot be evaluated as {}s", Self::NAME)
+    }
+
+    fn list(elements: Vec<Self>) -> Result<Self> {
+        let _ = elements;
+        bail!("Lists cannot be evaluated as {}s", Self::NAME)
+    }
+
+    fn elements(&self) -> Result<Vec<Self>> {
+        bail!("Expected a list but got a {}", Self::NAME)
+    }
+
+    /// Makes a function from a lambda and the local variables it refers to.
+    fn closure(args: &ArgList<Ident>, body: &Expr, captured: HashMap<Ident, Self>) -> Result<Self> {
+        let _ = (args, body, captured);
+        bail!("Functions cannot be evaluated as {}s", Self::NAME)
+    }
+
+    fn as_function(&self) -> Result<&Function> {
+        bail!("Expected a function but got a {}", Self::NAME)
+    }
+
+    fn access(&self, field: Field) -> Result<Self> {
+        bail!("Cannot access .{field} of a {}", Self::NAME)
+    }
+
+    /// Converts to `unit`, which only changes how a number is displayed.
+    fn convert(&self, unit: &Unit) -> Result<Self> {
+        let _ = unit;
+        Ok(self.clone())
+    }
+}
+
+/// Plain real numbers, which are cheaper to evaluate with than values but fail
+/// where the result is complex.
+impl Number for f64 {
+    const NAME: &'static str = "real number";
+
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+
+    fn from_value(value: &Value) -> Result<Self> {
+        value.as_number()
+    }
+
+    fn to_f64(&self) -> Result<f64> {
+        Ok(*self)
+    }
+
+    fn unary_op(op: UnaryOp, arg: &Self) -> Result<Self> {
+        Ok(match op {
+            UnaryOp::Negate => -arg,
+            UnaryOp::Plus => *arg,
+            UnaryOp::Degrees => arg.to_radians(),
+        })
+    }
+
+    fn binary_op(op: BinaryOp, left: &Self, right: &Self) -> Result<Self> {
+        Ok(match op {
+            BinaryOp::Add => left + right,
+            BinaryOp::Subtract => left - right,
+            BinaryOp::DotProduct => left * right,
+            BinaryOp::Divide => left / right,
+            BinaryOp::Power if *left < 0.0 && right.fract() != 0.0 => {
+                bail!("Expected a real number but got a complex number")
+            }
+            BinaryOp::Power => left.powf(*right),
+        })
+    }
+
+    fn apply(builtin: BuiltinFunction, args: &[Self]) -> Result<Self> {
+        check_arity(builtin, args)?;
+        let args = args.iter().map(|&x| Value::Number(x)).collect::<Vec<_>>();
+        builtin.apply(&args)?.as_number()
+    }
+}
+
+/// Checks the number of arguments passed to a builtin.
+pub fn check_arity<T>(builtin: BuiltinFunction, args: &[T]) -> Result<()> {
+    if builtin.arity() != args.len() {
+        bail!(
+            "Cannot pass {} arguments to a function taking {} arguments",
+            args.len(),
+            builtin.arity(),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use anyhow::Result;
+
+/// Adaptive Runge-Kutta integrator using the Dormand-Prince 5(4) pair.
+#[derive(Debug, Clone, Copy)]
+pub struct Solver {
+    pub relative_tolerance: f64,
+    pub absolute_tolerance: f64,
+    /// Upper bound on the step size, used to keep plotted curves smooth.
+    pub max_step: f64,
+    /// Integration stops after this many accepted or rejected steps.
+    pub max_steps: usize,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self {
+            relative_tolerance: 1e-8,
+            absolute_tolerance: 1e-10,
+            max_step: f64::INFINITY,
+            max_steps: 10_000,
+        }
+    }
+}
+
+const C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const A: [[f64; 6]; 7] = [
+    [0.0; 6],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+        0.0,
+        0.0,
+    ],
+    [
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+        0.0,
+    ],
+    [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+/// Difference between the fifth and fourth order weights, used as the error estimate.
+const E: [f64; 7] = [
+    71.0 / 57600.0,
+    0.0,
+    -71.0 / 16695.0,
+    71.0 / 1920.0,
+    -17253.0 / 339200.0,
+    22.0 / 525.0,
+    -1.0 / 40.0,
+];
+
+impl Solver {
+    /// Integrates `y' = f(t, y)` from `(t0, y0)` towards `t_end`, which may lie
+    /// on either side of `t0`.
+    ///
+    /// Returns every accepted step including the initial point. Integration ends
+    /// early when `stop` returns true for an accepted point, when the step budget
+    /// runs out, or when the solution stops being finite.
+    pub fn integrate<const N: usize>(
+        &self,
+        mut f: impl FnMut(f64, [f64; N]) -> Result<[f64; N]>,
+        t0: f64,
+        y0: [f64; N],
+        t_end: f64,
+        mut stop: impl FnMut(f64, &[f64; N]) -> bool,
+    ) -> Result<Vec<(f64, [f64; N])>> {
+        let mut points = vec![(t0, y0)];
+        let span = t_end - t0;
+        if span == 0.0 || span.is_nan() {
+            return Ok(points);
+        }
+        let direction = span.signum();
+
+        let (mut t, mut y) = (t0, y0);
+        let mut k = [[0.0; N]; 7];
+        k[0] = f(t, y)?;
+        let mut h = 0.01 * span.abs().min(self.max_step).min(1.0);
+
+        for _ in 0..self.max_steps {
+            if !k[0].iter().all(|x| x.is_finite()) {
+                break;
+            }
+            let remaining = (t_end - t).abs();
+            h = h.min(self.max_step).min(remaining);
+            if h <= 1e-12 * t.abs().max(1.0) {
+                break;
+            }
+            let step = direction * h;
+
+            for stage in 1..7 {
+                let mut y_stage = y;
+                for (i, y_stage) in y_stage.iter_mut().enumerate() {
+                    for (j, a) in A[stage][..stage].iter().enumerate() {
+                        *y_stage += step * a * k[j][i];
+                    }
+                }
+                k[stage] = f(t + C[stage] * step, y_stage)?;
+            }
+            // the last stage is evaluated at the fifth order solution itself
+            let mut y_new = y;
+            for (i, y_new) in y_new.iter_mut().enumerate() {
+                for (j, a) in A[6].iter().enumerate() {
+                    *y_new += step * a * k[j][i];
+                }
+            }
+
+            let mut error = 0.0;
+            for i in 0..N {
+                let estimate: f64 = (0..7).map(|j| step * E[j] * k[j][i]).sum();
+                let scale = self.absolute_tolerance
+                    + self.relative_tolerance * y[i].abs().max(y_new[i].abs());
+                error += (estimate / scale).powi(2);
+            }
+            let error = (error / N as f64).sqrt();
+            if error.is_nan() {
+                break;
+            }
+
+            if error <= 1.0 {
+                t += step;
+                y = y_new;
+                k[0] = k[6];
+                points.push((t, y));
+                if stop(t, &y) || (t_end - t).abs() <= 1e-12 * t.abs().max(1.0) {
+                    break;
+                }
+            }
+            let factor = match error {
+                0.0 => 5.0,
+                error => (0.9 * error.powf(-0.2)).clamp(0.2, 5.0),
+            };
+            h *= factor;
+        }
+
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{E, FRAC_PI_2};
+
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        let error = ((actual - expected) / expected).abs();
+        assert!(
+            error < 1e-7,
+            "{actual} differs from {expected} by {error:.1e}"
+        );
+    }
+
+    #[test]
+    fn exponential_growth() {
+        let points = Solver::default()
+            .integrate(|_, [y]| Ok([y]), 0.0, [1.0], 1.0, |_, _| false)
+            .unwrap();
+        let &(t, [y]) = points.last().unwrap();
+        assert_eq!(t, 1.0);
+        assert_close(y, E);
+    }
+
+    #[test]
+    fn backwards_oscillation() {
+        // y'' = -y with y(0) = 0 and y'(0) = 1 is sin, here followed back to -π/2
+        let points = Solver::default()
+            .integrate(
+                |_, [y, v]| Ok([v, -y]),
+                0.0,
+                [0.0, 1.0],
+                -FRAC_PI_2,
+                |_, _| false,
+            )
+            .unwrap();
+        let &(_, [y, v]) = points.last().unwrap();
+        assert_close(y, -1.0);
+        assert!(v.abs() < 1e-7);
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    cell::RefCell,
     cmp::{self, Ordering},
     fmt::{self, Binary},
     hint::unreachable_unchecked,
@@ -9,6 +10,9 @@ use anyhow::{Result, bail};
 use ecow::{EcoString, EcoVec, eco_vec};
 
 use crate::lex::{Token, lex};
+use crate::rational::Rational;
+use crate::span::{Span, bail_at};
+use crate::units::Unit;
 
 pub type Ident = ecow::EcoString;
 
@@ -20,6 +24,8 @@ pub enum BinaryOp {
     DotProduct,
     // CrossProduct,
     Divide,
+    /// Binds tighter than signs and associates to the right.
+    Power,
 }
 
 impl BinaryOp {
@@ -30,19 +36,49 @@ impl BinaryOp {
             // Self::Multiply => 2,
             Self::DotProduct => 2,
             Self::Divide => 2,
+            Self::Power => 4,
         }
     }
 }
 
+/// Binding power of a sign, between that of products and powers.
+const UNARY_BINDING_POWER: u8 = 3;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnaryOp {
     Negate,
     Plus,
+    /// The postfix `°`, which converts degrees to radians.
+    Degrees,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    X,
+    Y,
 }
 
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+/// Expressions are equal if they have the same structure, wherever they are in the input.
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
     Lit(f64),
+    /// A number literal in exact mode, as the fraction it was written as, which
+    /// literals are rewritten to when the item they are in is added.
+    Rational(Rational),
+    /// A number literal followed by a unit, like `3 m/s`.
+    Quantity(f64, Unit),
     Variable(Ident),
     Call {
         func: Ident,
@@ -57,6 +93,51 @@ pub enum Expr {
         left: Box<Expr>,
         right: Box<Expr>,
     },
+    Point(Box<Expr>, Box<Expr>),
+    List(Vec<Expr>),
+    /// `[a...b]` or `[a, b...c]`, the numbers from `a` up or down to the last one
+    /// not past `c`, in steps of 1 or of `b - a`.
+    Range {
+        first: Box<Expr>,
+        second: Option<Box<Expr>>,
+        last: Box<Expr>,
+    },
+    /// `[f for k in L]`, the list of `f` with `k` bound to each element of `L` in turn.
+    Comprehension {
+        body: Box<Expr>,
+        var: Ident,
+        list: Box<Expr>,
+    },
+    Access {
+        arg: Box<Expr>,
+        field: Field,
+    },
+    /// `L[i]`, the `i`th element of a list, counting from 1.
+    Index {
+        list: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// `d/dx arg`, replaced by the derivative of `arg` before the expression is checked.
+    Derivative {
+        arg: Box<Expr>,
+        var: Ident,
+    },
+    /// `arg to km/h`, which displays `arg` in another unit of the same dimension.
+    Convert {
+        arg: Box<Expr>,
+        unit: Unit,
+    },
+    /// `x -> x^2` or `(x, y) -> x*y`, a function that can be passed around as a value.
+    Lambda {
+        args: ArgList<Ident>,
+        body: Box<Expr>,
+    },
+    /// `let a = 1, b = a + 1 in a*b`, or `a*b where a = 1, b = a + 1`, which evaluates
+    /// each binding once, in order, and then the body with the names bound locally.
+    Let {
+        bindings: ArgList<(Ident, Expr)>,
+        body: Box<Expr>,
+    },
 }
 
 pub enum TopLevelItem {
@@ -70,27 +151,88 @@ pub enum TopLevelItem {
         args: ArgList<Ident>,
         body: Expr,
     },
+    /// `a(0) := 1`, a base case of the sequence `a`, whose other terms are given by a
+    /// recursive definition like `a(n) := 2*a(n-1)`.
+    BaseCase {
+        name: Ident,
+        index: i64,
+        body: Expr,
+    },
+    /// `y' = f(x, y)` or `x' = f(t, x, y), y' = g(t, x, y)`, followed by `at`
+    /// and the initial points of the trajectories to draw.
+    DifferentialEquation {
+        equations: ArgList<(Ident, Expr)>,
+        initial_points: ArgList<Expr>,
+    },
+    /// `solve(f = g, x, start)` or `solve(f = g, h = k, x, y, start)`, which solves the
+    /// equations for the unknowns, optionally binding the solutions to a name as in
+    /// `r := solve(...)`. The start is a number or point, or a list of them.
+    Solve {
+        name: Option<Ident>,
+        equations: ArgList<(Expr, Expr)>,
+        unknowns: ArgList<Ident>,
+        start: Option<Expr>,
+    },
+    /// `y1 ~ a*x1 + b`, which fits the unknowns of the model on the right to the list of
+    /// data on the left by least squares, and binds them to the fitted values.
+    Fit {
+        data: Expr,
+        model: Expr,
+    },
+    /// A line of settings, which change how the following items are evaluated or displayed.
+    Settings(Vec<Setting>),
+    /// An item followed by settings that only apply to it, like `pi/4 #fractions`.
+    WithSettings {
+        item: Box<TopLevelItem>,
+        settings: Vec<Setting>,
+    },
 }
 
+/// `#name args...`, where the arguments are words or numbers.
 #[derive(Debug, Clone)]
+pub struct Setting {
+    pub name: Ident,
+    pub args: Vec<Expr>,
+}
+
+pub enum SettingArg<'a> {
+    Word(&'a str),
+    Number(f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ArgList<T>(Vec<T>);
 
 struct Parser {
-    tokens: Vec<Token>,
+    tokens: Vec<(Token, Span)>,
+    /// End of the last consumed token.
+    prev_end: usize,
 }
 
 impl Parser {
-    pub fn new(mut tokens: Vec<Token>) -> Self {
+    pub fn new(mut tokens: Vec<(Token, Span)>) -> Self {
         tokens.reverse();
-        Self { tokens }
+        Self {
+            tokens,
+            prev_end: 0,
+        }
     }
 
     fn peek(&self) -> Option<&Token> {
-        self.tokens.last()
+        self.tokens.last().map(|(token, _)| token)
+    }
+
+    /// Start of the next token.
+    fn peek_start(&self) -> usize {
+        self.tokens
+            .last()
+            .map_or(self.prev_end, |(_, span)| span.start)
     }
 
     fn next(&mut self) -> Option<Token> {
-        self.tokens.pop()
+        let (token, span) = self.tokens.pop()?;
+        self.prev_end = span.end;
+        Some(token)
     }
 
     fn next_if(&mut self, f: impl Fn(&Token) -> bool) -> Option<Token> {
@@ -112,51 +254,151 @@ impl Parser {
 
     pub fn parse_expr(&mut self, last_op: Option<BinaryOp>) -> Result<Expr> {
         // eprintln!("parsing expr {:?}", self.tokens);
+        let start = self.peek_start();
         let signs = self.consume_while(|t| match t {
             Token::Minus => Some(true),
             Token::Plus => Some(false),
             _ => None,
         });
-        let mut left = match self.next() {
+        let primary_start = self.peek_start();
+        let kind = match self.next() {
+            Some(Token::LeftParen) if let Some(args) = self.parse_lambda_args() => {
+                ExprKind::Lambda {
+                    args,
+                    body: Box::new(self.parse_expr(None)?),
+                }
+            }
             Some(Token::LeftParen) => {
                 let inner = self.parse_expr(None)?;
                 match self.next() {
-                    Some(Token::RightParen) => {}
+                    Some(Token::RightParen) => inner.kind,
+                    Some(Token::Comma) => {
+                        let second = self.parse_expr(None)?;
+                        match self.next() {
+                            Some(Token::RightParen) => {}
+                            Some(token) => bail!("{token} is not allowed here"),
+                            None => bail!("Unclosed parenthesis"),
+                        }
+                        ExprKind::Point(Box::new(inner), Box::new(second))
+                    }
                     Some(token) => bail!("{token} is not allowed here"),
                     None => bail!("Unclosed parenthesis"),
                 }
-                inner
             }
-            Some(Token::NumLit(s)) => Expr::Lit(s.parse().expect("Failed to parse float literal")),
-            Some(Token::Ident(name)) => match self.peek() {
-                Some(Token::LeftParen) => {
-                    self.next();
-                    if self.peek() == Some(&Token::RightParen) {
-                        bail!("Cannot call {name} with no arguments");
+            Some(Token::NumLit(s)) => {
+                let x = s.parse().expect("Failed to parse float literal");
+                match self.parse_unit()? {
+                    Some(unit) => ExprKind::Quantity(x, unit),
+                    None => ExprKind::Lit(x),
+                }
+            }
+            Some(Token::Ident(name))
+                if name == "d"
+                    && matches!(
+                        self.tokens.as_slice(),
+                        [.., (Token::Ident(var), _), (Token::Slash, _)]
+                            if var.len() > 1 && var.starts_with('d')
+                    ) =>
+            {
+                self.next();
+                let Some(Token::Ident(var)) = self.next() else {
+                    unreachable!()
+                };
+                // `d/dx` applies to the following product, so `d/dx x^2 + 1` is `(d/dx x^2) + 1`
+                let arg = self.parse_expr(Some(BinaryOp::Add))?;
+                ExprKind::Derivative {
+                    arg: Box::new(arg),
+                    var: Ident::from(&var[1..]),
+                }
+            }
+            Some(Token::Ident(name))
+                if name == "let"
+                    && matches!(
+                        self.tokens.as_slice(),
+                        [.., (Token::Equals, _), (Token::Ident(_), _)]
+                    ) =>
+            {
+                let bindings = self.parse_bindings()?;
+                match self.next() {
+                    Some(Token::Ident(keyword)) if keyword == "in" => {}
+                    Some(token) => bail!("Expected 'in' after the bindings but got {token}"),
+                    None => bail!("Expected 'in' followed by an expression after the bindings"),
+                }
+                ExprKind::Let {
+                    bindings,
+                    body: Box::new(self.parse_expr(None)?),
+                }
+            }
+            Some(Token::Ident(mut name)) => match self.peek() {
+                Some(Token::Prime) => {
+                    // `f'(x)` calls the derivative of `f`
+                    while self.next_if(|t| *t == Token::Prime).is_some() {
+                        name.push('\'');
                     }
-                    let mut args = ArgList::from_head(self.parse_expr(None)?);
-                    loop {
-                        match self.next() {
-                            Some(Token::RightParen) => break,
-                            Some(Token::Comma) => {}
-                            Some(token) => bail!("Expected comma but got {token}"),
-                            None => bail!("Unclosed parenthesis"),
-                        }
-                        args.push(self.parse_expr(None)?);
+                    if self.peek() != Some(&Token::LeftParen) {
+                        bail!("Expected arguments after {name}");
+                    }
+                    self.parse_call(name)?
+                }
+                Some(Token::LeftParen) => self.parse_call(name)?,
+                Some(Token::Arrow) => {
+                    self.next();
+                    // the body of a lambda extends as far as possible
+                    ExprKind::Lambda {
+                        args: ArgList::from_head(name),
+                        body: Box::new(self.parse_expr(None)?),
                     }
-                    Expr::Call { func: name, args }
                 }
-                _ => Expr::Variable(name),
+                _ => ExprKind::Variable(name),
             },
+            Some(Token::LeftBracket) => self.parse_list()?,
             Some(t) => bail!("unknown token {t:?}"),
             None => bail!("cannot parse empty expression"),
         };
+        let mut left = Expr::new(kind, Span::new(primary_start, self.prev_end));
+        loop {
+            let kind = match self.peek() {
+                Some(Token::Dot) => {
+                    self.next();
+                    let field = match self.next() {
+                        Some(Token::Ident(name)) if name == "x" => Field::X,
+                        Some(Token::Ident(name)) if name == "y" => Field::Y,
+                        Some(token) => bail!("Expected .x or .y but got .{token}"),
+                        None => bail!("Expected .x or .y"),
+                    };
+                    ExprKind::Access {
+                        arg: Box::new(left),
+                        field,
+                    }
+                }
+                Some(Token::LeftBracket) => {
+                    self.next();
+                    let index = self.parse_expr(None)?;
+                    self.expect_right_bracket()?;
+                    ExprKind::Index {
+                        list: Box::new(left),
+                        index: Box::new(index),
+                    }
+                }
+                _ => break,
+            };
+            left = Expr::new(kind, Span::new(primary_start, self.prev_end));
+        }
+        while self.next_if(|t| *t == Token::Degree).is_some() {
+            left = Expr::un_op(UnaryOp::Degrees, left);
+            left.span.end = self.prev_end;
+        }
+        if self.next_if(|t| *t == Token::Caret).is_some() {
+            let exponent = self.parse_expr(Some(BinaryOp::Power))?;
+            left = Expr::bin_op(BinaryOp::Power, left, exponent);
+        }
         for sign in signs {
             let op = match sign {
                 true => UnaryOp::Negate,
                 false => UnaryOp::Plus,
             };
             left = Expr::un_op(op, left);
+            left.span.start = start;
         }
         loop {
             let op = match self.peek() {
@@ -164,16 +406,374 @@ impl Parser {
                 Some(Token::Minus) => BinaryOp::Subtract,
                 Some(Token::Cdot) => BinaryOp::DotProduct,
                 Some(Token::Slash) => BinaryOp::Divide,
-                _ => break Ok(left),
+                _ => break,
             };
             if Some(op.binding_power()) > last_op.map(|op| op.binding_power()) {
                 self.next();
                 let right = self.parse_expr(Some(op))?;
                 left = Expr::bin_op(op, left, right);
             } else {
-                break Ok(left);
+                break;
+            }
+        }
+        // a conversion applies to the whole expression before it
+        if last_op.is_none() && matches!(self.peek(), Some(Token::Ident(word)) if word == "to") {
+            self.next();
+            let Some(unit) = self.parse_unit()? else {
+                match self.next() {
+                    Some(token) => bail!("Expected a unit after 'to' but got {token}"),
+                    None => bail!("Expected a unit after 'to'"),
+                }
+            };
+            let span = Span::new(left.span.start, self.prev_end);
+            let arg = Box::new(left);
+            left = Expr::new(ExprKind::Convert { arg, unit }, span);
+        }
+        if last_op.is_none() && matches!(self.peek(), Some(Token::Ident(word)) if word == "where") {
+            self.next();
+            let bindings = self.parse_bindings()?;
+            let span = Span::new(left.span.start, self.prev_end);
+            let body = Box::new(left);
+            left = Expr::new(ExprKind::Let { bindings, body }, span);
+        }
+        Ok(left)
+    }
+
+    /// Parses what follows `[`: a list like `[1, 2, 3]`, a range like `[1...10]` or
+    /// `[0, 0.1 ... 1]`, or a comprehension like `[k^2 for k in L]`.
+    fn parse_list(&mut self) -> Result<ExprKind> {
+        let mut elements = Vec::new();
+        if self.next_if(|t| *t == Token::RightBracket).is_some() {
+            return Ok(ExprKind::List(elements));
+        }
+        loop {
+            elements.push(self.parse_expr(None)?);
+            match self.next() {
+                Some(Token::RightBracket) => break,
+                Some(Token::Comma) => {}
+                Some(Token::Ellipsis) => {
+                    let last = Box::new(self.parse_expr(None)?);
+                    self.expect_right_bracket()?;
+                    let mut elements = elements.into_iter().map(Box::new);
+                    return match (elements.next(), elements.next(), elements.next()) {
+                        (Some(first), second, None) => Ok(ExprKind::Range {
+                            first,
+                            second,
+                            last,
+                        }),
+                        _ => bail!("Expected one or two numbers before ... in a range"),
+                    };
+                }
+                Some(Token::Ident(keyword)) if keyword == "for" && elements.len() == 1 => {
+                    let var = match self.next() {
+                        Some(Token::Ident(name)) => name,
+                        Some(token) => bail!("Expected the name of a variable but got {token}"),
+                        None => bail!("Expected the name of a variable after 'for'"),
+                    };
+                    match self.next() {
+                        Some(Token::Ident(keyword)) if keyword == "in" => {}
+                        Some(token) => bail!("Expected 'in' after 'for {var}' but got {token}"),
+                        None => bail!("Expected 'in' followed by a list after 'for {var}'"),
+                    }
+                    let list = Box::new(self.parse_expr(None)?);
+                    self.expect_right_bracket()?;
+                    return Ok(ExprKind::Comprehension {
+                        body: Box::new(elements.remove(0)),
+                        var,
+                        list,
+                    });
+                }
+                Some(token) => bail!("Expected comma but got {token}"),
+                None => bail!("Unclosed bracket"),
             }
         }
+        Ok(ExprKind::List(elements))
+    }
+
+    fn expect_right_bracket(&mut self) -> Result<()> {
+        match self.next() {
+            Some(Token::RightBracket) => Ok(()),
+            Some(token) => bail!("Expected ] but got {token}"),
+            None => bail!("Unclosed bracket"),
+        }
+    }
+
+    /// Parses the bindings of `let` or `where`, like `a = 1, b = a + 1`.
+    fn parse_bindings(&mut self) -> Result<ArgList<(Ident, Expr)>> {
+        let mut bindings = Vec::new();
+        loop {
+            let name = match self.next() {
+                Some(Token::Ident(name)) => name,
+                Some(token) => bail!("Expected the name of a variable but got {token}"),
+                None => bail!("Expected the name of a variable"),
+            };
+            if self.next_if(|t| *t == Token::Equals).is_none() {
+                bail!("Expected = after {name}");
+            }
+            bindings.push((name, self.parse_expr(None)?));
+            // a comma continues the bindings only if another one follows, as in `f(a where a = 1, 2)`
+            match self.tokens.as_slice() {
+                [
+                    ..,
+                    (Token::Equals, _),
+                    (Token::Ident(_), _),
+                    (Token::Comma, _),
+                ] => self.next(),
+                _ => break,
+            };
+        }
+        Ok(ArgList::from_vec(bindings))
+    }
+
+    /// Parses a unit like `km/h` or `kg*m/s^2`, if the next token is the name of one.
+    ///
+    /// Units only continue with `*` or `/` followed by another unit, so `6 m / t`
+    /// divides `6 m` by `t`.
+    fn parse_unit(&mut self) -> Result<Option<Unit>> {
+        let Some(mut unit) = self.parse_unit_power()? else {
+            return Ok(None);
+        };
+        loop {
+            let op = match self.tokens.as_slice() {
+                [
+                    ..,
+                    (Token::Ident(name), _),
+                    (op @ (Token::Cdot | Token::Slash), _),
+                ] if Unit::named(name).is_some() => op.clone(),
+                _ => break,
+            };
+            self.next();
+            let factor = self.parse_unit_power()?.expect("checked to be a unit");
+            unit = match op {
+                Token::Cdot => unit.mul(&factor),
+                _ => unit.div(&factor),
+            };
+        }
+        Ok(Some(unit))
+    }
+
+    /// Parses a single unit with an optional integer power, like `s^-2`.
+    fn parse_unit_power(&mut self) -> Result<Option<Unit>> {
+        let unit = match self.peek() {
+            Some(Token::Ident(name)) => Unit::named(name),
+            _ => None,
+        };
+        let Some(unit) = unit else {
+            return Ok(None);
+        };
+        self.next();
+        if self.next_if(|t| *t == Token::Caret).is_none() {
+            return Ok(Some(unit));
+        }
+        let sign = match self.next_if(|t| *t == Token::Minus) {
+            Some(_) => -1,
+            None => 1,
+        };
+        let power = match self.next() {
+            Some(Token::NumLit(n)) => n.parse::<i8>().ok().and_then(|n| unit.pow(sign * n)),
+            _ => None,
+        };
+        match power {
+            Some(power) => Ok(Some(power)),
+            None => bail!("Expected a small integer power of {unit}"),
+        }
+    }
+
+    /// Parses the parameters of a lambda like `(x, y) -> x*y` up to and including the
+    /// arrow, if the tokens after an opening parenthesis start one.
+    fn parse_lambda_args(&mut self) -> Option<ArgList<Ident>> {
+        let mut args = Vec::new();
+        let mut tokens = self.tokens.iter().rev().map(|(token, _)| token);
+        loop {
+            match tokens.next()? {
+                Token::Ident(name) => args.push(name.clone()),
+                _ => return None,
+            }
+            match tokens.next()? {
+                Token::Comma => {}
+                Token::RightParen => break,
+                _ => return None,
+            }
+        }
+        if *tokens.next()? != Token::Arrow {
+            return None;
+        }
+        // each parameter is followed by a comma or the closing parenthesis
+        for _ in 0..2 * args.len() + 1 {
+            self.next();
+        }
+        Some(ArgList::from_vec(args))
+    }
+
+    /// Parses the arguments of a call to `func`, starting at the opening parenthesis.
+    fn parse_call(&mut self, func: Ident) -> Result<ExprKind> {
+        self.next();
+        if self.peek() == Some(&Token::RightParen) {
+            bail!("Cannot call {func} with no arguments");
+        }
+        let mut args = ArgList::from_head(self.parse_expr(None)?);
+        loop {
+            match self.next() {
+                Some(Token::RightParen) => break,
+                Some(Token::Comma) => {}
+                Some(token) => bail!("Expected comma but got {token}"),
+                None => bail!("Unclosed parenthesis"),
+            }
+            args.push(self.parse_expr(None)?);
+        }
+        Ok(ExprKind::Call { func, args })
+    }
+
+    fn parse_differential_equation(&mut self) -> Result<TopLevelItem> {
+        let mut parse_equation = |parser: &mut Self| {
+            let name = match parser.next() {
+                Some(Token::Ident(name)) => name,
+                Some(token) => bail!("Expected a derivative but got {token}"),
+                None => bail!("Expected a derivative"),
+            };
+            match (parser.next(), parser.next()) {
+                (Some(Token::Prime), Some(Token::Equals)) => {}
+                _ => bail!("Expected an equation of the form {name}' = ..."),
+            }
+            Ok((name, parser.parse_expr(None)?))
+        };
+        let mut equations = ArgList::from_head(parse_equation(self)?);
+        while self.next_if(|t| *t == Token::Comma).is_some() {
+            equations.push(parse_equation(self)?);
+        }
+
+        match self.next() {
+            Some(Token::Ident(keyword)) if keyword == "at" => {}
+            Some(token) => bail!("Expected 'at' followed by initial points but got {token}"),
+            None => bail!("Differential equation needs initial points, given as 'at (x, y)'"),
+        }
+        let mut initial_points = ArgList::from_head(self.parse_expr(None)?);
+        while self.next_if(|t| *t == Token::Comma).is_some() {
+            initial_points.push(self.parse_expr(None)?);
+        }
+        Ok(TopLevelItem::DifferentialEquation {
+            equations,
+            initial_points,
+        })
+    }
+
+    /// Parses `a(0) := ...`, a base case of a sequence, whose index is an integer.
+    fn parse_base_case(&mut self) -> Result<TopLevelItem> {
+        let Some(Token::Ident(name)) = self.next() else {
+            unreachable!("checked before parsing a base case")
+        };
+        self.next();
+        let sign = match self.next_if(|t| *t == Token::Minus) {
+            Some(_) => -1.0,
+            None => 1.0,
+        };
+        let Some(Token::NumLit(index)) = self.next() else {
+            unreachable!("checked before parsing a base case")
+        };
+        let index = sign * index.parse::<f64>().expect("Failed to parse float literal");
+        if index.fract() != 0.0 {
+            bail!("The index of a base case must be an integer, not {index}");
+        }
+        self.next(); // pop Token::RightParen
+        self.next(); // pop Token::Assign
+        Ok(TopLevelItem::BaseCase {
+            name,
+            index: index as i64,
+            body: self.parse_expr(None)?,
+        })
+    }
+
+    /// Parses the arguments of `solve(...)`: the equations, as many unknowns and an
+    /// optional start.
+    fn parse_solve(&mut self, name: Option<Ident>) -> Result<TopLevelItem> {
+        self.next();
+        self.next();
+        let mut equations = Vec::new();
+        let mut rest = Vec::new();
+        loop {
+            let left = self.parse_expr(None)?;
+            match self.next_if(|t| *t == Token::Equals) {
+                Some(_) if !rest.is_empty() => bail!("Equations must come before the unknowns"),
+                Some(_) => equations.push((left, self.parse_expr(None)?)),
+                None => rest.push(left),
+            }
+            match self.next() {
+                Some(Token::RightParen) => break,
+                Some(Token::Comma) => {}
+                Some(token) => bail!("Expected comma but got {token}"),
+                None => bail!("Unclosed parenthesis"),
+            }
+        }
+        if equations.is_empty() {
+            bail!(
+                "Expected an equation to solve, like solve(x^2 = 2, x), or linsolve(A, b) for \
+                 the linear system A*x = b"
+            );
+        }
+        if rest.len() < equations.len() || rest.len() > equations.len() + 1 {
+            bail!(
+                "Expected {} unknowns and an optional start after the equations",
+                equations.len()
+            );
+        }
+        let start = (rest.len() > equations.len()).then(|| rest.pop().unwrap());
+        let unknowns = rest
+            .into_iter()
+            .map(|unknown| match unknown.kind {
+                ExprKind::Variable(name) => Ok(name),
+                _ => bail!("Expected the name of an unknown but got {unknown}"),
+            })
+            .collect::<Result<_>>()?;
+        Ok(TopLevelItem::Solve {
+            name,
+            equations: ArgList::from_vec(equations),
+            unknowns: ArgList::from_vec(unknowns),
+            start,
+        })
+    }
+
+    /// Parses `data ~ model`, a fit of the unknowns of the model to the data.
+    fn parse_fit(&mut self) -> Result<TopLevelItem> {
+        let data = self.parse_expr(None)?;
+        match self.next() {
+            Some(Token::Tilde) => {}
+            Some(token) => bail!("Expected ~ but got {token}"),
+            None => bail!("Expected ~ followed by a model to fit"),
+        }
+        Ok(TopLevelItem::Fit {
+            data,
+            model: self.parse_expr(None)?,
+        })
+    }
+
+    /// Parses settings until the end of the line.
+    fn parse_settings(&mut self) -> Result<Vec<Setting>> {
+        let mut settings = Vec::new();
+        while self.next_if(|t| *t == Token::Hash).is_some() {
+            let name = match self.next() {
+                Some(Token::Ident(name)) => name,
+                Some(token) => bail!("Expected the name of a setting but got {token}"),
+                None => bail!("Expected the name of a setting"),
+            };
+            let mut args = Vec::new();
+            while !matches!(self.peek(), Some(Token::Newline | Token::Hash) | None) {
+                args.push(self.parse_expr(None)?);
+            }
+            settings.push(Setting { name, args });
+        }
+        Ok(settings)
+    }
+
+    /// Attaches the settings at the end of the line to an item.
+    fn with_settings(&mut self, item: TopLevelItem) -> Result<TopLevelItem> {
+        let settings = self.parse_settings()?;
+        Ok(match settings.is_empty() {
+            true => item,
+            false => TopLevelItem::WithSettings {
+                item: Box::new(item),
+                settings,
+            },
+        })
     }
 
     pub fn parse(&mut self) -> Result<Vec<TopLevelItem>> {
@@ -186,60 +786,129 @@ impl Parser {
             // parse top level item
             // println!("parsing item {:?}", self.tokens);
 
-            let assignment = if let Some(pos) = self
+            if self.peek() == Some(&Token::Hash) {
+                items.push(TopLevelItem::Settings(self.parse_settings()?));
+            } else if let [
+                ..,
+                (Token::Equals, _),
+                (Token::Prime, _),
+                (Token::Ident(_), _),
+            ] = self.tokens.as_slice()
+            {
+                let item = self.parse_differential_equation()?;
+                items.push(self.with_settings(item)?);
+            } else if matches!(
+                self.tokens.as_slice(),
+                [
+                    ..,
+                    (Token::Assign, _),
+                    (Token::RightParen, _),
+                    (Token::NumLit(_), _),
+                    (Token::LeftParen, _),
+                    (Token::Ident(_), _),
+                ] | [
+                    ..,
+                    (Token::Assign, _),
+                    (Token::RightParen, _),
+                    (Token::NumLit(_), _),
+                    (Token::Minus, _),
+                    (Token::LeftParen, _),
+                    (Token::Ident(_), _),
+                ]
+            ) {
+                let item = self.parse_base_case()?;
+                items.push(self.with_settings(item)?);
+            } else if self
                 .tokens
                 .iter()
                 .rev()
-                .take_while(|t| **t != Token::Newline)
-                .position(|t| *t == Token::Assign)
+                .take_while(|(token, _)| *token != Token::Newline)
+                .any(|(token, _)| *token == Token::Tilde)
             {
-                let mut tokens = self.tokens.drain(self.tokens.len() - pos..).rev();
-                // println!("{:?}", tokens.collect::<Vec<_>>());
-                let name = match tokens.next() {
-                    Some(Token::Ident(name)) => name,
-                    Some(token) => bail!("Unknown token {token} in assignment"),
-                    None => bail!("Empty assignment"),
-                };
-                let args = match tokens.next() {
-                    Some(Token::LeftParen) => {
-                        let mut args = ArgList::from_head(match tokens.next() {
-                            Some(Token::Ident(name)) => name,
-                            Some(token) => bail!("Unexpected token {token} in argument list"),
-                            None => bail!("Unclosed argument list"),
-                        });
-                        loop {
-                            match tokens.next() {
-                                Some(Token::Comma) => {}
-                                Some(Token::RightParen) => break,
-                                Some(token) => bail!("Unknown token {token} in argument list"),
+                let item = self.parse_fit()?;
+                items.push(self.with_settings(item)?);
+            } else {
+                let assignment = if let Some(pos) = self
+                    .tokens
+                    .iter()
+                    .rev()
+                    .take_while(|(t, _)| *t != Token::Newline)
+                    .position(|(t, _)| *t == Token::Assign)
+                {
+                    let mut tokens = self
+                        .tokens
+                        .drain(self.tokens.len() - pos..)
+                        .rev()
+                        .map(|(t, _)| t);
+                    // println!("{:?}", tokens.collect::<Vec<_>>());
+                    let name = match tokens.next() {
+                        Some(Token::Ident(name)) => name,
+                        Some(token) => bail!("Unknown token {token} in assignment"),
+                        None => bail!("Empty assignment"),
+                    };
+                    let args = match tokens.next() {
+                        Some(Token::LeftParen) => {
+                            let mut args = ArgList::from_head(match tokens.next() {
+                                Some(Token::Ident(name)) => name,
+                                Some(token) => bail!("Unexpected token {token} in argument list"),
                                 None => bail!("Unclosed argument list"),
+                            });
+                            loop {
+                                match tokens.next() {
+                                    Some(Token::Comma) => {}
+                                    Some(Token::RightParen) => break,
+                                    Some(token) => bail!("Unknown token {token} in argument list"),
+                                    None => bail!("Unclosed argument list"),
+                                }
+                                match tokens.next() {
+                                    Some(Token::Ident(name)) => args.push(name),
+                                    Some(token) => bail!("Expected parameter but got {token}"),
+                                    None => bail!("Unclosed argument list"),
+                                }
                             }
-                            match tokens.next() {
-                                Some(Token::Ident(name)) => args.push(name),
-                                Some(token) => bail!("Expected parameter but got {token}"),
-                                None => bail!("Unclosed argument list"),
+                            Some(args)
+                        }
+                        Some(token) => bail!("Unknown token {token} in assignment"),
+                        None => None,
+                    };
+                    drop(tokens);
+                    assert_eq!(Some(Token::Assign), self.next()); // pop Token::Assign
+                    Some((name, args))
+                } else {
+                    None
+                };
+
+                // eprintln!("parsed assignment {assignment:?}");
+                // eprintln!("parsing body {:?}", self.tokens);
+                let is_solve = matches!(
+                    self.tokens.as_slice(),
+                    [.., (Token::LeftParen, _), (Token::Ident(name), _)] if name == "solve"
+                );
+                // a function can't be defined as the solution of an equation
+                let item = match assignment {
+                    Some((name, None)) if is_solve => self.parse_solve(Some(name))?,
+                    None if is_solve => self.parse_solve(None)?,
+                    assignment => {
+                        let body = self.parse_expr(None)?;
+                        match assignment {
+                            Some((name, Some(args))) => {
+                                TopLevelItem::FunctionDef { name, args, body }
                             }
+                            // `f := x -> x^2` defines a function just like `f(x) := x^2`
+                            Some((name, None)) => match body.kind {
+                                ExprKind::Lambda { args, body } => TopLevelItem::FunctionDef {
+                                    name,
+                                    args,
+                                    body: *body,
+                                },
+                                _ => TopLevelItem::Assignment { name, body },
+                            },
+                            None => TopLevelItem::Expression(body),
                         }
-                        Some(args)
                     }
-                    Some(token) => bail!("Unknown token {token} in assignment"),
-                    None => None,
                 };
-                drop(tokens);
-                assert_eq!(Some(Token::Assign), self.tokens.pop()); // pop Token::Assign
-                Some((name, args))
-            } else {
-                None
-            };
-
-            // eprintln!("parsed assignment {assignment:?}");
-            // eprintln!("parsing body {:?}", self.tokens);
-            let body = self.parse_expr(None)?;
-            items.push(match assignment {
-                Some((name, Some(args))) => TopLevelItem::FunctionDef { name, args, body },
-                Some((name, None)) => TopLevelItem::Assignment { name, body },
-                None => TopLevelItem::Expression(body),
-            });
+                items.push(self.with_settings(item)?);
+            }
 
             match self.peek() {
                 Some(Token::Newline) => {
@@ -260,73 +929,411 @@ pub fn parse(input: &str) -> Result<Vec<TopLevelItem>> {
     Parser::new(tokens).parse()
 }
 
+impl Setting {
+    pub fn args(&self) -> Result<Vec<SettingArg<'_>>> {
+        self.args
+            .iter()
+            .map(|arg| match &arg.kind {
+                ExprKind::Variable(word) => Ok(SettingArg::Word(word)),
+                ExprKind::Lit(x) => Ok(SettingArg::Number(*x)),
+                _ => bail_at!(arg.span, "Expected a word or a number but got {arg}"),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.name)?;
+        for arg in self.args.iter() {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Display for TopLevelItem {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Expression(expr) => write!(f, "{expr}"),
             Self::Assignment { name, body } => write!(f, "{name} := {body}"),
             Self::FunctionDef { name, args, body } => write!(f, "{name}({args}) := {body}"),
+            Self::BaseCase { name, index, body } => write!(f, "{name}({index}) := {body}"),
+            Self::DifferentialEquation {
+                equations,
+                initial_points,
+            } => {
+                for (i, (name, body)) in equations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}' = {body}")?;
+                }
+                write!(f, " at {initial_points}")
+            }
+            Self::Solve {
+                name,
+                equations,
+                unknowns,
+                start,
+            } => {
+                if let Some(name) = name {
+                    write!(f, "{name} := ")?;
+                }
+                write!(f, "solve(")?;
+                for (left, right) in equations.iter() {
+                    write!(f, "{left} = {right}, ")?;
+                }
+                write!(f, "{unknowns}")?;
+                if let Some(start) = start {
+                    write!(f, ", {start}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Fit { data, model } => write!(f, "{data} ~ {model}"),
+            Self::Settings(settings) => {
+                for (i, setting) in settings.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{setting}")?;
+                }
+                Ok(())
+            }
+            Self::WithSettings { item, settings } => {
+                write!(f, "{item}")?;
+                for setting in settings {
+                    write!(f, " {setting}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
 impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
     pub fn un_op(op: UnaryOp, arg: Self) -> Self {
+        let span = arg.span;
         let arg = Box::new(arg);
-        Self::UnOp { op, arg }
+        Self::new(ExprKind::UnOp { op, arg }, span)
     }
     pub fn bin_op(op: BinaryOp, left: Self, right: Self) -> Self {
+        let span = left.span.to(right.span);
         let left = Box::new(left);
         let right = Box::new(right);
-        Self::BinOp { op, left, right }
+        Self::new(ExprKind::BinOp { op, left, right }, span)
+    }
+    pub fn point(x: Self, y: Self) -> Self {
+        let span = x.span.to(y.span);
+        Self::new(ExprKind::Point(Box::new(x), Box::new(y)), span)
+    }
+
+    /// Rebuilds the expression with `f` applied to each of its direct children.
+    pub fn try_map_children<E>(
+        &self,
+        mut f: impl FnMut(&Expr) -> Result<Expr, E>,
+    ) -> Result<Expr, E> {
+        let mut map = |expr: &Expr| f(expr).map(Box::new);
+        let kind = match &self.kind {
+            ExprKind::Lit(_)
+            | ExprKind::Rational(_)
+            | ExprKind::Quantity(..)
+            | ExprKind::Variable(_) => self.kind.clone(),
+            ExprKind::Call { func, args } => {
+                let mut mapped = Vec::with_capacity(args.len());
+                for arg in args.iter() {
+                    mapped.push(*map(arg)?);
+                }
+                ExprKind::Call {
+                    func: func.clone(),
+                    args: ArgList::from_vec(mapped),
+                }
+            }
+            ExprKind::UnOp { op, arg } => ExprKind::UnOp {
+                op: *op,
+                arg: map(arg)?,
+            },
+            ExprKind::BinOp { op, left, right } => ExprKind::BinOp {
+                op: *op,
+                left: map(left)?,
+                right: map(right)?,
+            },
+            ExprKind::Point(x, y) => ExprKind::Point(map(x)?, map(y)?),
+            ExprKind::List(elements) => {
+                let mut mapped = Vec::with_capacity(elements.len());
+                for element in elements {
+                    mapped.push(*map(element)?);
+                }
+                ExprKind::List(mapped)
+            }
+            ExprKind::Range {
+                first,
+                second,
+                last,
+            } => ExprKind::Range {
+                first: map(first)?,
+                second: match second {
+                    Some(second) => Some(map(second)?),
+                    None => None,
+                },
+                last: map(last)?,
+            },
+            ExprKind::Comprehension { body, var, list } => ExprKind::Comprehension {
+                body: map(body)?,
+                var: var.clone(),
+                list: map(list)?,
+            },
+            ExprKind::Access { arg, field } => ExprKind::Access {
+                arg: map(arg)?,
+                field: *field,
+            },
+            ExprKind::Index { list, index } => ExprKind::Index {
+                list: map(list)?,
+                index: map(index)?,
+            },
+            ExprKind::Derivative { arg, var } => ExprKind::Derivative {
+                arg: map(arg)?,
+                var: var.clone(),
+            },
+            ExprKind::Convert { arg, unit } => ExprKind::Convert {
+                arg: map(arg)?,
+                unit: unit.clone(),
+            },
+            ExprKind::Lambda { args, body } => ExprKind::Lambda {
+                args: args.clone(),
+                body: map(body)?,
+            },
+            ExprKind::Let { bindings, body } => {
+                let mut mapped = Vec::with_capacity(bindings.len());
+                for (name, value) in bindings.iter() {
+                    mapped.push((name.clone(), *map(value)?));
+                }
+                ExprKind::Let {
+                    bindings: ArgList::from_vec(mapped),
+                    body: map(body)?,
+                }
+            }
+        };
+        Ok(Self::new(kind, self.span))
+    }
+
+    /// Rebuilds the expression with `f` applied to each of its direct children.
+    pub fn map_children(&self, mut f: impl FnMut(&Expr) -> Expr) -> Expr {
+        let Ok(expr) = self.try_map_children(|expr| Ok::<_, std::convert::Infallible>(f(expr)));
+        expr
+    }
+
+    /// The value of a literal, where exact ones are read as the floats they were written as.
+    pub fn as_lit(&self) -> Option<f64> {
+        match &self.kind {
+            ExprKind::Lit(x) => Some(*x),
+            ExprKind::Rational(x) => Some(x.to_f64()),
+            _ => None,
+        }
+    }
+
+    /// The names of the variables in the expression, in the order they first appear.
+    pub fn variables(&self) -> Vec<Ident> {
+        let names = RefCell::new(Vec::new());
+        self.any(&|expr| {
+            if let ExprKind::Variable(name) = &expr.kind
+                && !names.borrow().contains(name)
+            {
+                names.borrow_mut().push(name.clone());
+            }
+            false
+        });
+        names.into_inner()
+    }
+
+    /// Whether `f` holds for this expression or any expression inside it.
+    pub fn any(&self, f: &dyn Fn(&Expr) -> bool) -> bool {
+        f(self)
+            || match &self.kind {
+                ExprKind::Lit(_)
+                | ExprKind::Rational(_)
+                | ExprKind::Quantity(..)
+                | ExprKind::Variable(_) => false,
+                ExprKind::Call { args, .. } => args.iter().any(|arg| arg.any(f)),
+                ExprKind::UnOp { arg, .. }
+                | ExprKind::Access { arg, .. }
+                | ExprKind::Derivative { arg, .. }
+                | ExprKind::Convert { arg, .. }
+                | ExprKind::Lambda { body: arg, .. } => arg.any(f),
+                ExprKind::BinOp { left, right, .. }
+                | ExprKind::Point(left, right)
+                | ExprKind::Comprehension {
+                    body: left,
+                    list: right,
+                    ..
+                }
+                | ExprKind::Index {
+                    list: left,
+                    index: right,
+                } => left.any(f) || right.any(f),
+                ExprKind::Range {
+                    first,
+                    second,
+                    last,
+                } => {
+                    first.any(f)
+                        || second.as_ref().is_some_and(|second| second.any(f))
+                        || last.any(f)
+                }
+                ExprKind::List(elements) => elements.iter().any(|element| element.any(f)),
+                ExprKind::Let { bindings, body } => {
+                    bindings.iter().any(|(_, value)| value.any(f)) || body.any(f)
+                }
+            }
+    }
+
+    /// How tightly this expression holds together when displayed next to an operator,
+    /// where atoms never need parentheses.
+    fn binding_power(&self) -> u8 {
+        match &self.kind {
+            ExprKind::Lit(x) if *x < 0.0 => UNARY_BINDING_POWER,
+            ExprKind::Rational(x) if x.to_f64() < 0.0 => UNARY_BINDING_POWER,
+            ExprKind::Lit(..)
+            | ExprKind::Rational(..)
+            | ExprKind::Variable(..)
+            | ExprKind::Call { .. }
+            | ExprKind::Point(..)
+            | ExprKind::List(..)
+            | ExprKind::Range { .. }
+            | ExprKind::Comprehension { .. }
+            | ExprKind::Access { .. }
+            | ExprKind::Index { .. }
+            | ExprKind::UnOp {
+                op: UnaryOp::Degrees,
+                ..
+            } => u8::MAX,
+            ExprKind::UnOp { .. } => UNARY_BINDING_POWER,
+            ExprKind::BinOp { op, .. } => op.binding_power(),
+            ExprKind::Quantity(..) => BinaryOp::DotProduct.binding_power(),
+            ExprKind::Derivative { .. } => BinaryOp::Add.binding_power(),
+            ExprKind::Convert { .. } | ExprKind::Lambda { .. } | ExprKind::Let { .. } => 0,
+        }
+    }
+
+    /// Displays the expression, in parentheses if it binds looser than `min_binding_power`.
+    fn fmt_operand(&self, f: &mut fmt::Formatter, min_binding_power: u8) -> fmt::Result {
+        if self.binding_power() < min_binding_power {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::X => "x",
+            Self::Y => "y",
+        })
     }
 }
 
 impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl fmt::Display for ExprKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Lit(x) => write!(f, "{x}"),
+            Self::Rational(x) => write!(f, "{}", x.to_f64()),
+            Self::Quantity(x, unit) => write!(f, "{x} {unit}"),
             Self::Variable(s) => write!(f, "{s}"),
             Self::Call { func, args } => write!(f, "{func}({args})"),
-            Self::UnOp { op, arg } => match op {
-                UnaryOp::Negate => write!(f, "-{arg}"),
-                UnaryOp::Plus => write!(f, "+{arg}"),
+            Self::Point(x, y) => write!(f, "({x}, {y})"),
+            Self::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Range {
+                first,
+                second,
+                last,
+            } => match second {
+                Some(second) => write!(f, "[{first}, {second}...{last}]"),
+                None => write!(f, "[{first}...{last}]"),
             },
+            Self::Comprehension { body, var, list } => write!(f, "[{body} for {var} in {list}]"),
+            Self::Access { arg, field } => {
+                arg.fmt_operand(f, u8::MAX)?;
+                write!(f, ".{field}")
+            }
+            Self::Index { list, index } => {
+                list.fmt_operand(f, u8::MAX)?;
+                write!(f, "[{index}]")
+            }
+            Self::UnOp {
+                op: UnaryOp::Degrees,
+                arg,
+            } => {
+                arg.fmt_operand(f, u8::MAX)?;
+                f.write_str("°")
+            }
+            Self::UnOp { op, arg } => {
+                f.write_str(match op {
+                    UnaryOp::Negate => "-",
+                    UnaryOp::Plus => "+",
+                    UnaryOp::Degrees => unreachable!(),
+                })?;
+                arg.fmt_operand(f, UNARY_BINDING_POWER)
+            }
+            Self::BinOp {
+                op: BinaryOp::Power,
+                left,
+                right,
+            } => {
+                left.fmt_operand(f, u8::MAX)?;
+                write!(f, "^")?;
+                right.fmt_operand(f, UNARY_BINDING_POWER)
+            }
             Self::BinOp { op, left, right } => {
-                // if *op == Op::Multiply {
-                //     return write!(f, "{left} {right}");
-                // }
-                // let left_bp = match left.as_ref() {
-                //     Self::BinOp { op, .. } => op.binding_power(),
-                //     _ => u8::MAX,
-                // };
-                // let right_bp = match right.as_ref() {
-                //     Self::BinOp { op, .. } => op.binding_power(),
-                //     _ => u8::MAX,
-                // };
-                // let bp = op.binding_power();
-                let op = match op {
+                // operators associate to the left
+                left.fmt_operand(f, op.binding_power())?;
+                let symbol = match op {
                     BinaryOp::Add => '+',
                     BinaryOp::Subtract => '-',
-                    // Op::Multiply => unreachable!(),
                     BinaryOp::DotProduct => '*',
                     BinaryOp::Divide => '/',
+                    BinaryOp::Power => unreachable!(),
                 };
-                // match (left_bp > bp, right_bp > bp) {
-                match (
-                    matches!(
-                        left.as_ref(),
-                        Self::Lit(..) | Self::Variable(..) | Self::Call { .. }
-                    ),
-                    matches!(
-                        right.as_ref(),
-                        Self::Lit(..) | Self::Variable(..) | Self::Call { .. }
-                    ),
-                ) {
-                    (true, true) => write!(f, "{left} {op} {right}"),
-                    (true, false) => write!(f, "{left} {op} ({right})"),
-                    (false, true) => write!(f, "({left}) {op} {right}"),
-                    (false, false) => write!(f, "({left}) {op} ({right})"),
+                write!(f, " {symbol} ")?;
+                right.fmt_operand(f, op.binding_power() + 1)
+            }
+            Self::Derivative { arg, var } => {
+                write!(f, "d/d{var} ")?;
+                arg.fmt_operand(f, BinaryOp::Add.binding_power() + 1)
+            }
+            Self::Convert { arg, unit } => {
+                arg.fmt_operand(f, 1)?;
+                write!(f, " to {unit}")
+            }
+            Self::Lambda { args, body } if args.len() == 1 => write!(f, "{args} -> {body}"),
+            Self::Lambda { args, body } => write!(f, "({args}) -> {body}"),
+            Self::Let { bindings, body } => {
+                write!(f, "let ")?;
+                for (i, (name, value)) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name} = {value}")?;
                 }
+                write!(f, " in {body}")
             }
         }
     }
@@ -339,6 +1346,11 @@ impl<T> ArgList<T> {
     pub fn push(&mut self, value: T) {
         self.0.push(value)
     }
+    /// Wraps a vector that must not be empty.
+    pub fn from_vec(values: Vec<T>) -> Self {
+        assert!(!values.is_empty(), "argument lists are never empty");
+        Self(values)
+    }
 }
 
 impl<T> std::ops::Deref for ArgList<T> {
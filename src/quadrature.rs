@@ -0,0 +1,217 @@
+use anyhow::Result;
+
+/// Adaptive integrator using the 7-point Gauss and 15-point Kronrod rules.
+///
+/// The interval with the largest error estimate is bisected until the total
+/// error is within tolerance. Infinite bounds are mapped onto a finite interval,
+/// and as the rules never evaluate the integrand at the ends of an interval,
+/// integrable singularities at the bounds are handled by repeated bisection.
+#[derive(Debug, Clone, Copy)]
+pub struct Quadrature {
+    pub relative_tolerance: f64,
+    pub absolute_tolerance: f64,
+    /// Integration stops with the current estimate once there are this many intervals.
+    pub max_intervals: usize,
+}
+
+impl Default for Quadrature {
+    fn default() -> Self {
+        Self {
+            relative_tolerance: 1e-12,
+            absolute_tolerance: 1e-14,
+            max_intervals: 500,
+        }
+    }
+}
+
+/// The value of an integral and an estimate of its absolute error.
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    pub value: f64,
+    pub error: f64,
+    /// Whether the error estimate is within the tolerance.
+    pub converged: bool,
+}
+
+/// Nodes of the Kronrod rule on `[-1, 1]`, from the end to the middle. The odd
+/// ones are also the nodes of the Gauss rule.
+const NODES: [f64; 8] = [
+    0.9914553711208126,
+    0.9491079123427585,
+    0.8648644233597691,
+    0.7415311855993945,
+    0.5860872354676911,
+    0.4058451513773972,
+    0.20778495500789848,
+    0.0,
+];
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022935322010529224,
+    0.06309209262997856,
+    0.10479001032225019,
+    0.14065325971552592,
+    0.1690047266392679,
+    0.19035057806478542,
+    0.20443294007529889,
+    0.20948214108472782,
+];
+/// Weights of the Gauss rule for the odd nodes.
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.1294849661688697,
+    0.27970539148927664,
+    0.3818300505051189,
+    0.4179591836734694,
+];
+
+struct Interval {
+    start: f64,
+    end: f64,
+    value: f64,
+    error: f64,
+}
+
+impl Quadrature {
+    /// Integrates `f` from `a` to `b`, either of which may be infinite.
+    pub fn integrate(
+        &self,
+        mut f: impl FnMut(f64) -> Result<f64>,
+        a: f64,
+        b: f64,
+    ) -> Result<Estimate> {
+        if a == b {
+            return Ok(Estimate {
+                value: 0.0,
+                error: 0.0,
+                converged: true,
+            });
+        }
+        if a > b {
+            let estimate = self.integrate(f, b, a)?;
+            return Ok(Estimate {
+                value: -estimate.value,
+                ..estimate
+            });
+        }
+        match (a.is_finite(), b.is_finite()) {
+            (true, true) => self.adaptive(f, a, b),
+            // t = a + s / (1 - s)
+            (true, false) => {
+                self.adaptive(|s| Ok(f(a + s / (1.0 - s))? / (1.0 - s).powi(2)), 0.0, 1.0)
+            }
+            // t = b - (1 - s) / s
+            (false, true) => self.adaptive(|s| Ok(f(b - (1.0 - s) / s)? / s.powi(2)), 0.0, 1.0),
+            // t = s / (1 - s^2)
+            (false, false) => self.adaptive(
+                |s| {
+                    let t = s / (1.0 - s * s);
+                    Ok(f(t)? * (1.0 + s * s) / (1.0 - s * s).powi(2))
+                },
+                -1.0,
+                1.0,
+            ),
+        }
+    }
+
+    fn adaptive(&self, mut f: impl FnMut(f64) -> Result<f64>, a: f64, b: f64) -> Result<Estimate> {
+        let mut intervals = vec![Self::gauss_kronrod(&mut f, a, b)?];
+        loop {
+            let value: f64 = intervals.iter().map(|interval| interval.value).sum();
+            let error: f64 = intervals.iter().map(|interval| interval.error).sum();
+            let tolerance = self
+                .absolute_tolerance
+                .max(self.relative_tolerance * value.abs());
+            let estimate = |converged| Estimate {
+                value,
+                error,
+                converged,
+            };
+            if value.is_nan() || error <= tolerance {
+                return Ok(estimate(!value.is_nan()));
+            }
+            if intervals.len() >= self.max_intervals {
+                return Ok(estimate(false));
+            }
+
+            let (worst, _) = intervals
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.error.total_cmp(&b.error))
+                .unwrap();
+            let Interval { start, end, .. } = intervals.swap_remove(worst);
+            let middle = 0.5 * (start + end);
+            if middle <= start || middle >= end {
+                // the interval cannot be split any further in floating point
+                return Ok(estimate(false));
+            }
+            intervals.push(Self::gauss_kronrod(&mut f, start, middle)?);
+            intervals.push(Self::gauss_kronrod(&mut f, middle, end)?);
+        }
+    }
+
+    /// Applies both rules to `[a, b]`, using their difference as the error estimate.
+    ///
+    /// An infinite value at a node is left out and gives an infinite error, so that
+    /// the interval is split away from the singularity.
+    fn gauss_kronrod(f: &mut impl FnMut(f64) -> Result<f64>, a: f64, b: f64) -> Result<Interval> {
+        let (center, half_width) = (0.5 * (a + b), 0.5 * (b - a));
+        let (mut kronrod, mut gauss) = (0.0, 0.0);
+        let mut singular = false;
+        for (i, node) in NODES.iter().enumerate() {
+            let points: &[f64] = match i {
+                7 => &[center],
+                _ => &[center - half_width * node, center + half_width * node],
+            };
+            for &t in points {
+                let y = f(t)?;
+                if y.is_infinite() {
+                    singular = true;
+                    continue;
+                }
+                kronrod += KRONROD_WEIGHTS[i] * y;
+                if i % 2 == 1 {
+                    gauss += GAUSS_WEIGHTS[i / 2] * y;
+                }
+            }
+        }
+        let (value, error) = (kronrod * half_width, ((kronrod - gauss) * half_width).abs());
+        Ok(Interval {
+            start: a,
+            end: b,
+            value,
+            error: if singular { f64::INFINITY } else { error },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        let error = ((actual - expected) / expected).abs();
+        assert!(
+            error < 1e-12,
+            "{actual} differs from {expected} by {error:.1e}"
+        );
+    }
+
+    #[test]
+    fn gaussian_over_the_real_line() {
+        let estimate = Quadrature::default()
+            .integrate(|t| Ok((-t * t).exp()), f64::NEG_INFINITY, f64::INFINITY)
+            .unwrap();
+        assert!(estimate.converged);
+        assert_close(estimate.value, PI.sqrt());
+    }
+
+    #[test]
+    fn finite_intervals() {
+        let quadrature = Quadrature::default();
+        let square = quadrature.integrate(|x| Ok(x * x), 0.0, 1.0).unwrap();
+        assert_close(square.value, 1.0 / 3.0);
+        let reversed = quadrature.integrate(|x| Ok(x.sin()), PI, 0.0).unwrap();
+        assert_close(reversed.value, -2.0);
+    }
+}
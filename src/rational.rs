@@ -0,0 +1,108 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::parse::BinaryOp;
+
+/// Powers with more bits than this are computed with floats instead.
+const MAX_POWER_BITS: u64 = 1 << 16;
+
+/// An exact fraction, which literals evaluate to in exact mode.
+///
+/// Displays as a reduced fraction such as `-7/2`, or with `{:#}` as a mixed
+/// number such as `-3 1/2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rational(BigRational);
+
+impl Rational {
+    /// The reduced fraction `numerator / denominator`, whose denominator must be nonzero.
+    pub fn new(numerator: i64, denominator: i64) -> Self {
+        Self(BigRational::new(numerator.into(), denominator.into()))
+    }
+
+    pub fn from_integer(n: i64) -> Self {
+        Self(BigRational::from_integer(BigInt::from(n)))
+    }
+
+    /// The fraction a number literal was written as, such as `1/10` for `0.1`.
+    pub fn from_literal(x: f64) -> Self {
+        // `f64`'s `Display` gives the shortest decimal that reads back as `x`
+        let digits = x.to_string();
+        let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+        let numerator: BigInt = format!("{whole}{fraction}").parse().unwrap();
+        let denominator = num_traits::pow(BigInt::from(10), fraction.len());
+        Self(BigRational::new(numerator, denominator))
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.0.to_f64().unwrap_or(f64::NAN)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.0.is_integer()
+    }
+
+    pub fn abs(&self) -> Self {
+        Self(self.0.abs())
+    }
+
+    /// The exact square root, if both the numerator and denominator are perfect squares.
+    pub fn sqrt(&self) -> Option<Self> {
+        if self.0.is_negative() {
+            return None;
+        }
+        let (numerator, denominator) = (self.0.numer().sqrt(), self.0.denom().sqrt());
+        let root = BigRational::new(numerator, denominator);
+        (&root * &root == self.0).then_some(Self(root))
+    }
+
+    /// Applies a binary operator exactly, or returns `None` if the result is not a
+    /// rational number (or too large to be worth computing exactly).
+    pub fn binary_op(op: BinaryOp, left: &Self, right: &Self) -> Option<Self> {
+        let (a, b) = (&left.0, &right.0);
+        Some(Self(match op {
+            BinaryOp::Add => a + b,
+            BinaryOp::Subtract => a - b,
+            BinaryOp::DotProduct => a * b,
+            BinaryOp::Divide if b.is_zero() => return None,
+            BinaryOp::Divide => a / b,
+            BinaryOp::Power => return left.pow(right),
+        }))
+    }
+
+    fn pow(&self, exponent: &Self) -> Option<Self> {
+        if !exponent.is_integer() || (self.0.is_zero() && exponent.0.is_negative()) {
+            return None;
+        }
+        let n = exponent.0.to_integer().to_i32()?;
+        let bits = self.0.numer().bits() + self.0.denom().bits();
+        if bits.saturating_mul(n.unsigned_abs() as u64) > MAX_POWER_BITS {
+            return None;
+        }
+        Some(Self(self.0.pow(n)))
+    }
+}
+
+impl std::ops::Neg for Rational {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (numerator, denominator) = (self.0.numer(), self.0.denom());
+        if self.is_integer() {
+            return write!(f, "{numerator}");
+        }
+        if f.alternate() && numerator.abs() > *denominator {
+            let whole = self.0.trunc().to_integer();
+            let remainder = (numerator % denominator).abs();
+            return write!(f, "{whole} {remainder}/{denominator}");
+        }
+        write!(f, "{numerator}/{denominator}")
+    }
+}
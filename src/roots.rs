@@ -0,0 +1,499 @@
+use anyhow::Result;
+
+use crate::matrix::{Matrix, norm};
+
+/// Brent's methods for finding a zero of a real function in a bracketing interval
+/// and a minimum between two points.
+///
+/// Both combine interpolation, which converges quickly for smooth functions,
+/// with bisection or golden section steps, which always make progress.
+#[derive(Debug, Clone, Copy)]
+pub struct Brent {
+    /// Absolute tolerance on the result, on top of the relative precision of floats.
+    pub tolerance: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for Brent {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-15,
+            max_iterations: 100,
+        }
+    }
+}
+
+/// The fraction of an interval that golden section steps move into its larger part.
+const GOLDEN_SECTION: f64 = 0.381_966_011_250_105_1;
+
+/// How many times the interval around a starting point is doubled when looking for
+/// a sign change.
+const MAX_WIDENINGS: usize = 50;
+
+impl Brent {
+    /// Finds a zero of `f` between `a` and `b`, or `None` if `f` does not change sign
+    /// between them or becomes undefined.
+    pub fn root(
+        &self,
+        mut f: impl FnMut(f64) -> Result<f64>,
+        a: f64,
+        b: f64,
+    ) -> Result<Option<f64>> {
+        let (mut a, mut b) = (a, b);
+        let (mut fa, mut fb) = (f(a)?, f(b)?);
+        if fa.is_nan() || fb.is_nan() || fa * fb > 0.0 {
+            return Ok(None);
+        }
+        // `b` is the best estimate so far, and the zero stays between `b` and `c`
+        let (mut c, mut fc) = (b, fb);
+        let (mut step, mut previous_step) = (b - a, b - a);
+        for _ in 0..self.max_iterations {
+            if fb.signum() == fc.signum() {
+                (c, fc) = (a, fa);
+                (step, previous_step) = (b - a, b - a);
+            }
+            if fc.abs() < fb.abs() {
+                (a, fa) = (b, fb);
+                (b, fb) = (c, fc);
+                (c, fc) = (a, fa);
+            }
+            let tolerance = 2.0 * f64::EPSILON * b.abs() + 0.5 * self.tolerance;
+            let middle = 0.5 * (c - b);
+            if middle.abs() <= tolerance || fb == 0.0 {
+                return Ok(Some(b));
+            }
+            (step, previous_step) = match previous_step.abs() >= tolerance && fa.abs() > fb.abs() {
+                true => {
+                    // interpolate linearly through two points, or inversely quadratically
+                    // through three
+                    let s = fb / fa;
+                    let (p, q) = match a == c {
+                        true => (2.0 * middle * s, 1.0 - s),
+                        false => {
+                            let (q, r) = (fa / fc, fb / fc);
+                            (
+                                s * (2.0 * middle * q * (q - r) - (b - a) * (r - 1.0)),
+                                (q - 1.0) * (r - 1.0) * (s - 1.0),
+                            )
+                        }
+                    };
+                    let (p, q) = if p > 0.0 { (p, -q) } else { (-p, q) };
+                    // only accept the interpolation if it stays well within the bracket
+                    // and shrinks faster than bisection would
+                    let limit =
+                        (3.0 * middle * q - (tolerance * q).abs()).min((previous_step * q).abs());
+                    match 2.0 * p < limit {
+                        true => (p / q, step),
+                        false => (middle, middle),
+                    }
+                }
+                false => (middle, middle),
+            };
+            (a, fa) = (b, fb);
+            b += match step.abs() > tolerance {
+                true => step,
+                false => tolerance.copysign(middle),
+            };
+            fb = f(b)?;
+            if fb.is_nan() {
+                return Ok(None);
+            }
+        }
+        Ok(Some(b))
+    }
+
+    /// Finds a zero of `f` near `x`, by widening an interval around `x` until `f`
+    /// changes sign between `x` and one of its ends.
+    pub fn root_near(&self, mut f: impl FnMut(f64) -> Result<f64>, x: f64) -> Result<Option<f64>> {
+        let fx = f(x)?;
+        if fx.is_nan() {
+            return Ok(None);
+        }
+        let mut width = 0.01 * x.abs().max(1.0);
+        for _ in 0..MAX_WIDENINGS {
+            for end in [x - width, x + width] {
+                if f(end)? * fx <= 0.0 {
+                    return self.root(&mut f, x, end);
+                }
+            }
+            width *= 2.0;
+        }
+        Ok(None)
+    }
+
+    /// Finds a local minimum of `f` between `a` and `b`, which is one of the bounds
+    /// if `f` is monotonic.
+    pub fn minimum(&self, mut f: impl FnMut(f64) -> Result<f64>, a: f64, b: f64) -> Result<f64> {
+        let (mut a, mut b) = (a.min(b), a.max(b));
+        // `x` is the best point so far, `w` the second best and `v` the one before `w`
+        let mut x = a + GOLDEN_SECTION * (b - a);
+        let mut fx = f(x)?;
+        let (mut w, mut fw, mut v, mut fv) = (x, fx, x, fx);
+        let (mut step, mut previous_step) = (0.0f64, 0.0f64);
+        for _ in 0..self.max_iterations {
+            let middle = 0.5 * (a + b);
+            let tolerance = f64::EPSILON.sqrt() * x.abs() + self.tolerance;
+            if (x - middle).abs() <= 2.0 * tolerance - 0.5 * (b - a) {
+                break;
+            }
+            let mut parabolic = false;
+            if previous_step.abs() > tolerance {
+                // the minimum of the parabola through `x`, `w` and `v` is at `x + p / q`
+                let r = (x - w) * (fx - fv);
+                let mut q = (x - v) * (fx - fw);
+                let mut p = (x - v) * q - (x - w) * r;
+                q = 2.0 * (q - r);
+                if q > 0.0 {
+                    p = -p;
+                } else {
+                    q = -q;
+                }
+                if p.abs() < (0.5 * q * previous_step).abs() && p > q * (a - x) && p < q * (b - x) {
+                    previous_step = step;
+                    step = p / q;
+                    // don't evaluate `f` too close to the bounds
+                    if x + step - a < 2.0 * tolerance || b - (x + step) < 2.0 * tolerance {
+                        step = tolerance.copysign(middle - x);
+                    }
+                    parabolic = true;
+                }
+            }
+            if !parabolic {
+                previous_step = if x >= middle { a - x } else { b - x };
+                step = GOLDEN_SECTION * previous_step;
+            }
+            let u = match step.abs() >= tolerance {
+                true => x + step,
+                false => x + tolerance.copysign(step),
+            };
+            let fu = f(u)?;
+            if fu <= fx {
+                if u >= x {
+                    a = x;
+                } else {
+                    b = x;
+                }
+                (v, fv) = (w, fw);
+                (w, fw) = (x, fx);
+                (x, fx) = (u, fu);
+            } else {
+                if u < x {
+                    a = u;
+                } else {
+                    b = u;
+                }
+                if fu <= fw || w == x {
+                    (v, fv) = (w, fw);
+                    (w, fw) = (u, fu);
+                } else if fu <= fv || v == x || v == w {
+                    (v, fv) = (u, fu);
+                }
+            }
+        }
+        Ok(x)
+    }
+
+    /// The zeros of `f`, found from the samples `(x, f(x))` that are zero and the
+    /// neighbouring samples between which `f` changes sign.
+    ///
+    /// Sign changes where `f` grows rather than shrinks towards the zero, like at the
+    /// poles of `tan`, are left out, as are runs of samples that are all zero.
+    pub fn roots(
+        &self,
+        mut f: impl FnMut(f64) -> Result<f64>,
+        samples: &[(f64, f64)],
+    ) -> Result<Vec<f64>> {
+        let mut roots = Vec::new();
+        for (i, &(x, y)) in samples.iter().enumerate() {
+            let is_zero = |j: Option<usize>| {
+                j.and_then(|j| samples.get(j))
+                    .is_some_and(|&(_, y)| y == 0.0)
+            };
+            if y == 0.0 {
+                if !is_zero(i.checked_sub(1)) && !is_zero(Some(i + 1)) {
+                    roots.push(x);
+                }
+                continue;
+            }
+            let Some(&(next_x, next_y)) = samples.get(i + 1) else {
+                break;
+            };
+            if y.is_nan() || next_y.is_nan() || y * next_y >= 0.0 {
+                continue;
+            }
+            if let Some(root) = self.root(&mut f, x, next_x)?
+                && f(root)?.abs() <= y.abs().min(next_y.abs())
+            {
+                roots.push(root);
+            }
+        }
+        Ok(roots)
+    }
+
+    /// The local minima `(x, f(x))` of `f`, each refined from a sample `(x, f(x))`
+    /// that is smaller than its neighbours.
+    ///
+    /// Minima that are much deeper than the samples suggest, like those found next to
+    /// the poles of `tan`, are left out.
+    pub fn minima(
+        &self,
+        mut f: impl FnMut(f64) -> Result<f64>,
+        samples: &[(f64, f64)],
+    ) -> Result<Vec<(f64, f64)>> {
+        let mut minima = Vec::new();
+        for window in samples.windows(3) {
+            let [(before, y0), (_, y1), (after, y2)] = *window else {
+                unreachable!()
+            };
+            if !(y1 < y0 && y1 <= y2) {
+                continue;
+            }
+            let x = self.minimum(&mut f, before, after)?;
+            let y = f(x)?;
+            // a parabola through the samples dips below the middle one by less than this
+            if y1 - y <= (y0 - y1) + (y2 - y1) {
+                minima.push((x, y));
+            }
+        }
+        Ok(minima)
+    }
+
+    /// Where `f` is smallest, refined from the smallest of the samples `(x, f(x))`,
+    /// or `None` if all of them are undefined.
+    pub fn argmin(
+        &self,
+        mut f: impl FnMut(f64) -> Result<f64>,
+        samples: &[(f64, f64)],
+    ) -> Result<Option<f64>> {
+        let Some((i, _)) = samples
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, y))| !y.is_nan())
+            .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+        else {
+            return Ok(None);
+        };
+        let (before, after) = (
+            samples[i.saturating_sub(1)].0,
+            samples[(i + 1).min(samples.len() - 1)].0,
+        );
+        let x = self.minimum(&mut f, before, after)?;
+        // refining can only move away from the sample if it finds a smaller value
+        Ok(Some(match f(x)? <= samples[i].1 {
+            true => x,
+            false => samples[i].0,
+        }))
+    }
+}
+
+/// Samples `f` at `n` evenly spaced points from `a` to `b`, as `(x, f(x))`.
+pub fn sample(
+    mut f: impl FnMut(f64) -> Result<f64>,
+    a: f64,
+    b: f64,
+    n: usize,
+) -> Result<Vec<(f64, f64)>> {
+    (0..n)
+        .map(|i| {
+            let t = i as f64 / (n - 1) as f64;
+            let x = a * (1.0 - t) + b * t;
+            Ok((x, f(x)?))
+        })
+        .collect()
+}
+
+/// Newton's method for `N` equations `f(x) = 0` in `N` unknowns, damped by halving
+/// steps that don't reduce the residual.
+///
+/// The Jacobian is taken from `jacobian`, which gives the exact one when it comes from
+/// evaluating the equations over dual numbers. Where that fails, it is estimated by
+/// finite differences instead and then kept up to date with Broyden's updates, which
+/// saves evaluating `f`, until a step fails with it.
+#[derive(Debug, Clone, Copy)]
+pub struct Newton {
+    /// Iteration stops once a step is this small relative to the solution.
+    pub tolerance: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for Newton {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-12,
+            max_iterations: 100,
+        }
+    }
+}
+
+/// Where Newton's method stopped, and the norm of `f` there.
+#[derive(Debug, Clone, Copy)]
+pub struct Solution<const N: usize> {
+    pub x: [f64; N],
+    pub residual: f64,
+    pub converged: bool,
+}
+
+/// The smallest fraction of a Newton step tried before giving up on it.
+const MIN_DAMPING: f64 = 1.0 / 1024.0;
+
+impl Newton {
+    /// Iterates from `start` until a step is small enough, or gives up when no step
+    /// reduces the residual. The rows of `jacobian(x)` are the gradients of the
+    /// components of `f` at `x`, and once it fails it is no longer asked for.
+    pub fn solve<const N: usize>(
+        &self,
+        mut f: impl FnMut([f64; N]) -> Result<[f64; N]>,
+        mut jacobian: impl FnMut([f64; N]) -> Result<[[f64; N]; N]>,
+        start: [f64; N],
+    ) -> Result<Solution<N>> {
+        let mut x = start;
+        let mut fx = f(x)?;
+        let mut residual = norm(&fx);
+        let mut converged = residual == 0.0;
+        let mut exact = true;
+        let mut estimate = None;
+        for _ in 0..self.max_iterations {
+            if converged || !residual.is_finite() {
+                break;
+            }
+            let fresh = estimate.is_none();
+            let j = match estimate {
+                Some(j) => j,
+                None => match exact.then(|| jacobian(x)) {
+                    Some(Ok(j)) => j,
+                    _ => {
+                        exact = false;
+                        finite_differences(&mut f, x, &fx)?
+                    }
+                },
+            };
+            let matrix = Matrix::from_rows(&j.map(|row| row.to_vec()))?;
+            let Some(step) = matrix.least_squares(&fx.map(|y| -y)) else {
+                match fresh {
+                    true => break,
+                    false => {
+                        estimate = None;
+                        continue;
+                    }
+                }
+            };
+            // near a solution, rounding can keep the residual from shrinking any further
+            let small = norm(&step) <= self.tolerance * (1.0 + norm(&x));
+            let mut accepted = None;
+            let mut damping = 1.0;
+            while damping >= MIN_DAMPING {
+                let trial: [f64; N] = std::array::from_fn(|i| x[i] + damping * step[i]);
+                let f_trial = f(trial)?;
+                let trial_residual = norm(&f_trial);
+                if trial_residual < residual {
+                    accepted = Some((trial, f_trial, trial_residual));
+                    break;
+                }
+                damping /= 2.0;
+            }
+            let Some((next, f_next, next_residual)) = accepted else {
+                match (small, fresh) {
+                    (true, _) => converged = true,
+                    (false, true) => break,
+                    // the updated Jacobian may have drifted, so retry with a new estimate
+                    (false, false) => estimate = None,
+                }
+                continue;
+            };
+            if !exact {
+                // Broyden's update, the smallest change to the Jacobian that matches the step
+                let dx: [f64; N] = std::array::from_fn(|i| next[i] - x[i]);
+                let df: [f64; N] = std::array::from_fn(|i| f_next[i] - fx[i]);
+                let dx_squared: f64 = dx.iter().map(|d| d * d).sum();
+                let mut j = j;
+                for row in 0..N {
+                    let predicted: f64 = (0..N).map(|i| j[row][i] * dx[i]).sum();
+                    for i in 0..N {
+                        j[row][i] += (df[row] - predicted) * dx[i] / dx_squared;
+                    }
+                }
+                estimate = Some(j);
+            }
+            (x, fx, residual) = (next, f_next, next_residual);
+            converged = small || residual == 0.0;
+        }
+        Ok(Solution {
+            x,
+            residual,
+            converged,
+        })
+    }
+}
+
+/// The Jacobian of `f` at `x` estimated by forward differences, given `fx = f(x)`.
+fn finite_differences<const N: usize>(
+    f: &mut impl FnMut([f64; N]) -> Result<[f64; N]>,
+    x: [f64; N],
+    fx: &[f64; N],
+) -> Result<[[f64; N]; N]> {
+    let mut j = [[0.0; N]; N];
+    for i in 0..N {
+        let h = f64::EPSILON.sqrt() * x[i].abs().max(1.0);
+        let mut shifted = x;
+        shifted[i] += h;
+        let f_shifted = f(shifted)?;
+        for (row, (shifted, y)) in std::iter::zip(&f_shifted, fx).enumerate() {
+            j[row][i] = (shifted - y) / h;
+        }
+    }
+    Ok(j)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_2, SQRT_2};
+
+    use anyhow::bail;
+
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        let error = ((actual - expected) / expected).abs();
+        assert!(
+            error < 1e-12,
+            "{actual} differs from {expected} by {error:.1e}"
+        );
+    }
+
+    /// `x^2 + y^2 = 4` and `x = y`, which meet at `(√2, √2)` from a start of `(1, 1)`.
+    fn circle_and_diagonal([x, y]: [f64; 2]) -> Result<[f64; 2]> {
+        Ok([x * x + y * y - 4.0, x - y])
+    }
+
+    #[test]
+    fn newton_system() {
+        let jacobian = |[x, y]: [f64; 2]| Ok([[2.0 * x, 2.0 * y], [1.0, -1.0]]);
+        let solution = Newton::default()
+            .solve(circle_and_diagonal, jacobian, [1.0, 1.0])
+            .unwrap();
+        assert!(solution.converged);
+        assert_close(solution.x[0], SQRT_2);
+        assert_close(solution.x[1], SQRT_2);
+    }
+
+    #[test]
+    fn newton_system_by_finite_differences() {
+        let jacobian = |_: [f64; 2]| bail!("no exact Jacobian");
+        let solution = Newton::default()
+            .solve(circle_and_diagonal, jacobian, [1.0, 1.0])
+            .unwrap();
+        assert!(solution.converged);
+        assert_close(solution.x[0], SQRT_2);
+        assert_close(solution.x[1], SQRT_2);
+    }
+
+    #[test]
+    fn brent() {
+        let brent = Brent::default();
+        let root = brent.root(|x| Ok(x.cos()), 0.0, 2.0).unwrap();
+        assert_close(root.unwrap(), FRAC_PI_2);
+        assert_eq!(brent.root(|x| Ok(x * x + 1.0), -1.0, 1.0).unwrap(), None);
+        let minimum = brent.minimum(|x| Ok((x - 1.5).powi(2)), 0.0, 4.0).unwrap();
+        assert!((minimum - 1.5).abs() < 1e-7);
+    }
+}
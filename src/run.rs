@@ -1,8 +1,28 @@
-use std::{collections::HashMap, hash::Hash};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::Hash,
+    slice,
+};
 
 use crate::{
-    builtins::BuiltinFunction,
-    parse::{ArgList, BinaryOp, Expr, Ident, TopLevelItem, UnaryOp},
+    builtins::{BuiltinConstant, BuiltinFunction},
+    check::{Type, TypeChecker},
+    derivative::{Definition, derivative, expand_derivatives, substitute},
+    dual::Dual,
+    format::Format,
+    least_squares::LevenbergMarquardt,
+    matrix::Matrix,
+    number::{Number, check_arity},
+    ode::Solver,
+    parse::{ArgList, BinaryOp, Expr, ExprKind, Ident, Setting, SettingArg, TopLevelItem, UnaryOp},
+    quadrature::Quadrature,
+    rational::Rational,
+    roots::{self, Brent, Newton, Solution},
+    simplify::Simplifier,
+    span::bail_at,
+    value::{Function, Value},
 };
 
 use anyhow::{Result, bail};
@@ -11,17 +31,116 @@ use strum::IntoEnumIterator;
 
 #[derive(Debug, Clone)]
 enum Binding {
-    Value(f64),
+    Value(Value),
     Function { args: ArgList<Ident>, body: Expr },
     Builtin(BuiltinFunction),
+    Sequence(Sequence),
+}
+
+/// A sequence like `a(n) := a(n-1) + a(n-2)` with base cases `a(0) := 0` and `a(1) := 1`.
+#[derive(Debug, Clone, Default)]
+struct Sequence {
+    base_cases: BTreeMap<i64, Value>,
+    /// The index and body of the recursive definition, once it is given.
+    definition: Option<(Ident, Expr)>,
+    /// Whether the recursive definition was given in exact mode, which makes the index
+    /// an exact integer.
+    exact: bool,
+    /// The terms computed so far.
+    terms: RefCell<HashMap<i64, Value>>,
+    /// Whether the terms before one are being computed, which the terms they refer to
+    /// don't do again.
+    warming_up: Cell<bool>,
+}
+
+/// One argument functions with a parameter of this name are plotted with domain coloring.
+pub const COMPLEX_PARAMETER: &str = "z";
+
+/// How many points `root`, `argmin` and `argmax` sample their function at to find a bracket.
+const SEARCH_SAMPLES: usize = 200;
+
+/// The most terms a `sum` or `prod` can have.
+const MAX_TERMS: u32 = 1_000_000;
+
+/// The largest bound of a `sum` or `prod`, beyond which floats skip some integers.
+const MAX_BOUND: f64 = 9_007_199_254_740_992.0;
+
+/// How deeply the terms of sequences may refer to terms that are not computed yet.
+const MAX_DEPTH: usize = 100;
+
+/// How deeply functions may call each other, which only recursion through arguments
+/// like `w(w)` with `w := x -> x(x)` can reach.
+const MAX_CALL_DEPTH: usize = 100;
+
+#[derive(Debug, Clone)]
+pub enum DifferentialEquation {
+    /// `y' = f(x, y)`, solved for `y` as a function of `x`.
+    Scalar { name: Ident, body: Expr },
+    /// `x' = f(t, x, y), y' = g(t, x, y)`, solved for a curve parametrized by `t`.
+    System {
+        names: [Ident; 2],
+        bodies: [Expr; 2],
+    },
+}
+
+impl DifferentialEquation {
+    pub const INDEPENDENT_SCALAR: &str = "x";
+    pub const INDEPENDENT_SYSTEM: &str = "t";
 }
 
 #[derive(Debug)]
 pub struct Interpreter {
+    checker: TypeChecker,
     bindings: HashMap<Ident, Binding>,
-    arg_bindings: HashMap<Ident, f64>,
-    pub constants: Vec<(Option<Ident>, f64)>,
+    arg_bindings: HashMap<Ident, Value>,
+    /// Values of top level expressions and assignments, with the format to display them in.
+    pub constants: Vec<(Option<Ident>, Value, Format)>,
     pub single_var_functions: Vec<(Ident, Expr)>,
+    pub complex_functions: Vec<(Ident, Expr)>,
+    pub slope_fields: Vec<([Ident; 2], Expr)>,
+    pub vector_fields: Vec<([Ident; 2], Expr)>,
+    pub differential_equations: Vec<(DifferentialEquation, Vec<(f64, f64)>)>,
+    /// Sequences, which are plotted at their integer indices.
+    pub sequences: Vec<Ident>,
+    /// The data of fits to a single list, drawn as points along with the fitted
+    /// curves among the single variable functions.
+    pub fitted_data: Vec<Vec<(f64, f64)>>,
+    /// Functions defined with derivatives or `simplify`, as they are after rewriting.
+    pub rewritten_functions: Vec<(Ident, ArgList<Ident>, Expr)>,
+    pub settings: Settings,
+    /// Problems that did not stop evaluation, at most one for each expression.
+    warnings: RefCell<Vec<(Expr, String)>>,
+    /// How many terms of sequences are being computed, each needed by the one before.
+    depth: Cell<usize>,
+    /// How many function calls are being evaluated, each inside the one before.
+    call_depth: Cell<usize>,
+}
+
+/// Options set by `#name args` lines in the input, in effect for the items that follow.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Whether the literals of the items that follow are exact fractions rather than floats.
+    pub exact: bool,
+    pub format: Format,
+    /// The unit of angles taken and returned by the trigonometric functions.
+    pub angle_unit: AngleUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleUnit {
+    #[default]
+    Radians,
+    /// Trigonometric functions are rewritten to convert from and to radians.
+    Degrees,
+}
+
+impl fmt::Display for AngleUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Radians => "radians",
+            Self::Degrees => "degrees",
+        })
+    }
 }
 
 impl Interpreter {
@@ -33,11 +152,29 @@ impl Interpreter {
                 Binding::Builtin(builtin),
             );
         }
+        for constant in BuiltinConstant::iter() {
+            bindings.insert(
+                EcoString::from(constant.to_string()),
+                Binding::Value(constant.value()),
+            );
+        }
         Self {
+            checker: TypeChecker::new(),
             bindings,
             arg_bindings: HashMap::new(),
             constants: Vec::new(),
             single_var_functions: Vec::new(),
+            complex_functions: Vec::new(),
+            slope_fields: Vec::new(),
+            vector_fields: Vec::new(),
+            differential_equations: Vec::new(),
+            sequences: Vec::new(),
+            fitted_data: Vec::new(),
+            rewritten_functions: Vec::new(),
+            settings: Settings::default(),
+            warnings: RefCell::new(Vec::new()),
+            depth: Cell::new(0),
+            call_depth: Cell::new(0),
         }
     }
 
@@ -49,96 +186,1316 @@ impl Interpreter {
     }
 
     pub fn add_item(&mut self, item: TopLevelItem) -> Result<()> {
+        if let TopLevelItem::WithSettings { item, settings } = item {
+            let outer = self.settings.clone();
+            let result = settings
+                .iter()
+                .try_for_each(|setting| self.apply_setting(setting))
+                .and_then(|()| self.add_item(*item));
+            self.settings = outer;
+            return result;
+        }
         // eprintln!("running {item}");
+        let rewritten = match &item {
+            TopLevelItem::FunctionDef { body, .. } => body.any(&|expr| match &expr.kind {
+                ExprKind::Derivative { .. } => true,
+                ExprKind::Call { func, .. } => {
+                    func.ends_with('\'') || *func == BuiltinFunction::Simplify.to_string()
+                }
+                _ => false,
+            }),
+            _ => false,
+        };
+        let item = self.rewrite(item)?;
+        self.checker.check_item(&item)?;
         match item {
             TopLevelItem::Expression(expr) => {
                 let value = self.evaluate(&expr, &HashMap::new())?;
-                self.constants.push((None, value));
+                self.constants.push((None, value, self.settings.format));
             }
             TopLevelItem::Assignment { name, body } => {
-                //
-                if self.bindings.contains_key(&name) {
-                    bail!("Cannot define variable '{name}' as this name is already bound");
-                }
                 let value = self.evaluate(&body, &HashMap::new())?;
-                self.constants.push((Some(name.clone()), value));
+                self.constants
+                    .push((Some(name.clone()), value.clone(), self.settings.format));
                 self.bindings.insert(name, Binding::Value(value));
             }
-            TopLevelItem::FunctionDef { name, args, body } => {
-                if self.bindings.contains_key(&name) {
-                    bail!("Cannot define function '{name}' as this name is already bound");
+            TopLevelItem::FunctionDef { name, args, body } if self.checker.is_sequence(&name) => {
+                let definition = Some((args[0].clone(), body));
+                let exact = self.settings.exact;
+                match self.bindings.get_mut(&name) {
+                    Some(Binding::Sequence(sequence)) => {
+                        (sequence.definition, sequence.exact) = (definition, exact)
+                    }
+                    _ => {
+                        self.sequences.push(name.clone());
+                        let sequence = Sequence {
+                            definition,
+                            exact,
+                            ..Default::default()
+                        };
+                        self.bindings.insert(name, Binding::Sequence(sequence));
+                    }
                 }
-                for arg in args.iter() {
-                    if self.bindings.contains_key(arg) {
-                        bail!("Cannot use argument '{arg}' as this name is already bound")
+            }
+            TopLevelItem::FunctionDef { name, args, body } => {
+                // functions are graphed depending on what they return when called with numbers
+                let graphed_type = self
+                    .checker
+                    .call_type(&name, &vec![Type::Number; args.len()]);
+                let numbers = args.iter().map(|arg| (arg.clone(), Type::Number)).collect();
+                // the function is called, drawn and printed with the same body
+                let body = match graphed_type {
+                    Ok(Type::Number | Type::Point) => self.simplify(&body, &numbers),
+                    _ => body,
+                };
+                match (args.as_slice(), graphed_type) {
+                    ([arg], Ok(Type::Number)) if arg == COMPLEX_PARAMETER => {
+                        self.complex_functions.push((arg.clone(), body.clone()))
+                    }
+                    ([arg], Ok(Type::Number)) => {
+                        self.single_var_functions.push((arg.clone(), body.clone()))
                     }
+                    ([x, y], Ok(Type::Point)) => self
+                        .vector_fields
+                        .push(([x.clone(), y.clone()], body.clone())),
+                    ([x, y], Ok(Type::Number)) if name == "slope" => self
+                        .slope_fields
+                        .push(([x.clone(), y.clone()], body.clone())),
+                    _ => {}
                 }
-                if let [arg] = args.as_slice() {
-                    self.single_var_functions.push((arg.clone(), body.clone()));
+                if rewritten {
+                    self.rewritten_functions
+                        .push((name.clone(), args.clone(), body.clone()));
                 }
                 self.bindings.insert(name, Binding::Function { args, body });
             }
+            TopLevelItem::BaseCase { name, index, body } => {
+                let value = self.evaluate(&body, &HashMap::new())?;
+                // terms of any sequence computed without this base case may be wrong
+                for binding in self.bindings.values() {
+                    if let Binding::Sequence(sequence) = binding {
+                        sequence.terms.borrow_mut().clear();
+                    }
+                }
+                match self.bindings.get_mut(&name) {
+                    Some(Binding::Sequence(sequence)) => {
+                        sequence.base_cases.insert(index, value);
+                    }
+                    _ => {
+                        self.sequences.push(name.clone());
+                        let sequence = Sequence {
+                            base_cases: BTreeMap::from([(index, value)]),
+                            ..Default::default()
+                        };
+                        self.bindings.insert(name, Binding::Sequence(sequence));
+                    }
+                }
+            }
+            TopLevelItem::DifferentialEquation {
+                equations,
+                initial_points,
+            } => {
+                let env = Self::differential_equation_env(&equations);
+                let equation = match equations.as_slice() {
+                    [(name, body)] => DifferentialEquation::Scalar {
+                        name: name.clone(),
+                        body: self.simplify(body, &env),
+                    },
+                    [(x, x_body), (y, y_body)] => DifferentialEquation::System {
+                        names: [x.clone(), y.clone()],
+                        bodies: [self.simplify(x_body, &env), self.simplify(y_body, &env)],
+                    },
+                    _ => unreachable!("the number of equations is checked by the type checker"),
+                };
+
+                let mut points = Vec::new();
+                for point in initial_points.iter() {
+                    match self.evaluate(point, &HashMap::new())?.points() {
+                        Some(initial) => points.extend(initial),
+                        None => bail!("Initial condition {point} is not a point"),
+                    }
+                }
+                self.differential_equations.push((equation, points));
+            }
+            TopLevelItem::Solve {
+                name,
+                equations,
+                unknowns,
+                start,
+            } => {
+                let value = self.solve(&equations, &unknowns, start.as_ref())?;
+                self.constants
+                    .push((name.clone(), value.clone(), self.settings.format));
+                if let Some(name) = name {
+                    self.bindings.insert(name, Binding::Value(value));
+                }
+            }
+            TopLevelItem::Fit { data, model } => self.fit(&data, &model)?,
+            TopLevelItem::Settings(settings) => {
+                for setting in settings.iter() {
+                    self.apply_setting(setting)?;
+                }
+            }
+            TopLevelItem::WithSettings { .. } => unreachable!("handled before rewriting"),
         }
         Ok(())
     }
 
-    pub fn evaluate(&self, expr: &Expr, arg_map: &HashMap<Ident, f64>) -> Result<f64> {
-        Ok(match expr {
-            Expr::Lit(x) => *x,
-            Expr::Variable(name) => match arg_map.get(name) {
-                Some(x) => *x,
-                None => match self.bindings.get(name) {
-                    Some(Binding::Value(x)) => *x,
-                    Some(Binding::Function { .. } | Binding::Builtin(_)) => {
-                        bail!("'{name}' is a function and not a variable")
-                    }
-                    None => bail!("Binding '{name}' not defined"),
-                },
+    fn apply_setting(&mut self, setting: &Setting) -> Result<()> {
+        let Settings {
+            exact,
+            format,
+            angle_unit,
+        } = &mut self.settings;
+        match (setting.name.as_str(), setting.args()?.as_slice()) {
+            ("radians", []) => *angle_unit = AngleUnit::Radians,
+            ("degrees", []) => *angle_unit = AngleUnit::Degrees,
+            ("exact", []) => (*exact, format.mixed_numbers) = (true, false),
+            ("exact", [SettingArg::Word("mixed")]) => (*exact, format.mixed_numbers) = (true, true),
+            ("float", []) => *exact = false,
+            ("exact" | "float" | "radians" | "degrees", _) => {
+                bail!("Unexpected arguments to #{}", setting.name)
+            }
+            (name, args) => format.apply_setting(name, args)?,
+        }
+        Ok(())
+    }
+
+    fn definition(&self, name: &Ident) -> Option<Definition<'_>> {
+        Some(match self.bindings.get(name)? {
+            Binding::Value(value) => Definition::Value(value),
+            Binding::Builtin(builtin) => Definition::Builtin(*builtin),
+            Binding::Function { args, body } => Definition::Function { args, body },
+            Binding::Sequence(_) => Definition::Sequence,
+        })
+    }
+
+    /// Runs `f` with a simplifier for expressions whose free variables have the types in `env`.
+    fn with_simplifier<T>(
+        &self,
+        env: &HashMap<Ident, Type>,
+        f: impl FnOnce(&Simplifier) -> T,
+    ) -> T {
+        let definitions = |name: &Ident| self.definition(name);
+        let is_number = |expr: &Expr| matches!(self.checker.check(expr, env), Ok(Type::Number));
+        f(&Simplifier {
+            definitions: &definitions,
+            is_number: &is_number,
+            exact: self.settings.exact,
+        })
+    }
+
+    fn simplify(&self, expr: &Expr, env: &HashMap<Ident, Type>) -> Expr {
+        self.with_simplifier(env, |simplifier| simplifier.simplify(expr))
+    }
+
+    /// The types of the variables in the right hand sides of differential equations.
+    fn differential_equation_env(equations: &[(Ident, Expr)]) -> HashMap<Ident, Type> {
+        let independent = match equations.len() {
+            1 => DifferentialEquation::INDEPENDENT_SCALAR,
+            _ => DifferentialEquation::INDEPENDENT_SYSTEM,
+        };
+        equations
+            .iter()
+            .map(|(name, _)| name.clone())
+            .chain([Ident::from(independent)])
+            .map(|name| (name, Type::Number))
+            .collect()
+    }
+
+    /// Replaces derivatives and `simplify(...)` in an item by the expressions they stand
+    /// for, and its literals by fractions in exact mode.
+    fn rewrite(&self, item: TopLevelItem) -> Result<TopLevelItem> {
+        let definitions = |name: &Ident| self.definition(name);
+        let rewrite = |expr: &Expr, env: &HashMap<Ident, Type>| {
+            let expr = match self.settings.angle_unit {
+                AngleUnit::Radians => expr.clone(),
+                AngleUnit::Degrees => self.convert_to_degrees(expr)?,
+            };
+            // converting first lets `simplify(...)` fold the fractions exactly, and
+            // converting again catches the literals that derivatives introduce
+            let exact = |expr: Expr| match self.settings.exact {
+                true => Self::convert_to_exact(&expr),
+                false => expr,
+            };
+            let expanded = expand_derivatives(&exact(expr), &definitions)?;
+            let expanded = self.with_simplifier(env, |simplifier| simplifier.expand(&expanded));
+            Ok::<_, anyhow::Error>(exact(expanded))
+        };
+        let globals = HashMap::new();
+        Ok(match item {
+            TopLevelItem::Expression(expr) => TopLevelItem::Expression(rewrite(&expr, &globals)?),
+            TopLevelItem::Assignment { name, body } => TopLevelItem::Assignment {
+                name,
+                body: rewrite(&body, &globals)?,
+            },
+            TopLevelItem::FunctionDef { name, args, body } => {
+                // parameters can be of any type until the function is called
+                let env = args
+                    .iter()
+                    .map(|arg| (arg.clone(), Type::Unknown))
+                    .collect();
+                let body = rewrite(&body, &env)?;
+                TopLevelItem::FunctionDef { name, args, body }
+            }
+            TopLevelItem::BaseCase { name, index, body } => TopLevelItem::BaseCase {
+                name,
+                index,
+                body: rewrite(&body, &globals)?,
+            },
+            TopLevelItem::DifferentialEquation {
+                equations,
+                initial_points,
+            } => {
+                let env = Self::differential_equation_env(&equations);
+                let mut rewritten_equations = Vec::new();
+                for (name, body) in equations.iter() {
+                    rewritten_equations.push((name.clone(), rewrite(body, &env)?));
+                }
+                let mut rewritten_points = Vec::new();
+                for point in initial_points.iter() {
+                    rewritten_points.push(rewrite(point, &globals)?);
+                }
+                TopLevelItem::DifferentialEquation {
+                    equations: ArgList::from_vec(rewritten_equations),
+                    initial_points: ArgList::from_vec(rewritten_points),
+                }
+            }
+            TopLevelItem::Solve {
+                name,
+                equations,
+                unknowns,
+                start,
+            } => {
+                let env = unknowns
+                    .iter()
+                    .map(|unknown| (unknown.clone(), Type::Number))
+                    .collect();
+                let mut rewritten_equations = Vec::new();
+                for (left, right) in equations.iter() {
+                    rewritten_equations.push((rewrite(left, &env)?, rewrite(right, &env)?));
+                }
+                TopLevelItem::Solve {
+                    name,
+                    equations: ArgList::from_vec(rewritten_equations),
+                    unknowns,
+                    start: start.map(|start| rewrite(&start, &globals)).transpose()?,
+                }
+            }
+            TopLevelItem::Fit { data, model } => {
+                let env = self
+                    .fit_parameters(&model)
+                    .into_iter()
+                    .map(|parameter| (parameter, Type::Number))
+                    .collect();
+                TopLevelItem::Fit {
+                    data: rewrite(&data, &globals)?,
+                    model: rewrite(&model, &env)?,
+                }
+            }
+            TopLevelItem::Settings(_) => item,
+            TopLevelItem::WithSettings { item, settings } => TopLevelItem::WithSettings {
+                item: Box::new(self.rewrite(*item)?),
+                settings,
             },
-            Expr::Call { func, args } => match self.bindings.get(func) {
-                Some(Binding::Builtin(builtin)) => bail!("Builtin functions not yet implemented"),
-
-                Some(Binding::Function {
-                    args: arg_names,
-                    body,
-                }) => {
-                    // eprintln!("evaluating {func}");
-                    if arg_names.len() != args.len() {
-                        bail!(
-                            "Cannot pass {} arguments to a function taking {} arguments",
-                            args.len(),
-                            arg_names.len(),
+        })
+    }
+
+    /// Replaces the literals in `expr` by the fractions they were written as, so that
+    /// the item keeps the exact mode it was added in wherever it is evaluated.
+    fn convert_to_exact(expr: &Expr) -> Expr {
+        match &expr.kind {
+            ExprKind::Lit(x) => {
+                Expr::new(ExprKind::Rational(Rational::from_literal(*x)), expr.span)
+            }
+            _ => expr.map_children(Self::convert_to_exact),
+        }
+    }
+
+    /// Makes the trigonometric functions in `expr` take and return degrees, by
+    /// converting their arguments with `°` and dividing their results by `1°`.
+    ///
+    /// Angles are already in degrees, so the `°`s in the input are dropped, and
+    /// derivatives like `sin'(x)` are taken of the converted functions.
+    fn convert_to_degrees(&self, expr: &Expr) -> Result<Expr> {
+        use BuiltinFunction::*;
+        let expr = expr.try_map_children(|expr| self.convert_to_degrees(expr))?;
+        let (func, args) = match expr.kind {
+            ExprKind::UnOp {
+                op: UnaryOp::Degrees,
+                arg,
+            } => return Ok(*arg),
+            ExprKind::Call { ref func, ref args } => (func, args),
+            _ => return Ok(expr),
+        };
+        let name = Ident::from(func.trim_end_matches('\''));
+        let Some(Binding::Builtin(builtin @ (Sin | Cos | Tan | Asin | Acos | Atan | Atan2 | Arg))) =
+            self.bindings.get(&name)
+        else {
+            return Ok(expr);
+        };
+        let span = expr.span;
+        if name != *func {
+            let ([arg], 1) = (args.as_slice(), builtin.arity()) else {
+                return Ok(expr);
+            };
+            // the same placeholder as for the derivatives of other builtins
+            let var = Ident::from("_");
+            let variable = Expr::new(ExprKind::Variable(var.clone()), span);
+            let call = ExprKind::Call {
+                func: name.clone(),
+                args: ArgList::from_head(variable),
+            };
+            let definitions = |name: &Ident| self.definition(name);
+            let mut result = self.convert_to_degrees(&Expr::new(call, span))?;
+            for _ in 0..func.len() - name.len() {
+                result = derivative(&result, &var, &definitions)?;
+            }
+            return Ok(substitute(&result, &HashMap::from([(var, arg.clone())])));
+        }
+        Ok(match builtin {
+            Sin | Cos | Tan => {
+                let args = args
+                    .iter()
+                    .map(|arg| Expr::un_op(UnaryOp::Degrees, arg.clone()))
+                    .collect();
+                let kind = ExprKind::Call {
+                    func: func.clone(),
+                    args: ArgList::from_vec(args),
+                };
+                Expr::new(kind, span)
+            }
+            _ => {
+                let degree = Expr::un_op(UnaryOp::Degrees, Expr::new(ExprKind::Lit(1.0), span));
+                Expr::bin_op(BinaryOp::Divide, expr, degree)
+            }
+        })
+    }
+
+    /// Solves the equations of `solve(...)` from each starting point, or from a grid of
+    /// them if none are given. A single start gives its solution, and otherwise the
+    /// distinct solutions are listed in order.
+    fn solve(
+        &self,
+        equations: &ArgList<(Expr, Expr)>,
+        unknowns: &ArgList<Ident>,
+        start: Option<&Expr>,
+    ) -> Result<Value> {
+        let start = match start {
+            Some(start) => match self.evaluate(start, &HashMap::new())? {
+                Value::List(elements) if elements.is_empty() => {
+                    bail_at!(start.span, "Expected at least one start")
+                }
+                value => Some(value),
+            },
+            None => None,
+        };
+        let single = !matches!(start, None | Some(Value::List(_)));
+        let mut solutions = match unknowns.len() {
+            1 => {
+                let starts = match &start {
+                    Some(Value::List(elements)) => elements
+                        .iter()
+                        .map(|x| Ok([x.as_number()?]))
+                        .collect::<Result<_>>()?,
+                    Some(x) => vec![[x.as_number()?]],
+                    None => (-10..=10).map(|x| [x as f64]).collect(),
+                };
+                self.solve_system(equations, unknowns, starts)?
+            }
+            _ => {
+                let starts = match &start {
+                    Some(start) => start.points().expect("checked to be points"),
+                    None => {
+                        let grid = (-4..=4).map(|i| i as f64 * 2.5);
+                        let grid = grid.clone().flat_map(|x| grid.clone().map(move |y| (x, y)));
+                        grid.collect()
+                    }
+                };
+                let starts = starts.into_iter().map(|(x, y)| [x, y]).collect();
+                self.solve_system(equations, unknowns, starts)?
+            }
+        };
+        Ok(match single {
+            true => solutions.remove(0),
+            false => Value::List(solutions.into()),
+        })
+    }
+
+    /// Runs Newton's method for `N` equations from each start, falling back to
+    /// bracketing for a single equation, and returns the distinct solutions found in
+    /// order. Fails with the smallest residual found if there are none.
+    fn solve_system<const N: usize>(
+        &self,
+        equations: &ArgList<(Expr, Expr)>,
+        unknowns: &ArgList<Ident>,
+        starts: Vec<[f64; N]>,
+    ) -> Result<Vec<Value>> {
+        let residuals: Vec<Expr> = equations
+            .iter()
+            .map(|(left, right)| Expr::bin_op(BinaryOp::Subtract, left.clone(), right.clone()))
+            .collect();
+        let f = |x: [f64; N]| {
+            let arg_map = std::iter::zip(unknowns.iter().cloned(), x.map(Value::Number)).collect();
+            let mut y = [0.0; N];
+            for (y, residual) in std::iter::zip(&mut y, &residuals) {
+                *y = self.evaluate(residual, &arg_map)?.as_sample()?;
+            }
+            Ok(y)
+        };
+        let jacobian = |x: [f64; N]| {
+            let arg_map = std::iter::zip(unknowns.iter().cloned(), x).collect();
+            let mut j = [[0.0; N]; N];
+            for (row, residual) in std::iter::zip(&mut j, &residuals) {
+                let (_, gradient) = self.evaluate_with_gradient(residual, &arg_map, unknowns)?;
+                row.copy_from_slice(&gradient);
+            }
+            Ok(j)
+        };
+        let to_value = |x: [f64; N]| match x.as_slice() {
+            [x] => Value::Number(*x),
+            [x, y] => Value::Point(*x, *y),
+            _ => unreachable!("only systems of up to two equations are solved"),
+        };
+
+        let mut solutions = Vec::new();
+        let mut best: Option<Solution<N>> = None;
+        for start in starts {
+            let near = |x: f64| -> Result<Option<Solution<N>>> {
+                let Some(root) = Brent::default().root_near(|x| Ok(f([x; N])?[0]), x)? else {
+                    return Ok(None);
+                };
+                Ok(Some(Solution {
+                    x: [root; N],
+                    residual: f([root; N])?[0].abs(),
+                    converged: true,
+                }))
+            };
+            let solution = match (
+                Newton::default().solve(f, jacobian, start),
+                start.as_slice(),
+            ) {
+                (Ok(solution), [x]) if !solution.converged => near(*x)?.unwrap_or(solution),
+                // Newton's method can fail where bracketing still finds a root nearby
+                (Err(error), [x]) => near(*x).ok().flatten().ok_or(error)?,
+                (solution, _) => solution?,
+            };
+            match solution.converged {
+                true => solutions.push(solution.x),
+                false
+                    if best.is_none_or(|best| {
+                        best.residual.is_nan() || solution.residual < best.residual
+                    }) =>
+                {
+                    best = Some(solution)
+                }
+                false => {}
+            }
+        }
+        if solutions.is_empty() {
+            let Some(best) = best else {
+                bail!("Expected at least one start");
+            };
+            let (left, right) = (&equations[0].0, &equations[equations.len() - 1].1);
+            let equations = equations
+                .iter()
+                .map(|(left, right)| format!("{left} = {right}"));
+            let unknowns = match N {
+                1 => unknowns.to_string(),
+                _ => format!("({unknowns})"),
+            };
+            bail_at!(
+                left.span.to(right.span),
+                "Could not solve {}, the smallest residual found is {:.1e} at {unknowns} = {}",
+                equations.collect::<Vec<_>>().join(", "),
+                best.residual,
+                self.settings.format.value(&to_value(best.x)),
+            );
+        }
+        solutions.sort_by(|a, b| {
+            std::iter::zip(a, b)
+                .map(|(a, b)| a.total_cmp(b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        // the same solution reached from different starts differs by rounding
+        solutions.dedup_by(|a, b| {
+            std::iter::zip(a, b).all(|(a, b)| (*a - *b).abs() <= 1e-9 * (1.0 + b.abs()))
+        });
+        Ok(solutions.into_iter().map(to_value).collect())
+    }
+
+    /// The parameters of a fit, which are the variables of its model that are not bound.
+    fn fit_parameters(&self, model: &Expr) -> Vec<Ident> {
+        let variables = model.variables().into_iter();
+        variables
+            .filter(|name| !self.bindings.contains_key(name))
+            .collect()
+    }
+
+    /// Fits the parameters of `model` to `data` by least squares and binds them,
+    /// directly if the model is linear in them and by Levenberg–Marquardt from all
+    /// ones otherwise. The fitted values are shown along with `R²` and the residuals,
+    /// and a model of a single list is drawn as a curve through the data.
+    fn fit(&mut self, data: &Expr, model: &Expr) -> Result<()> {
+        let parameters = self.fit_parameters(model);
+        let y = match self.evaluate(data, &HashMap::new())? {
+            Value::List(elements) => elements
+                .iter()
+                .map(|element| match element {
+                    Value::Number(_) | Value::Rational(_) => element.as_number(),
+                    _ => bail_at!(data.span, "Cannot fit a {}", element.type_name()),
+                })
+                .collect::<Result<Vec<_>>>()?,
+            value => bail_at!(data.span, "Expected a list but got a {}", value.type_name()),
+        };
+        // the values of an expression of the parameters at each data point
+        let evaluate_at = |expr: &Expr, values: &[f64]| -> Result<Vec<f64>> {
+            let values = values.iter().map(|&x| Value::Number(x));
+            let arg_map = std::iter::zip(parameters.iter().cloned(), values).collect();
+            match self.evaluate(expr, &arg_map)? {
+                Value::List(elements) if elements.len() == y.len() => {
+                    elements.iter().map(Value::as_number).collect()
+                }
+                Value::List(elements) => bail_at!(
+                    model.span,
+                    "Cannot fit {} values of {model} to {} data points",
+                    elements.len(),
+                    y.len()
+                ),
+                value => Ok(vec![value.as_number()?; y.len()]),
+            }
+        };
+        let residuals = |values: &[f64]| -> Result<Vec<f64>> {
+            let predictions = evaluate_at(model, values)?;
+            Ok(std::iter::zip(&y, predictions)
+                .map(|(y, p)| y - p)
+                .collect())
+        };
+
+        // the model is linear if its derivatives don't depend on the parameters, and
+        // then they are the columns of the least squares problem
+        let definitions = |name: &Ident| self.definition(name);
+        let derivatives = parameters
+            .iter()
+            .map(|parameter| derivative(model, parameter, &definitions))
+            .collect::<Result<Vec<_>>>();
+        let linear = derivatives.as_ref().is_ok_and(|derivatives| {
+            derivatives.iter().all(|derivative| {
+                let variables = derivative.variables();
+                !parameters
+                    .iter()
+                    .any(|parameter| variables.contains(parameter))
+            })
+        });
+        let values = match derivatives {
+            Ok(derivatives) if linear => {
+                let zeros = vec![0.0; parameters.len()];
+                let columns = derivatives
+                    .iter()
+                    .map(|derivative| evaluate_at(derivative, &zeros))
+                    .collect::<Result<Vec<_>>>()?;
+                match Matrix::from_columns(&columns).least_squares(&residuals(&zeros)?) {
+                    Some(values) => values,
+                    None => bail_at!(
+                        model.span,
+                        "Cannot fit {model}, as the data does not determine its parameters"
+                    ),
+                }
+            }
+            _ => {
+                // at each data point the model is a number, with its lists replaced by
+                // their elements there, and is differentiated over dual numbers
+                let mut lists = Vec::new();
+                for name in model.variables() {
+                    if let Some(Binding::Value(Value::List(elements))) = self.bindings.get(&name)
+                        && elements.len() == y.len()
+                    {
+                        let elements = elements.iter().map(Value::as_number);
+                        lists.push((name, elements.collect::<Result<Vec<_>>>()?));
+                    }
+                }
+                let jacobian = |values: &[f64]| -> Result<Vec<Vec<f64>>> {
+                    let mut columns = vec![Vec::with_capacity(y.len()); parameters.len()];
+                    for k in 0..y.len() {
+                        let mut arg_map: HashMap<Ident, f64> =
+                            std::iter::zip(parameters.iter().cloned(), values.iter().copied())
+                                .collect();
+                        arg_map.extend(
+                            lists
+                                .iter()
+                                .map(|(name, elements)| (name.clone(), elements[k])),
                         );
+                        let (_, gradient) =
+                            self.evaluate_with_gradient(model, &arg_map, &parameters)?;
+                        for (column, partial) in std::iter::zip(&mut columns, gradient) {
+                            // the residuals are the data minus the model
+                            column.push(-partial);
+                        }
                     }
+                    Ok(columns)
+                };
+                let start = vec![1.0; parameters.len()];
+                let minimum = LevenbergMarquardt::default().minimize(residuals, jacobian, start)?;
+                if !minimum.residuals.iter().all(|r| r.is_finite()) {
+                    bail_at!(
+                        model.span,
+                        "Could not fit {model}, as it is undefined at the data"
+                    );
+                }
+                if !minimum.converged {
+                    self.warn(model, format!("The fit of {model} may not have converged"));
+                }
+                minimum.x
+            }
+        };
+
+        let residuals = residuals(&values)?;
+        let format = self.settings.format;
+        for (parameter, value) in std::iter::zip(&parameters, &values) {
+            let value = Value::Number(*value);
+            self.constants
+                .push((Some(parameter.clone()), value.clone(), format));
+            self.bindings
+                .insert(parameter.clone(), Binding::Value(value));
+        }
+        let mean = y.iter().sum::<f64>() / y.len() as f64;
+        let total: f64 = y.iter().map(|y| (y - mean) * (y - mean)).sum();
+        let unexplained: f64 = residuals.iter().map(|r| r * r).sum();
+        let r_squared = Value::Number(1.0 - unexplained / total);
+        self.constants
+            .push((Some(Ident::from("R²")), r_squared, format));
+        let residuals = Value::List(residuals.into_iter().map(Value::Number).collect());
+        self.constants
+            .push((Some(Ident::from("residuals")), residuals, format));
+
+        let lists: Vec<Ident> = model
+            .variables()
+            .into_iter()
+            .filter(|name| {
+                matches!(
+                    self.bindings.get(name),
+                    Some(Binding::Value(Value::List(_)))
+                )
+            })
+            .collect();
+        if let [x] = lists.as_slice()
+            && let Some(Binding::Value(Value::List(xs))) = self.bindings.get(x)
+        {
+            let xs = xs
+                .iter()
+                .map(Value::as_number)
+                .collect::<Result<Vec<_>>>()?;
+            self.fitted_data.push(std::iter::zip(xs, y).collect());
+            self.single_var_functions.push((x.clone(), model.clone()));
+        }
+        Ok(())
+    }
+
+    /// Evaluates the right hand side of a differential equation as a vector field in the plane.
+    ///
+    /// For a scalar equation the state is the point `(x, y)` itself and `t` equals `x`.
+    pub fn evaluate_derivative(
+        &self,
+        equation: &DifferentialEquation,
+        t: f64,
+        [x, y]: [f64; 2],
+    ) -> Result<[f64; 2]> {
+        let mut arg_map = HashMap::new();
+        match equation {
+            DifferentialEquation::Scalar { name, body } => {
+                arg_map.insert(
+                    Ident::from(DifferentialEquation::INDEPENDENT_SCALAR),
+                    Value::Number(x),
+                );
+                arg_map.insert(name.clone(), Value::Number(y));
+                Ok([1.0, self.evaluate(body, &arg_map)?.as_number()?])
+            }
+            DifferentialEquation::System {
+                names: [x_name, y_name],
+                bodies: [x_body, y_body],
+            } => {
+                arg_map.insert(
+                    Ident::from(DifferentialEquation::INDEPENDENT_SYSTEM),
+                    Value::Number(t),
+                );
+                arg_map.insert(x_name.clone(), Value::Number(x));
+                arg_map.insert(y_name.clone(), Value::Number(y));
+                Ok([
+                    self.evaluate(x_body, &arg_map)?.as_number()?,
+                    self.evaluate(y_body, &arg_map)?.as_number()?,
+                ])
+            }
+        }
+    }
+
+    /// Records a warning about `expr`, unless there already is one.
+    fn warn(&self, expr: &Expr, message: String) {
+        let mut warnings = self.warnings.borrow_mut();
+        if !warnings.iter().any(|(other, _)| other == expr) {
+            warnings.push((expr.clone(), message));
+        }
+    }
+
+    /// All warnings so far, oldest first.
+    pub fn warnings(&self) -> Vec<String> {
+        let warnings = self.warnings.borrow();
+        warnings
+            .iter()
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+
+    /// Calls the function bound to `func` with already evaluated arguments.
+    pub fn call(&self, func: &Ident, args: &[Value]) -> Result<Value> {
+        self.call_as(func, args)
+    }
+
+    pub fn evaluate(&self, expr: &Expr, arg_map: &HashMap<Ident, Value>) -> Result<Value> {
+        self.evaluate_as(expr, arg_map)
+    }
+
+    /// Evaluates `body` and its exact derivative with respect to `arg` at `x`, in one
+    /// pass over dual numbers.
+    pub fn evaluate_with_derivative(&self, body: &Expr, arg: &Ident, x: f64) -> Result<(f64, f64)> {
+        let (y, gradient) = self.evaluate_with_gradient(
+            body,
+            &HashMap::from([(arg.clone(), x)]),
+            slice::from_ref(arg),
+        )?;
+        Ok((y, gradient[0]))
+    }
+
+    /// Evaluates `body` with the arguments in `arg_map` and its partial derivatives with
+    /// respect to each of `args`, in one pass over dual numbers per derivative.
+    pub fn evaluate_with_gradient(
+        &self,
+        body: &Expr,
+        arg_map: &HashMap<Ident, f64>,
+        args: &[Ident],
+    ) -> Result<(f64, Vec<f64>)> {
+        let mut value = f64::NAN;
+        let mut gradient = Vec::with_capacity(args.len());
+        for i in 0..args.len().max(1) {
+            let arg_map = arg_map
+                .iter()
+                .map(|(name, &x)| match args.get(i) == Some(name) {
+                    true => (name.clone(), Dual::variable(x)),
+                    false => (name.clone(), Dual::constant(x)),
+                })
+                .collect();
+            let y = self.evaluate_as::<Dual>(body, &arg_map)?;
+            value = y.value;
+            if i < args.len() {
+                gradient.push(y.derivative);
+            }
+        }
+        Ok((value, gradient))
+    }
+
+    /// Calls the function bound to `func` with arguments in any number system.
+    pub fn call_as<N: Number>(&self, func: &Ident, args: &[N]) -> Result<N> {
+        match self.bindings.get(func) {
+            Some(Binding::Builtin(builtin)) if builtin.function_arity().is_some() => {
+                bail!("'{builtin}' takes a function as its first argument")
+            }
+            Some(Binding::Builtin(
+                builtin @ (BuiltinFunction::Sum | BuiltinFunction::Prod | BuiltinFunction::Int),
+            )) => bail!("'{builtin}' binds a variable and cannot be called with values"),
+            Some(Binding::Builtin(builtin)) => N::apply(*builtin, args),
+            Some(Binding::Function {
+                args: arg_names,
+                body,
+            }) => {
+                if arg_names.len() != args.len() {
+                    bail!(
+                        "Cannot pass {} arguments to a function taking {} arguments",
+                        args.len(),
+                        arg_names.len(),
+                    );
+                }
+                // eprintln!("evaluating {func}");
+                let arg_map = std::iter::zip(arg_names.iter().cloned(), args.iter().cloned());
+                self.evaluate_body(body, &arg_map.collect())
+            }
+            Some(Binding::Sequence(sequence)) => {
+                let [index] = args else {
+                    bail!(
+                        "Cannot pass {} arguments to a sequence, which takes an index",
+                        args.len()
+                    );
+                };
+                let n = match index.to_f64()? {
+                    n if n.fract() == 0.0 => n as i64,
+                    n => bail!("The index of sequence '{func}' must be an integer, not {n}"),
+                };
+                N::from_value(&self.term(func, sequence, n)?)
+            }
+            Some(Binding::Value(Value::Function(function))) => self.call_function(function, args),
+            Some(Binding::Value(_)) => bail!("Cannot call '{func}' as it is not a function"),
+            None => bail!("Function '{func}' not defined"),
+        }
+    }
+
+    /// The index of the first base case of the sequence `name`, from which on it is plotted.
+    pub fn first_index(&self, name: &Ident) -> Option<i64> {
+        match self.bindings.get(name)? {
+            Binding::Sequence(sequence) => sequence.base_cases.keys().next().copied(),
+            _ => None,
+        }
+    }
+
+    /// The `n`th term of a sequence. The terms before it are computed first, so that
+    /// recurrences on the previous terms never have to recurse deeply.
+    fn term(&self, name: &Ident, sequence: &Sequence, n: i64) -> Result<Value> {
+        let known = |n| {
+            let base_case = sequence.base_cases.get(&n).cloned();
+            base_case.or_else(|| sequence.terms.borrow().get(&n).cloned())
+        };
+        if let Some(term) = known(n) {
+            return Ok(term);
+        }
+        let Some(&first) = sequence.base_cases.keys().next() else {
+            bail!("Sequence '{name}' has no base cases, like {name}(0) := 1");
+        };
+        if n < first {
+            bail!("{name}({n}) comes before the first base case {name}({first})");
+        }
+        if n - first > MAX_TERMS as i64 {
+            bail!("Cannot compute more than {MAX_TERMS} terms of '{name}'");
+        }
+        let Some((index, body)) = &sequence.definition else {
+            bail!("{name}({n}) is not a base case and '{name}' has no recursive definition");
+        };
+        let compute = |n| {
+            let depth = self.depth.get();
+            if depth >= MAX_DEPTH {
+                bail!(
+                    "{name}({n}) refers to terms more than {MAX_DEPTH} levels deep without \
+                     reaching a base case"
+                );
+            }
+            let index_value = match sequence.exact {
+                true => Value::Rational(Rational::from_integer(n)),
+                false => Value::Number(n as f64),
+            };
+            self.depth.set(depth + 1);
+            let term = self.evaluate(body, &HashMap::from([(index.clone(), index_value)]));
+            self.depth.set(depth);
+            let term = term?;
+            sequence.terms.borrow_mut().insert(n, term.clone());
+            Ok(term)
+        };
+        if !sequence.warming_up.replace(true) {
+            for m in first + 1..n {
+                // the terms after one that fails would likely fail as well
+                if known(m).is_none() && compute(m).is_err() {
+                    break;
+                }
+            }
+            sequence.warming_up.set(false);
+        }
+        compute(n)
+    }
+
+    /// Calls a function value with arguments in any number system.
+    pub fn call_function<N: Number>(&self, function: &Function, args: &[N]) -> Result<N> {
+        let closure = match function {
+            Function::Named(name) => return self.call_as(name, args),
+            Function::Closure(closure) => closure,
+        };
+        if closure.args.len() != args.len() {
+            bail!(
+                "Cannot pass {} arguments to a function taking {} arguments",
+                args.len(),
+                closure.args.len(),
+            );
+        }
+        let mut arg_map = HashMap::new();
+        for (name, value) in closure.captured.iter() {
+            arg_map.insert(name.clone(), N::from_value(value)?);
+        }
+        arg_map.extend(std::iter::zip(
+            closure.args.iter().cloned(),
+            args.iter().cloned(),
+        ));
+        self.evaluate_body(&closure.body, &arg_map)
+    }
+
+    /// Evaluates the body of a function for a call, failing instead of overflowing the
+    /// stack when a function keeps calling itself through its arguments.
+    fn evaluate_body<N: Number>(&self, body: &Expr, arg_map: &HashMap<Ident, N>) -> Result<N> {
+        let depth = self.call_depth.get();
+        if depth >= MAX_CALL_DEPTH {
+            bail!("Cannot nest calls of functions more than {MAX_CALL_DEPTH} levels deep");
+        }
+        self.call_depth.set(depth + 1);
+        let result = self.evaluate_as(body, arg_map);
+        self.call_depth.set(depth);
+        result
+    }
+
+    /// Evaluates `ode(f, x0, y0, x)`, the solution of `y' = f(x, y)` with `y(x0) = y0` at `x`.
+    fn evaluate_ode<N: Number>(
+        &self,
+        args: &ArgList<Expr>,
+        arg_map: &HashMap<Ident, N>,
+    ) -> Result<N> {
+        let [f, x0, y0, x] = args.as_slice() else {
+            bail!(
+                "Cannot pass {} arguments to a function taking 4 arguments",
+                args.len()
+            );
+        };
+        let f = self.evaluate_as(f, arg_map)?;
+        let f = f.as_function()?;
+        let (x0, y0, x) = (
+            self.evaluate_as(x0, arg_map)?.to_f64()?,
+            self.evaluate_as(y0, arg_map)?.to_f64()?,
+            self.evaluate_as(x, arg_map)?.to_f64()?,
+        );
+        let points = Solver::default().integrate(
+            |x, [y]| {
+                Ok([self
+                    .call_function(f, &[Value::Number(x), Value::Number(y)])?
+                    .as_number()?])
+            },
+            x0,
+            [y0],
+            x,
+            |_, _| false,
+        )?;
+        Ok(N::from_f64(match points.last() {
+            Some(&(end, [y])) if (end - x).abs() <= 1e-9 * x.abs().max(1.0) => y,
+            // the solution blew up or the step budget ran out before reaching `x`
+            _ => f64::NAN,
+        }))
+    }
 
-                    let mut new_arg_map = HashMap::new();
-                    for (arg_name, arg) in std::iter::zip(arg_names.iter(), args.iter()) {
-                        let evaluated = self.evaluate(arg, arg_map)?;
-                        new_arg_map.insert(arg_name.clone(), evaluated);
+    /// Evaluates `root(f, a, b)`, `argmin(f, a, b)` or `argmax(f, a, b)` by sampling
+    /// `f` to find a bracket and refining it with Brent's method, which gives an
+    /// undefined result if there is no zero or `f` is undefined everywhere.
+    fn evaluate_search<N: Number>(
+        &self,
+        builtin: BuiltinFunction,
+        args: &ArgList<Expr>,
+        arg_map: &HashMap<Ident, N>,
+    ) -> Result<N> {
+        let [f, a, b] = args.as_slice() else {
+            bail!(
+                "Cannot pass {} arguments to a function taking 3 arguments",
+                args.len()
+            );
+        };
+        let f = self.evaluate_as(f, arg_map)?;
+        let f = f.as_function()?;
+        let (a, b) = (
+            self.evaluate_as(a, arg_map)?.to_f64()?,
+            self.evaluate_as(b, arg_map)?.to_f64()?,
+        );
+        let sign = match builtin {
+            BuiltinFunction::Argmax => -1.0,
+            _ => 1.0,
+        };
+        let f = |x| Ok(sign * self.call_function(f, &[Value::Number(x)])?.as_sample()?);
+        let samples = roots::sample(f, a, b, SEARCH_SAMPLES)?;
+        let brent = Brent::default();
+        let x = match builtin {
+            BuiltinFunction::Root => brent.roots(f, &samples)?.first().copied(),
+            _ => brent.argmin(f, &samples)?,
+        };
+        Ok(N::from_f64(x.unwrap_or(f64::NAN)))
+    }
+
+    /// Evaluates `map(f, L)`, `filter(f, L)` or `fold(f, x0, L)` by calling `f` with
+    /// each element of `L` in turn. `filter` keeps the elements where `f` is a
+    /// nonzero number, so elements where it is undefined are dropped.
+    fn evaluate_higher_order<N: Number>(
+        &self,
+        builtin: BuiltinFunction,
+        args: &ArgList<Expr>,
+        arg_map: &HashMap<Ident, N>,
+    ) -> Result<N> {
+        let [f, rest @ ..] = args.as_slice() else {
+            unreachable!("argument lists are never empty")
+        };
+        check_arity(builtin, args)?;
+        let f = self.evaluate_as(f, arg_map)?;
+        let f = f.as_function()?;
+        let list = self.evaluate_as(&rest[rest.len() - 1], arg_map)?;
+        let elements = list.elements()?;
+        match builtin {
+            BuiltinFunction::Map => N::list(
+                elements
+                    .iter()
+                    .map(|element| self.call_function(f, slice::from_ref(element)))
+                    .collect::<Result<_>>()?,
+            ),
+            BuiltinFunction::Filter => {
+                let mut kept = Vec::new();
+                for element in elements {
+                    let keep = self.call_function(f, slice::from_ref(&element))?.to_f64()?;
+                    if keep != 0.0 && !keep.is_nan() {
+                        kept.push(element);
                     }
-                    self.evaluate(body, &new_arg_map)?
                 }
-                Some(Binding::Value(x)) => {
-                    bail!("Cannot call '{func}' as it is not a function")
+                N::list(kept)
+            }
+            _ => {
+                let mut result = self.evaluate_as(&rest[0], arg_map)?;
+                for element in elements {
+                    result = self.call_function(f, &[result, element])?;
                 }
-                None => bail!("Function '{func}' not defined"),
+                Ok(result)
+            }
+        }
+    }
+
+    /// Evaluates `sum(k, a, b, f)` or `prod(k, a, b, f)` by binding the index `k`
+    /// to each integer from `a` to `b` in turn, where an empty range gives 0 or 1,
+    /// or `int(f, t, a, b)` by numerical integration.
+    fn evaluate_big_operator<N: Number>(
+        &self,
+        expr: &Expr,
+        builtin: BuiltinFunction,
+        args: &ArgList<Expr>,
+        arg_map: &HashMap<Ident, N>,
+    ) -> Result<N> {
+        let Some([var, from, to, body]) = builtin.bound_args(args) else {
+            bail!(
+                "Cannot pass {} arguments to a function taking 4 arguments",
+                args.len()
+            );
+        };
+        let ExprKind::Variable(index) = &var.kind else {
+            bail!("Expected the name of a variable but got {var}");
+        };
+        if let BuiltinFunction::Int = builtin {
+            let (from, to) = (
+                self.evaluate_as(from, arg_map)?.to_f64()?,
+                self.evaluate_as(to, arg_map)?.to_f64()?,
+            );
+            let mut arg_map = arg_map.clone();
+            let estimate = Quadrature::default().integrate(
+                |t| {
+                    arg_map.insert(index.clone(), N::from_f64(t));
+                    self.evaluate_as(body, &arg_map)?.to_f64()
+                },
+                from,
+                to,
+            )?;
+            // an undefined integral is already displayed as such
+            if !estimate.converged && !estimate.value.is_nan() {
+                self.warn(
+                    expr,
+                    format!(
+                        "{expr} may be inaccurate, with an estimated error of {:.1e}",
+                        estimate.error
+                    ),
+                );
+            }
+            return Ok(N::from_f64(estimate.value));
+        }
+        let bound = |x: &N| match x.to_f64()? {
+            x if x.fract() != 0.0 => bail!("The bounds of '{builtin}' must be integers, not {x}"),
+            x if x.abs() > MAX_BOUND => {
+                bail!("The bounds of '{builtin}' must be at most 2^53 in magnitude, not {x}")
+            }
+            x => Ok(x as i64),
+        };
+        let lower = self.evaluate_as(from, arg_map)?;
+        let (from, to) = (bound(&lower)?, bound(&self.evaluate_as(to, arg_map)?)?);
+        if to - from >= MAX_TERMS as i64 {
+            bail!("Cannot take the {builtin} of more than {MAX_TERMS} terms");
+        }
+        // the index is exact if the lower bound is, so sums of fractions stay exact
+        let integer = |n: i64| {
+            let offset = N::from_rational(Rational::from_integer(n - from));
+            N::binary_op(BinaryOp::Add, &lower, &offset)
+        };
+        let op = match builtin {
+            BuiltinFunction::Sum => BinaryOp::Add,
+            _ => BinaryOp::DotProduct,
+        };
+        let mut arg_map = arg_map.clone();
+        let mut result: Option<N> = None;
+        for k in from..=to {
+            arg_map.insert(index.clone(), integer(k)?);
+            let term = self.evaluate_as(body, &arg_map)?;
+            result = Some(match result {
+                Some(result) => N::binary_op(op, &result, &term)?,
+                None => term,
+            });
+        }
+        Ok(match (result, builtin) {
+            (Some(result), _) => result,
+            (None, BuiltinFunction::Sum) => integer(0)?,
+            (None, _) => integer(1)?,
+        })
+    }
+
+    /// Evaluates `[a...b]` or `[a, b...c]`, which steps from `a` by 1 or `b - a` for as
+    /// long as it does not pass the last bound.
+    fn evaluate_range<N: Number>(
+        &self,
+        first: &Expr,
+        second: Option<&Expr>,
+        last: &Expr,
+        arg_map: &HashMap<Ident, N>,
+    ) -> Result<N> {
+        // integers are exact, and leave the elements exact only if the bounds are
+        let integer = |n: i64| N::from_rational(Rational::from_integer(n));
+        let first = self.evaluate_as(first, arg_map)?;
+        let last = self.evaluate_as(last, arg_map)?;
+        let step = match second {
+            Some(second) => {
+                let second = self.evaluate_as(second, arg_map)?;
+                N::binary_op(BinaryOp::Subtract, &second, &first)?
+            }
+            None if last.to_f64()? < first.to_f64()? => integer(-1),
+            None => integer(1),
+        };
+        let span = N::binary_op(BinaryOp::Subtract, &last, &first)?;
+        let count = match span.to_f64()? / step.to_f64()? {
+            _ if step.to_f64()? == 0.0 => bail!("A range cannot have a step of 0"),
+            count if count.is_nan() => bail!("The bounds of a range must be finite"),
+            count if count < 0.0 => 0.0,
+            // allow for rounding in steps like 0.1 that floats cannot represent
+            count => (count + 1e-9).floor() + 1.0,
+        };
+        if count > MAX_TERMS as f64 {
+            bail!("A range cannot have more than {MAX_TERMS} elements");
+        }
+        if count < 2.0 {
+            return N::list(if count == 0.0 { vec![] } else { vec![first] });
+        }
+        // interpolating between the ends rather than adding up steps keeps elements like
+        // 0.3 of `[0, 0.1 ... 1]` as close to their decimal value as floats allow
+        let intervals = count as i64 - 1;
+        let offset = N::binary_op(BinaryOp::DotProduct, &integer(intervals), &step)?;
+        let end = N::binary_op(BinaryOp::Add, &first, &offset)?;
+        let elements = (0..=intervals).map(|k| {
+            let from_first = N::binary_op(BinaryOp::DotProduct, &first, &integer(intervals - k))?;
+            let from_end = N::binary_op(BinaryOp::DotProduct, &end, &integer(k))?;
+            let sum = N::binary_op(BinaryOp::Add, &from_first, &from_end)?;
+            N::binary_op(BinaryOp::Divide, &sum, &integer(intervals))
+        });
+        N::list(elements.collect::<Result<_>>()?)
+    }
+
+    /// Evaluates an expression in any number system, such as `Value` or `Dual` for
+    /// values together with their derivatives.
+    pub fn evaluate_as<N: Number>(&self, expr: &Expr, arg_map: &HashMap<Ident, N>) -> Result<N> {
+        Ok(match &expr.kind {
+            ExprKind::Rational(x) => N::from_rational(x.clone()),
+            ExprKind::Lit(x) => N::from_f64(*x),
+            ExprKind::Quantity(x, unit) => N::from_quantity(*x, unit),
+            ExprKind::Variable(name) => match arg_map.get(name) {
+                Some(x) => x.clone(),
+                None => match self.bindings.get(name) {
+                    Some(Binding::Value(x)) => N::from_value(x)?,
+                    Some(Binding::Function { .. } | Binding::Builtin(_) | Binding::Sequence(_)) => {
+                        N::from_value(&Value::Function(Function::Named(name.clone())))?
+                    }
+                    None => bail!("Binding '{name}' not defined"),
+                },
             },
-            Expr::UnOp { op, arg } => {
-                let arg = self.evaluate(arg, arg_map)?;
-                match op {
-                    UnaryOp::Negate => -arg,
-                    UnaryOp::Plus => arg,
-                }
-            }
-            Expr::BinOp { op, left, right } => {
-                let left = self.evaluate(left, arg_map)?;
-                let right = self.evaluate(right, arg_map)?;
-                match op {
-                    BinaryOp::Add => left + right,
-                    BinaryOp::Subtract => left - right,
-                    BinaryOp::DotProduct => left * right,
-                    BinaryOp::Divide => left / right,
+            ExprKind::Call { func, args } => match self.bindings.get(func) {
+                Some(Binding::Builtin(BuiltinFunction::Ode)) => self.evaluate_ode(args, arg_map)?,
+                Some(Binding::Builtin(
+                    builtin @ (BuiltinFunction::Root
+                    | BuiltinFunction::Argmin
+                    | BuiltinFunction::Argmax),
+                )) => self.evaluate_search(*builtin, args, arg_map)?,
+                Some(Binding::Builtin(
+                    builtin @ (BuiltinFunction::Map
+                    | BuiltinFunction::Filter
+                    | BuiltinFunction::Fold),
+                )) => self.evaluate_higher_order(*builtin, args, arg_map)?,
+                Some(Binding::Builtin(
+                    builtin @ (BuiltinFunction::Sum | BuiltinFunction::Prod | BuiltinFunction::Int),
+                )) => self.evaluate_big_operator(expr, *builtin, args, arg_map)?,
+                _ => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.evaluate_as(arg, arg_map))
+                        .collect::<Result<Vec<_>>>()?;
+                    match arg_map.get(func) {
+                        Some(function) => self.call_function(function.as_function()?, &args)?,
+                        None => self.call_as(func, &args)?,
+                    }
+                }
+            },
+            ExprKind::UnOp { op, arg } => {
+                let arg = self.evaluate_as(arg, arg_map)?;
+                N::unary_op(*op, &arg)?
+            }
+            ExprKind::BinOp { op, left, right } => {
+                let left = self.evaluate_as(left, arg_map)?;
+                let right = self.evaluate_as(right, arg_map)?;
+                N::binary_op(*op, &left, &right)?
+            }
+            ExprKind::Point(x, y) => {
+                N::point(self.evaluate_as(x, arg_map)?, self.evaluate_as(y, arg_map)?)?
+            }
+            ExprKind::List(elements) => N::list(
+                elements
+                    .iter()
+                    .map(|element| self.evaluate_as(element, arg_map))
+                    .collect::<Result<_>>()?,
+            )?,
+            ExprKind::Range {
+                first,
+                second,
+                last,
+            } => self.evaluate_range(first, second.as_deref(), last, arg_map)?,
+            ExprKind::Comprehension { body, var, list } => {
+                let mut arg_map = arg_map.clone();
+                let elements = self.evaluate_as(list, &arg_map)?.elements()?;
+                let elements = elements.into_iter().map(|element| {
+                    arg_map.insert(var.clone(), element);
+                    self.evaluate_as(body, &arg_map)
+                });
+                N::list(elements.collect::<Result<_>>()?)?
+            }
+            ExprKind::Index { list, index } => {
+                let elements = self.evaluate_as(list, arg_map)?.elements()?;
+                let index = match self.evaluate_as(index, arg_map)?.to_f64()? {
+                    i if i.fract() == 0.0 => i,
+                    i => bail!("Indices must be integers, not {i}"),
+                };
+                // lists are indexed from 1
+                match elements.get((index as usize).wrapping_sub(1)) {
+                    Some(element) if index >= 1.0 => element.clone(),
+                    _ => bail!(
+                        "Index {index} is out of range for a list of {} elements",
+                        elements.len()
+                    ),
+                }
+            }
+            ExprKind::Access { arg, field } => self.evaluate_as(arg, arg_map)?.access(*field)?,
+            ExprKind::Derivative { .. } => {
+                unreachable!("derivatives are expanded before evaluation")
+            }
+            ExprKind::Convert { arg, unit } => self.evaluate_as(arg, arg_map)?.convert(unit)?,
+            ExprKind::Lambda { args, body } => {
+                // global bindings never change, so only local variables need to be captured
+                let captured = arg_map.iter().filter(|(name, _)| {
+                    body.any(&|expr| match &expr.kind {
+                        ExprKind::Variable(var) | ExprKind::Call { func: var, .. } => var == *name,
+                        _ => false,
+                    })
+                });
+                let captured = captured.map(|(name, x)| (name.clone(), x.clone()));
+                N::closure(args, body, captured.collect())?
+            }
+            ExprKind::Let { bindings, body } => {
+                let mut arg_map = arg_map.clone();
+                for (name, value) in bindings.iter() {
+                    let value = self.evaluate_as(value, &arg_map)?;
+                    arg_map.insert(name.clone(), value);
                 }
+                self.evaluate_as(body, &arg_map)?
             }
         })
     }
@@ -0,0 +1,432 @@
+use std::cmp::Ordering;
+
+use crate::{
+    builtins::BuiltinFunction,
+    derivative::{Definition, Definitions},
+    parse::{BinaryOp, Expr, ExprKind, UnaryOp},
+    rational::Rational,
+    span::Span,
+    value::Value,
+};
+
+/// Rewrites expressions into an equivalent, usually cheaper form.
+///
+/// Numeric constants are folded, sums are collected into like terms and products
+/// into powers of distinct factors. Reordering is only valid for numbers, so sums
+/// and products of anything else only lose their trivial operations.
+pub struct Simplifier<'a> {
+    pub definitions: &'a Definitions<'a>,
+    /// Whether an expression is known to evaluate to a (real or complex) number.
+    pub is_number: &'a dyn Fn(&Expr) -> bool,
+    /// Whether constants are folded into exact fractions, as literals are in exact mode.
+    pub exact: bool,
+}
+
+/// A product `coefficient * base_1^exponent_1 * ...` with distinct bases in a canonical
+/// order, where the coefficient is a number or an exact fraction.
+#[derive(Debug, Clone, PartialEq)]
+struct Product {
+    coefficient: Value,
+    factors: Vec<(Expr, f64)>,
+}
+
+impl Simplifier<'_> {
+    /// Replaces every `simplify(...)` in `expr` by its simplified argument.
+    pub fn expand(&self, expr: &Expr) -> Expr {
+        let expr = expr.map_children(|expr| self.expand(expr));
+        match &expr.kind {
+            ExprKind::Call { func, args } => match ((self.definitions)(func), args.as_slice()) {
+                (Some(Definition::Builtin(BuiltinFunction::Simplify)), [arg]) => self.simplify(arg),
+                _ => expr,
+            },
+            _ => expr,
+        }
+    }
+
+    pub fn simplify(&self, expr: &Expr) -> Expr {
+        let expr = expr.map_children(|expr| self.simplify(expr));
+        let span = expr.span;
+        if let Some(folded) = fold_constants(&expr) {
+            return folded;
+        }
+        match &expr.kind {
+            ExprKind::Rational(x) if !self.exact => lit(x.to_f64(), span),
+            ExprKind::Variable(name) => match (self.definitions)(name) {
+                Some(Definition::Value(value @ (Value::Number(_) | Value::Rational(_)))) => {
+                    self.literal(value, span)
+                }
+                _ => expr,
+            },
+            ExprKind::Call { func, args } => match ((self.definitions)(func), args.as_slice()) {
+                (Some(Definition::Builtin(BuiltinFunction::Simplify)), [arg]) => arg.clone(),
+                (Some(Definition::Builtin(BuiltinFunction::Ode)), _) => expr,
+                (Some(Definition::Builtin(builtin)), [arg]) => match literal_value(arg) {
+                    Some(x) => match builtin.apply(&[x]) {
+                        Ok(y @ (Value::Number(_) | Value::Rational(_))) => self.literal(&y, span),
+                        _ => expr,
+                    },
+                    None => expr,
+                },
+                _ => expr,
+            },
+            ExprKind::UnOp { .. } | ExprKind::BinOp { .. } if (self.is_number)(&expr) => {
+                self.normalize(&expr)
+            }
+            ExprKind::UnOp { op, arg } => match (op, &arg.kind) {
+                (UnaryOp::Plus, _) => arg.as_ref().clone(),
+                (
+                    UnaryOp::Negate,
+                    ExprKind::UnOp {
+                        op: UnaryOp::Negate,
+                        arg,
+                    },
+                ) => arg.as_ref().clone(),
+                _ => expr,
+            },
+            ExprKind::BinOp { op, left, right } => {
+                use BinaryOp::*;
+                match (op, left.as_lit(), right.as_lit()) {
+                    (Add | Subtract, _, Some(0.0)) => left.as_ref().clone(),
+                    (Add, Some(0.0), _) => right.as_ref().clone(),
+                    (DotProduct, _, Some(1.0)) | (Divide, _, Some(1.0)) => left.as_ref().clone(),
+                    (DotProduct, Some(1.0), _) => right.as_ref().clone(),
+                    _ => expr,
+                }
+            }
+            _ => expr,
+        }
+    }
+
+    /// Collects a numeric sum into like terms, and each term into a product.
+    fn normalize(&self, expr: &Expr) -> Expr {
+        let mut terms: Vec<Product> = Vec::new();
+        self.collect_terms(expr, &self.number(1.0), &mut terms);
+        // `0 * x` is undefined where `x` is, so only bare zeros are dropped
+        terms.retain(|term| !(term.is_zero() && term.factors.is_empty()));
+        terms.sort_by(|a, b| {
+            let degree = |term: &Product| term.factors.iter().map(|(_, e)| e).sum::<f64>();
+            degree(b)
+                .total_cmp(&degree(a))
+                .then_with(|| compare_factor_lists(&a.factors, &b.factors))
+        });
+
+        let mut result: Option<Expr> = None;
+        for term in terms {
+            result = Some(match result {
+                None => self.product_expr(&term, expr.span),
+                Some(sum) if term.coefficient.as_number().is_ok_and(|c| c < 0.0) => {
+                    let term = Product {
+                        coefficient: negate(&term.coefficient),
+                        ..term
+                    };
+                    Expr::bin_op(BinaryOp::Subtract, sum, self.product_expr(&term, expr.span))
+                }
+                Some(sum) => Expr::bin_op(BinaryOp::Add, sum, self.product_expr(&term, expr.span)),
+            });
+        }
+        result.unwrap_or_else(|| self.literal(&self.number(0.0), expr.span))
+    }
+
+    fn collect_terms(&self, expr: &Expr, sign: &Value, terms: &mut Vec<Product>) {
+        match &expr.kind {
+            ExprKind::BinOp {
+                op: op @ (BinaryOp::Add | BinaryOp::Subtract),
+                left,
+                right,
+            } => {
+                self.collect_terms(left, sign, terms);
+                let sign = match op {
+                    BinaryOp::Subtract => negate(sign),
+                    _ => sign.clone(),
+                };
+                self.collect_terms(right, &sign, terms);
+            }
+            ExprKind::UnOp { op, arg } => {
+                let sign = combine(BinaryOp::DotProduct, sign, &self.unary_factor(*op));
+                self.collect_terms(arg, &sign, terms);
+            }
+            _ => {
+                let mut term = self.product(expr);
+                term.coefficient = combine(BinaryOp::DotProduct, &term.coefficient, sign);
+                match terms.iter_mut().find(|like| like.factors == term.factors) {
+                    Some(like) => {
+                        like.coefficient =
+                            combine(BinaryOp::Add, &like.coefficient, &term.coefficient)
+                    }
+                    None => terms.push(term),
+                }
+            }
+        }
+    }
+
+    fn product(&self, expr: &Expr) -> Product {
+        let mut product = Product {
+            coefficient: self.number(1.0),
+            factors: Vec::new(),
+        };
+        self.collect_factors(expr, 1.0, &mut product);
+        product.factors.retain(|(_, exponent)| *exponent != 0.0);
+        product
+            .factors
+            .sort_by(|(a, _), (b, _)| compare_factors(a, b));
+        product
+    }
+
+    /// Multiplies `product` by `expr^exponent`.
+    fn collect_factors(&self, expr: &Expr, exponent: f64, product: &mut Product) {
+        match &expr.kind {
+            _ if let Some(x) = literal_value(expr) => product.multiply(&x, exponent),
+            ExprKind::BinOp {
+                op: op @ (BinaryOp::DotProduct | BinaryOp::Divide),
+                left,
+                right,
+            } => {
+                self.collect_factors(left, exponent, product);
+                let exponent = if *op == BinaryOp::Divide {
+                    -exponent
+                } else {
+                    exponent
+                };
+                self.collect_factors(right, exponent, product);
+            }
+            ExprKind::UnOp { op, arg } => {
+                product.multiply(&self.unary_factor(*op), exponent);
+                self.collect_factors(arg, exponent, product);
+            }
+            // `(a^b)^n = a^(bn)` and `(ab)^n = a^n b^n` only hold for whole numbers `n`
+            ExprKind::BinOp {
+                op: BinaryOp::Power,
+                left,
+                right,
+            } if let Some(n) = right.as_lit()
+                && n.fract() == 0.0 =>
+            {
+                self.collect_factors(left, exponent * n, product);
+            }
+            ExprKind::BinOp {
+                op: BinaryOp::Power,
+                left,
+                right,
+            } if exponent.fract() == 0.0
+                && let Some(n) = right.as_lit() =>
+            {
+                add_factor(product, left, exponent * n);
+            }
+            // sums are normalized on their own, so only the single term of a sum
+            // needs to be taken apart here
+            _ => match self.normalize_if_sum(expr) {
+                Some(single) => self.collect_factors(&single, exponent, product),
+                None => add_factor(product, expr, exponent),
+            },
+        }
+    }
+
+    /// Normalizes a sum that collapses into a single term.
+    fn normalize_if_sum(&self, expr: &Expr) -> Option<Expr> {
+        let ExprKind::BinOp {
+            op: BinaryOp::Add | BinaryOp::Subtract,
+            ..
+        } = expr.kind
+        else {
+            return None;
+        };
+        let mut terms = Vec::new();
+        self.collect_terms(expr, &self.number(1.0), &mut terms);
+        terms.retain(|term| !(term.is_zero() && term.factors.is_empty()));
+        match terms.as_slice() {
+            [] => Some(self.literal(&self.number(0.0), expr.span)),
+            [term] => Some(self.product_expr(term, expr.span)),
+            _ => None,
+        }
+    }
+
+    /// The number `x`, as an exact fraction in exact mode.
+    fn number(&self, x: f64) -> Value {
+        match self.exact {
+            true => Value::Rational(Rational::from_literal(x)),
+            false => Value::Number(x),
+        }
+    }
+
+    /// A literal of a real number, which stays exact in exact mode.
+    fn literal(&self, value: &Value, span: Span) -> Expr {
+        match value {
+            Value::Rational(x) if self.exact => Expr::new(ExprKind::Rational(x.clone()), span),
+            value => lit(value.as_number().unwrap_or(f64::NAN), span),
+        }
+    }
+
+    /// The number a unary operator multiplies its argument by.
+    fn unary_factor(&self, op: UnaryOp) -> Value {
+        match op {
+            UnaryOp::Negate => self.number(-1.0),
+            UnaryOp::Plus => self.number(1.0),
+            UnaryOp::Degrees => Value::Number(1f64.to_radians()),
+        }
+    }
+
+    fn product_expr(&self, product: &Product, span: Span) -> Expr {
+        let power = |base: &Expr, exponent: f64| match exponent {
+            1.0 => base.clone(),
+            _ => Expr::bin_op(
+                BinaryOp::Power,
+                base.clone(),
+                self.literal(&self.number(exponent), base.span),
+            ),
+        };
+        let multiply = |head: Option<Expr>, factors: Vec<Expr>| {
+            head.into_iter()
+                .chain(factors)
+                .reduce(|product, factor| Expr::bin_op(BinaryOp::DotProduct, product, factor))
+        };
+        let numerator_factors = product
+            .factors
+            .iter()
+            .filter(|(_, exponent)| *exponent > 0.0)
+            .map(|(base, exponent)| power(base, *exponent))
+            .collect::<Vec<_>>();
+        let denominator = multiply(
+            None,
+            product
+                .factors
+                .iter()
+                .filter(|(_, exponent)| *exponent < 0.0)
+                .map(|(base, exponent)| power(base, -exponent))
+                .collect(),
+        );
+
+        let numerator = match product.coefficient.as_number() {
+            Ok(1.0) if !numerator_factors.is_empty() => multiply(None, numerator_factors).unwrap(),
+            Ok(-1.0) if !numerator_factors.is_empty() => {
+                Expr::un_op(UnaryOp::Negate, multiply(None, numerator_factors).unwrap())
+            }
+            _ => multiply(
+                Some(self.literal(&product.coefficient, span)),
+                numerator_factors,
+            )
+            .unwrap(),
+        };
+        match denominator {
+            Some(denominator) => Expr::bin_op(BinaryOp::Divide, numerator, denominator),
+            None => numerator,
+        }
+    }
+}
+
+impl Product {
+    /// Multiplies the coefficient by `x^exponent`, which stays exact for exact `x` and
+    /// whole exponents.
+    fn multiply(&mut self, x: &Value, exponent: f64) {
+        let exponent = match exponent.fract() == 0.0 && exponent.abs() <= MAX_EXACT {
+            true => Value::Rational(Rational::from_integer(exponent as i64)),
+            false => Value::Number(exponent),
+        };
+        let factor = combine(BinaryOp::Power, x, &exponent);
+        self.coefficient = combine(BinaryOp::DotProduct, &self.coefficient, &factor);
+    }
+
+    fn is_zero(&self) -> bool {
+        self.coefficient.as_number().is_ok_and(|c| c == 0.0)
+    }
+}
+
+/// Integers up to this magnitude are exact as floats.
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+/// `left op right` for real coefficients, which is undefined if it is not real.
+fn combine(op: BinaryOp, left: &Value, right: &Value) -> Value {
+    match Value::binary_op(op, left, right) {
+        Ok(value @ (Value::Number(_) | Value::Rational(_))) => value,
+        _ => Value::Number(f64::NAN),
+    }
+}
+
+fn negate(x: &Value) -> Value {
+    Value::unary_op(UnaryOp::Negate, x).unwrap_or(Value::Number(f64::NAN))
+}
+
+/// Multiplies `product` by `base^exponent`, merging it into a power of the same base only
+/// where that holds for every value of the base: `x^2 x^3 = x^5`, but `x / x` is undefined
+/// at 0 and `x^0.5 x^0.5` is not real for negative `x`.
+fn add_factor(product: &mut Product, base: &Expr, exponent: f64) {
+    let mergeable = |other: f64| {
+        exponent.fract() == 0.0 && other.fract() == 0.0 && exponent.signum() == other.signum()
+    };
+    match product
+        .factors
+        .iter_mut()
+        .find(|(other, total)| other == base && mergeable(*total))
+    {
+        Some((_, total)) => *total += exponent,
+        None => product.factors.push((base.clone(), exponent)),
+    }
+}
+
+/// Orders variables before function calls before everything else, then alphabetically.
+fn compare_factors(a: &Expr, b: &Expr) -> Ordering {
+    let rank = |expr: &Expr| match expr.kind {
+        ExprKind::Variable(_) => 0,
+        ExprKind::Call { .. } => 1,
+        _ => 2,
+    };
+    rank(a)
+        .cmp(&rank(b))
+        .then_with(|| a.to_string().cmp(&b.to_string()))
+}
+
+fn compare_factor_lists(a: &[(Expr, f64)], b: &[(Expr, f64)]) -> Ordering {
+    for ((a, _), (b, _)) in std::iter::zip(a, b) {
+        match compare_factors(a, b) {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// An operator applied to literals folded into one literal, if it is a real number.
+/// Exact literals fold into exact ones, like they evaluate, and so do whole numbers
+/// next to them, like those derivatives introduce.
+pub fn fold_constants(expr: &Expr) -> Option<Expr> {
+    let value = match &expr.kind {
+        ExprKind::UnOp { op, arg } => Value::unary_op(*op, &literal_value(arg)?).ok()?,
+        ExprKind::BinOp { op, left, right } => {
+            let (left, right) = match (literal_value(left)?, literal_value(right)?) {
+                (left @ Value::Rational(_), right) | (left, right @ Value::Rational(_)) => {
+                    (exact_integer(left), exact_integer(right))
+                }
+                values => values,
+            };
+            Value::binary_op(*op, &left, &right).ok()?
+        }
+        _ => return None,
+    };
+    match value {
+        Value::Number(x) => Some(lit(x, expr.span)),
+        Value::Rational(x) => Some(Expr::new(ExprKind::Rational(x), expr.span)),
+        _ => None,
+    }
+}
+
+/// The value of a literal, exact if the literal is.
+fn literal_value(expr: &Expr) -> Option<Value> {
+    match &expr.kind {
+        ExprKind::Lit(x) => Some(Value::Number(*x)),
+        ExprKind::Rational(x) => Some(Value::Rational(x.clone())),
+        _ => None,
+    }
+}
+
+/// A whole number as an exact one, and any other value as it is.
+fn exact_integer(value: Value) -> Value {
+    match value {
+        Value::Number(x) if x.fract() == 0.0 && x.abs() <= MAX_EXACT => {
+            Value::Rational(Rational::from_integer(x as i64))
+        }
+        value => value,
+    }
+}
+
+fn lit(x: f64, span: Span) -> Expr {
+    Expr::new(ExprKind::Lit(x), span)
+}
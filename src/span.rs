@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// A byte range in the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Self) -> Self {
+        Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// An error that points at the part of the input that caused it.
+#[derive(Debug)]
+pub struct SpannedError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for SpannedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SpannedError {}
+
+macro_rules! bail_at {
+    ($span:expr, $($arg:tt)*) => {
+        return Err(crate::span::SpannedError {
+            span: $span,
+            message: format!($($arg)*),
+        }
+        .into())
+    };
+}
+pub(crate) use bail_at;
+
+/// Formats an error for the terminal, underlining the offending input if the error has a span.
+pub fn report(source: &str, error: &anyhow::Error) -> String {
+    let Some(SpannedError { span, message }) = error.downcast_ref::<SpannedError>() else {
+        return format!("error: {error}");
+    };
+    let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |i| span.start + i);
+    let line = &source[line_start..line_end];
+    let line_number = source[..span.start].matches('\n').count() + 1;
+    // spans count bytes, but the caret lines up with characters like `°`
+    let column = source[line_start..span.start].chars().count() + 1;
+    let width = source[span.start..span.end.max(span.start).min(line_end)]
+        .chars()
+        .count()
+        .max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+    format!(
+        "error: {message}\n{gutter}--> {line_number}:{column}\n{gutter} |\n{line_number} | {}\n{gutter} | {}{}",
+        line.trim_end(),
+        " ".repeat(column - 1),
+        "^".repeat(width),
+    )
+}
@@ -1,16 +1,24 @@
 use std::collections::HashMap;
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use vello::Scene;
 use vello::kurbo::*;
 use vello::peniko::*;
 use winit::dpi::PhysicalPosition;
 use winit::event::ElementState;
 use winit::event::MouseScrollDelta;
+use winit::keyboard::Key;
 
+use crate::complex::Complex;
+use crate::format::Precision;
+use crate::ode::Solver;
 use crate::parse::Expr;
 use crate::parse::Ident;
+use crate::roots::Brent;
+use crate::run::DifferentialEquation;
 use crate::run::Interpreter;
+use crate::units::Dimension;
+use crate::value::Value;
 
 #[derive(Debug, Clone)]
 pub struct Viewport {
@@ -22,6 +30,49 @@ pub struct Viewport {
 pub struct Graph {
     viewport: Viewport,
     pub single_var_functions: Vec<(Color, Ident, Expr)>,
+    pub slope_fields: Vec<(Color, [Ident; 2], Expr)>,
+    pub vector_fields: Vec<(Color, [Ident; 2], Expr)>,
+    pub differential_equations: Vec<(Color, DifferentialEquation, Vec<Point>)>,
+    pub points: Vec<(Color, Vec<Point>)>,
+    /// Sequences, drawn as a dot at each integer index.
+    pub sequences: Vec<(Color, Ident)>,
+    /// Complex functions `f(z)`, one at a time drawn with domain coloring beneath the grid.
+    pub complex_functions: Vec<(Ident, Expr)>,
+    /// The index of the complex function drawn, which `z` moves on to the next.
+    pub domain_coloring: usize,
+    /// Shade rings where the modulus of the domain colored function crosses a power of two.
+    pub show_modulus_contours: bool,
+    /// Color field arrows by their magnitude instead of scaling their length.
+    pub color_fields_by_magnitude: bool,
+    /// Mark the roots, local extrema and intersections of the single variable functions.
+    pub show_markers: bool,
+}
+
+/// A point of interest on the graphed functions, whose coordinates are shown when it is clicked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    pub point: Point,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Root,
+    Minimum,
+    Maximum,
+    Intersection,
+}
+
+impl std::fmt::Display for MarkerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Root => "root",
+            Self::Minimum => "local minimum",
+            Self::Maximum => "local maximum",
+            Self::Intersection => "intersection",
+        })
+    }
 }
 
 struct ClickStartState {
@@ -33,25 +84,87 @@ pub struct State {
     pub graph: Graph,
     interpreter: Interpreter,
     sampled_functions: Vec<(Color, BezPath)>,
+    /// The distinct dimensions of the sampled functions that have units, which label the y axis.
+    function_dimensions: Vec<Dimension>,
+    sampled_fields: Vec<(Color, BezPath)>,
+    sampled_trajectories: Vec<(Color, BezPath)>,
+    /// The terms of each sequence in view, as points on the graph.
+    sampled_sequences: Vec<(Color, Vec<Point>)>,
+    markers: Vec<Marker>,
+    /// The marker last clicked, whose coordinates are part of the status.
+    selected_marker: Option<Marker>,
+    domain_coloring_image: Option<Image>,
     cursor: Point,
     click_start: Option<ClickStartState>,
     window_size: Vec2,
+    /// How many of the interpreter's warnings have been printed.
+    reported_warnings: usize,
 }
 
+const COLORS: [Color; 5] = [
+    Color::from_rgb8(199, 68, 64),
+    Color::from_rgb8(45, 112, 179),
+    Color::from_rgb8(52, 133, 67),
+    Color::from_rgb8(96, 66, 166),
+    Color::from_rgb8(0, 0, 0),
+];
+
+/// Intersections belong to two functions, so they are marked in a neutral color.
+const INTERSECTION_COLOR: Color = Color::from_rgb8(90, 90, 90);
+
+/// How far in pixels from a marker a click still selects it.
+const MARKER_CLICK_RADIUS: f64 = 10.0;
+
+/// Approximate distance in pixels between neighbouring arrows of a field.
+const FIELD_SPACING: f64 = 40.0;
+
+/// How many terms past its first base case a sequence is plotted for.
+const MAX_PLOTTED_TERMS: i64 = 10_000;
+
+/// How close in pixels a trajectory has to come back to its start to count as a closed orbit.
+const ORBIT_CLOSING_DISTANCE: f64 = 3.0;
+
+/// Size in screen pixels of each sample of a domain coloring plot.
+const DOMAIN_COLORING_PIXEL_SIZE: f64 = 2.0;
+
 impl State {
     pub fn new(interpreter: Interpreter) -> Self {
-        let colors = [
-            Color::from_rgb8(199, 68, 64),
-            Color::from_rgb8(45, 112, 179),
-            Color::from_rgb8(52, 133, 67),
-            Color::from_rgb8(96, 66, 166),
-            Color::from_rgb8(0, 0, 0),
-        ];
+        let mut colors = COLORS.iter().copied().cycle();
         let mut single_var_functions = Vec::new();
-        for (i, (arg, body)) in interpreter.single_var_functions.iter().enumerate() {
-            single_var_functions.push((colors[i % colors.len()], arg.clone(), body.clone()));
+        for (arg, body) in interpreter.single_var_functions.iter() {
+            single_var_functions.push((colors.next().unwrap(), arg.clone(), body.clone()));
+        }
+        let mut slope_fields = Vec::new();
+        for (args, body) in interpreter.slope_fields.iter() {
+            slope_fields.push((colors.next().unwrap(), args.clone(), body.clone()));
+        }
+        let mut vector_fields = Vec::new();
+        for (args, body) in interpreter.vector_fields.iter() {
+            vector_fields.push((colors.next().unwrap(), args.clone(), body.clone()));
+        }
+        let mut differential_equations = Vec::new();
+        for (equation, initial_points) in interpreter.differential_equations.iter() {
+            let initial_points = initial_points.iter().map(|&p| Point::from(p)).collect();
+            differential_equations.push((colors.next().unwrap(), equation.clone(), initial_points));
+        }
+        let mut points = Vec::new();
+        for (_, value, _) in interpreter.constants.iter() {
+            if let Some(value_points) = value.points() {
+                let value_points = value_points.into_iter().map(Point::from).collect();
+                points.push((colors.next().unwrap(), value_points));
+            }
+        }
+        for data in interpreter.fitted_data.iter() {
+            let data = data.iter().map(|&p| Point::from(p)).collect();
+            points.push((colors.next().unwrap(), data));
+        }
+        let mut sequences = Vec::new();
+        for name in interpreter.sequences.iter() {
+            sequences.push((colors.next().unwrap(), name.clone()));
         }
 
+        // the warnings so far are printed along with the constants
+        let reported_warnings = interpreter.warnings().len();
         Self {
             graph: Graph {
                 viewport: Viewport {
@@ -59,17 +172,56 @@ impl State {
                     width: 20.0,
                 },
                 single_var_functions,
+                slope_fields,
+                vector_fields,
+                differential_equations,
+                points,
+                sequences,
+                complex_functions: interpreter.complex_functions.clone(),
+                domain_coloring: interpreter.complex_functions.len().saturating_sub(1),
+                show_modulus_contours: true,
+                color_fields_by_magnitude: false,
+                show_markers: true,
             },
             interpreter,
             sampled_functions: Vec::new(),
+            function_dimensions: Vec::new(),
+            sampled_fields: Vec::new(),
+            sampled_trajectories: Vec::new(),
+            sampled_sequences: Vec::new(),
+            markers: Vec::new(),
+            selected_marker: None,
+            domain_coloring_image: None,
             window_size: Vec2::ZERO,
             cursor: Point::ZERO,
             click_start: None,
+            reported_warnings,
+        }
+    }
+
+    pub fn sample(&mut self) -> Result<()> {
+        self.sample_functions()?;
+        self.sample_fields()?;
+        self.sample_trajectories()?;
+        self.sample_sequences()?;
+        self.sample_domain_coloring()?;
+        self.report_warnings();
+        Ok(())
+    }
+
+    /// Prints the warnings given since the last report, like those of integrals in
+    /// plotted functions that did not converge.
+    fn report_warnings(&mut self) {
+        let warnings = self.interpreter.warnings();
+        for warning in &warnings[self.reported_warnings..] {
+            eprintln!("warning: {warning}");
         }
+        self.reported_warnings = warnings.len();
     }
 
     pub fn sample_functions(&mut self) -> Result<()> {
         self.sampled_functions.clear();
+        self.function_dimensions.clear();
         if let Some(n) = self
             .graph
             .single_var_functions
@@ -88,36 +240,399 @@ impl State {
         };
         let n = (self.window_size.x / 5.0).round() as u32;
 
+        // the values are kept to search for markers in
+        let mut samples = Vec::new();
         for (color, arg, body) in self.graph.single_var_functions.iter() {
             let mut arg_map = HashMap::new();
-            arg_map.insert(arg.clone(), 0.0);
+            let mut float_arg_map = HashMap::new();
+            let mut values = Vec::new();
             let mut points = Vec::new();
             for i in 0..n {
                 let x = {
                     let t = i as f64 / (n - 1) as f64;
                     xmin * (1.0 - t) + xmax * t
                 };
-                arg_map.insert(arg.clone(), x);
-                let y = self.interpreter.evaluate(body, &arg_map)?;
-                let point = Point { x, y } - self.graph.viewport.pos;
-                let point = point * self.window_size.x / self.graph.viewport.width;
-                let point = Affine::FLIP_Y * point.to_point();
-                points.push(point + self.window_size / 2.0);
+                // plain floats are cheaper, and values are only needed for the units of
+                // the first sample and where floats fail, like where the result is complex
+                float_arg_map.insert(arg.clone(), x);
+                let y = match (i, self.interpreter.evaluate_as(body, &float_arg_map)) {
+                    (1.., Ok(y)) => y,
+                    _ => {
+                        arg_map.insert(arg.clone(), Value::Number(x));
+                        let y = self.interpreter.evaluate(body, &arg_map)?;
+                        let dimension = y.dimension();
+                        if !dimension.is_none() && !self.function_dimensions.contains(&dimension) {
+                            self.function_dimensions.push(dimension);
+                        }
+                        y.as_sample()?
+                    }
+                };
+                values.push((x, y));
+                points.push(self.to_screen(Point { x, y }));
             }
+            samples.push(values);
+            // undefined values, including non-real complex ones, leave a gap in the curve
             let mut path = BezPath::new();
-            path.move_to(points[0]);
-            for point in &points[1..] {
-                path.line_to(*point);
+            let mut pen_down = false;
+            for point in points {
+                match (point.is_finite(), pen_down) {
+                    (true, true) => path.line_to(point),
+                    (true, false) => path.move_to(point),
+                    (false, _) => {}
+                }
+                pen_down = point.is_finite();
             }
             self.sampled_functions.push((*color, path));
         }
 
+        self.markers = match self.graph.show_markers {
+            true => self.find_markers(&samples)?,
+            false => Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Finds the roots, local extrema and pairwise intersections of the single variable
+    /// functions, bracketing them with the samples of each function and refining them
+    /// with Brent's method. Extrema are the roots of the exact derivative, evaluated
+    /// over dual numbers.
+    fn find_markers(&self, samples: &[Vec<(f64, f64)>]) -> Result<Vec<Marker>> {
+        let functions = &self.graph.single_var_functions;
+        // a function has no markers where it fails to evaluate, just like where it is
+        // undefined, so that an error doesn't take the curves down with it
+        let evaluate = |i: usize, x: f64| {
+            let (_, arg, body) = &functions[i];
+            let arg_map = HashMap::from([(arg.clone(), Value::Number(x))]);
+            let y = self.interpreter.evaluate(body, &arg_map);
+            y.and_then(|y| y.as_sample()).unwrap_or(f64::NAN)
+        };
+        let slope = |i: usize, x: f64| {
+            let (_, arg, body) = &functions[i];
+            let slope = self.interpreter.evaluate_with_derivative(body, arg, x);
+            slope.map_or(f64::NAN, |(_, slope)| slope)
+        };
+        let brent = Brent::default();
+        let mut markers = Vec::new();
+        for (i, ((color, ..), samples)) in std::iter::zip(functions, samples).enumerate() {
+            let marker = |kind, x, y| Marker {
+                kind,
+                point: Point::new(x, y),
+                color: *color,
+            };
+            for x in brent.roots(|x| Ok(evaluate(i, x)), samples)? {
+                markers.push(marker(MarkerKind::Root, x, 0.0));
+            }
+            let slopes: Vec<_> = samples.iter().map(|&(x, _)| (x, slope(i, x))).collect();
+            if slopes.iter().all(|(_, slope)| slope.is_nan()) {
+                // without a slope, like through a numerical integral, extrema are refined
+                // from the samples instead
+                for (x, y) in brent.minima(|x| Ok(evaluate(i, x)), samples)? {
+                    markers.push(marker(MarkerKind::Minimum, x, y));
+                }
+                // maxima are the minima of the negated function
+                let negated: Vec<_> = samples.iter().map(|&(x, y)| (x, -y)).collect();
+                for (x, y) in brent.minima(|x| Ok(-evaluate(i, x)), &negated)? {
+                    markers.push(marker(MarkerKind::Maximum, x, -y));
+                }
+                continue;
+            }
+            for x in brent.roots(|x| Ok(slope(i, x)), &slopes)? {
+                // the slope falls to zero at a maximum and rises from it at a minimum,
+                // and keeps its sign at a saddle point
+                let before = slopes.iter().rev().find(|&&(sample, _)| sample < x);
+                let after = slopes.iter().find(|&&(sample, _)| sample > x);
+                let (Some(&(_, before)), Some(&(_, after))) = (before, after) else {
+                    continue;
+                };
+                let kind = if before < 0.0 && after > 0.0 {
+                    MarkerKind::Minimum
+                } else if before > 0.0 && after < 0.0 {
+                    MarkerKind::Maximum
+                } else {
+                    continue;
+                };
+                markers.push(marker(kind, x, evaluate(i, x)));
+            }
+        }
+        for i in 0..functions.len() {
+            for j in i + 1..functions.len() {
+                // all functions are sampled at the same points
+                let difference: Vec<_> = std::iter::zip(&samples[i], &samples[j])
+                    .map(|(&(x, y), &(_, z))| (x, y - z))
+                    .collect();
+                let roots = brent.roots(|x| Ok(evaluate(i, x) - evaluate(j, x)), &difference)?;
+                for x in roots {
+                    markers.push(Marker {
+                        kind: MarkerKind::Intersection,
+                        point: Point::new(x, evaluate(i, x)),
+                        color: INTERSECTION_COLOR,
+                    });
+                }
+            }
+        }
+        Ok(markers)
+    }
+
+    pub fn sample_fields(&mut self) -> Result<()> {
+        self.sampled_fields.clear();
+        if self.window_size.x <= 0.0 {
+            return Ok(());
+        }
+
+        // snap the arrow spacing to 1, 2 or 5 times a power of ten so that
+        // arrows sit on round coordinates and stay roughly evenly spaced when zooming
+        let scale = self.window_size.x / self.graph.viewport.width;
+        let spacing = {
+            let raw = FIELD_SPACING / scale;
+            let magnitude = 10f64.powf(raw.log10().floor());
+            let mantissa = [1.0, 2.0, 5.0, 10.0]
+                .into_iter()
+                .find(|m| m * magnitude >= raw)
+                .unwrap();
+            mantissa * magnitude
+        };
+        let min = self.to_viewport(Point::new(0.0, self.window_size.y));
+        let max = self.to_viewport(Point::new(self.window_size.x, 0.0));
+        let grid_points: Vec<Point> = {
+            let (i_min, i_max) = ((min.x / spacing).ceil(), (max.x / spacing).floor());
+            let (j_min, j_max) = ((min.y / spacing).ceil(), (max.y / spacing).floor());
+            let (i_min, i_max, j_min, j_max) =
+                (i_min as i64, i_max as i64, j_min as i64, j_max as i64);
+            (i_min..=i_max)
+                .flat_map(|i| (j_min..=j_max).map(move |j| (i, j)))
+                .map(|(i, j)| Point::new(i as f64 * spacing, j as f64 * spacing))
+                .collect()
+        };
+
+        // each field is sampled as a list of (grid point, direction) pairs first,
+        // so that arrow lengths and colors can be normalized by the largest magnitude
+        let mut fields = Vec::new();
+        for (color, [x_arg, y_arg], body) in self.graph.slope_fields.iter() {
+            let mut arg_map = HashMap::new();
+            let mut samples = Vec::new();
+            for &point in &grid_points {
+                arg_map.insert(x_arg.clone(), Value::Number(point.x));
+                arg_map.insert(y_arg.clone(), Value::Number(point.y));
+                let slope = self.interpreter.evaluate(body, &arg_map)?.as_sample()?;
+                let direction = if slope.is_nan() {
+                    continue;
+                } else if slope.is_infinite() {
+                    Vec2::new(0.0, 1.0)
+                } else {
+                    Vec2::new(1.0, slope)
+                };
+                samples.push((point, direction));
+            }
+            fields.push((*color, false, samples));
+        }
+        for (color, [x_arg, y_arg], body) in self.graph.vector_fields.iter() {
+            let mut arg_map = HashMap::new();
+            let mut samples = Vec::new();
+            for &point in &grid_points {
+                arg_map.insert(x_arg.clone(), Value::Number(point.x));
+                arg_map.insert(y_arg.clone(), Value::Number(point.y));
+                let direction = match self.interpreter.evaluate(body, &arg_map)? {
+                    Value::Point(dx, dy) => Vec2::new(dx, dy),
+                    value => bail!("Vector field returned a {}", value.type_name()),
+                };
+                if direction.is_finite() {
+                    samples.push((point, direction));
+                }
+            }
+            fields.push((*color, true, samples));
+        }
+
+        for (color, is_vector_field, samples) in fields {
+            let max_magnitude = samples
+                .iter()
+                .map(|(_, direction)| direction.length())
+                .fold(0.0, f64::max);
+            for (point, direction) in samples {
+                let magnitude = direction.length();
+                if magnitude == 0.0 {
+                    continue;
+                }
+                let relative = magnitude / max_magnitude;
+                let color = match self.graph.color_fields_by_magnitude {
+                    true => magnitude_color(relative),
+                    false => color,
+                };
+                let length = match is_vector_field && !self.graph.color_fields_by_magnitude {
+                    true => 0.85 * relative,
+                    false => 0.7,
+                } * spacing
+                    * scale;
+                let direction = Affine::FLIP_Y * (direction / magnitude * length / 2.0).to_point();
+                let center = self.to_screen(point);
+                let (start, end) = (center - direction.to_vec2(), center + direction.to_vec2());
+
+                let mut path = BezPath::new();
+                path.move_to(start);
+                path.line_to(end);
+                if is_vector_field {
+                    let head = (start - end).normalize() * (length * 0.3).min(8.0);
+                    path.move_to(end + (Affine::rotate(0.45) * head.to_point()).to_vec2());
+                    path.line_to(end);
+                    path.line_to(end + (Affine::rotate(-0.45) * head.to_point()).to_vec2());
+                }
+                self.sampled_fields.push((color, path));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn sample_trajectories(&mut self) -> Result<()> {
+        self.sampled_trajectories.clear();
+        if self.window_size.x <= 0.0 {
+            return Ok(());
+        }
+
+        // trajectories are followed slightly past the edges of the window so that
+        // they don't visibly end inside of it
+        let view = Rect::from_points(
+            self.to_viewport(Point::ZERO),
+            self.to_viewport(self.window_size.to_point()),
+        )
+        .inflate(
+            self.graph.viewport.width * 0.05,
+            self.graph.viewport.width * 0.05,
+        );
+        let solver = Solver {
+            relative_tolerance: 1e-6,
+            absolute_tolerance: 1e-9,
+            max_step: self.graph.viewport.width / 200.0,
+            max_steps: 5_000,
+        };
+        let closing_distance =
+            ORBIT_CLOSING_DISTANCE * self.graph.viewport.width / self.window_size.x;
+
+        for (color, equation, initial_points) in self.graph.differential_equations.iter() {
+            for &start in initial_points {
+                let t0 = match equation {
+                    DifferentialEquation::Scalar { .. } => start.x,
+                    DifferentialEquation::System { .. } => 0.0,
+                };
+                let mut trajectory = Vec::new();
+                for t_end in [f64::NEG_INFINITY, f64::INFINITY] {
+                    // closed orbits are stopped once they come back around to the start
+                    let mut left_start = false;
+                    let mut previous = start;
+                    let points = solver.integrate(
+                        |t, y| self.interpreter.evaluate_derivative(equation, t, y),
+                        t0,
+                        [start.x, start.y],
+                        t_end,
+                        |_, &[x, y]| {
+                            let point = Point::new(x, y);
+                            // a step can pass by the start between two points
+                            let step = Line::new(previous, point);
+                            let distance = step.nearest(start, 1e-9).distance_sq.sqrt();
+                            let returned = left_start && distance < closing_distance;
+                            left_start |= start.distance(point) > self.graph.viewport.width / 50.0;
+                            previous = point;
+                            returned || !view.contains(point)
+                        },
+                    )?;
+                    let points = points.into_iter().map(|(_, [x, y])| Point::new(x, y));
+                    if t_end < t0 {
+                        trajectory.extend(points.rev());
+                    } else {
+                        trajectory.extend(points.skip(1));
+                    }
+                }
+
+                let mut path = BezPath::new();
+                path.move_to(self.to_screen(trajectory[0]));
+                for &point in &trajectory[1..] {
+                    path.line_to(self.to_screen(point));
+                }
+                self.sampled_trajectories.push((*color, path));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Computes the terms of the sequences at the integers in view. When zoomed far
+    /// out, only every so many terms are kept so that the dots don't overlap.
+    pub fn sample_sequences(&mut self) -> Result<()> {
+        self.sampled_sequences.clear();
+        if self.window_size.x <= 0.0 {
+            return Ok(());
+        }
+
+        let min = self.to_viewport(Point::ZERO);
+        let max = self.to_viewport(self.window_size.to_point());
+        let step = (8.0 * self.graph.viewport.width / self.window_size.x).ceil() as i64;
+        let step = step.max(1);
+        for (color, name) in self.graph.sequences.iter() {
+            let Some(first) = self.interpreter.first_index(name) else {
+                continue;
+            };
+            // multiples of the step past the first term, so that the same terms stay in
+            // view while panning
+            let start = first.max(min.x.ceil() as i64);
+            let mut n = start + (first - start).rem_euclid(step);
+            let mut points = Vec::new();
+            while n as f64 <= max.x && n - first <= MAX_PLOTTED_TERMS {
+                // terms that can't be computed leave a gap, like undefined values of functions
+                if let Ok(term) = self.interpreter.call(name, &[Value::Number(n as f64)]) {
+                    let point = Point::new(n as f64, term.as_sample()?);
+                    if point.is_finite() {
+                        points.push(point);
+                    }
+                }
+                n += step;
+            }
+            self.sampled_sequences.push((*color, points));
+        }
+
+        Ok(())
+    }
+
+    pub fn sample_domain_coloring(&mut self) -> Result<()> {
+        self.domain_coloring_image = None;
+        let Some((arg, body)) = self.graph.complex_functions.get(self.graph.domain_coloring) else {
+            return Ok(());
+        };
+        let width = (self.window_size.x / DOMAIN_COLORING_PIXEL_SIZE).ceil() as u32;
+        let height = (self.window_size.y / DOMAIN_COLORING_PIXEL_SIZE).ceil() as u32;
+
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        let mut arg_map = HashMap::new();
+        for j in 0..height {
+            for i in 0..width {
+                let pixel = (Vec2::new(i as f64 + 0.5, j as f64 + 0.5)
+                    * DOMAIN_COLORING_PIXEL_SIZE)
+                    .to_point();
+                let z = self.to_viewport(pixel);
+                arg_map.insert(arg.clone(), Value::complex(Complex::new(z.x, z.y)));
+                let value = self.interpreter.evaluate(body, &arg_map)?;
+                let Some(w) = value.as_complex() else {
+                    bail!("Complex function returned a {}", value.type_name());
+                };
+                data.extend(domain_color(w, self.graph.show_modulus_contours));
+            }
+        }
+        self.domain_coloring_image = Some(Image::new(
+            Blob::from(data),
+            ImageFormat::Rgba8,
+            width,
+            height,
+        ));
+
         Ok(())
     }
 
     pub fn render(&self, scene: &mut Scene, width: u32, height: u32) {
         const ID: Affine = Affine::IDENTITY;
 
+        // draw domain coloring
+        if let Some(image) = &self.domain_coloring_image {
+            scene.draw_image(image, Affine::scale(DOMAIN_COLORING_PIXEL_SIZE));
+        }
+
         // draw background
         let stroke = Stroke::new(1.5);
         let color = Color::BLACK;
@@ -138,6 +653,99 @@ impl State {
         for (color, path) in self.sampled_functions.iter() {
             scene.stroke(&stroke, ID, color, None, path);
         }
+
+        // draw solutions of differential equations, with a dot at their initial points
+        let stroke = Stroke::new(3.0);
+        for (color, path) in self.sampled_trajectories.iter() {
+            scene.stroke(&stroke, ID, color, None, path);
+        }
+        for (color, _, initial_points) in self.graph.differential_equations.iter() {
+            for &point in initial_points {
+                let dot = Circle::new(self.to_screen(point), 5.0);
+                scene.fill(Fill::NonZero, ID, color, None, &dot);
+            }
+        }
+
+        // draw slope and vector fields
+        let stroke = Stroke::new(2.0).with_caps(Cap::Round);
+        for (color, path) in self.sampled_fields.iter() {
+            scene.stroke(&stroke, ID, color, None, path);
+        }
+
+        // draw sequences
+        for (color, points) in self.sampled_sequences.iter() {
+            for &point in points {
+                let dot = Circle::new(self.to_screen(point), 4.0);
+                scene.fill(Fill::NonZero, ID, color, None, &dot);
+            }
+        }
+
+        // draw points
+        for (color, points) in self.graph.points.iter() {
+            for &point in points {
+                let marker = Circle::new(self.to_screen(point), 6.0);
+                scene.fill(Fill::NonZero, ID, color, None, &marker);
+                scene.stroke(&Stroke::new(1.5), ID, Color::WHITE, None, &marker);
+            }
+        }
+
+        // draw markers as hollow points, filling in the selected one
+        for marker in self.markers.iter() {
+            let circle = Circle::new(self.to_screen(marker.point), 5.0);
+            scene.fill(Fill::NonZero, ID, Color::WHITE, None, &circle);
+            scene.stroke(&Stroke::new(2.5), ID, marker.color, None, &circle);
+        }
+        if let Some(marker) = &self.selected_marker {
+            let circle = Circle::new(self.to_screen(marker.point), 7.0);
+            scene.fill(Fill::NonZero, ID, marker.color, None, &circle);
+            scene.stroke(&Stroke::new(1.5), ID, Color::WHITE, None, &circle);
+        }
+    }
+
+    /// The position of the cursor on the graph, in the format of the input file,
+    /// the units of the plotted functions and the unit of angles.
+    pub fn status(&self) -> String {
+        let settings = &self.interpreter.settings;
+        let mut format = settings.format;
+        // by default, show as many decimals as a pixel can resolve
+        if format.precision.is_none() {
+            let pixel_size = self.graph.viewport.width / self.window_size.x;
+            let decimals = (-pixel_size.log10()).ceil().max(0.0) as usize;
+            format.precision = Some(Precision::Decimals(decimals));
+        }
+        let cursor = self.to_viewport(self.cursor);
+        let (x, y) = (format.number(cursor.x), format.number(cursor.y));
+        let mut status = format!("({x}, {y})");
+        if let Some(Marker { kind, point, .. }) = &self.selected_marker {
+            // markers are exact up to rounding, so they are shown in the format of the input file
+            let format = settings.format;
+            let (x, y) = (format.number(point.x), format.number(point.y));
+            status += &format!(" — {kind} at ({x}, {y})");
+        }
+        if self.graph.complex_functions.len() > 1 {
+            let (i, n) = (
+                self.graph.domain_coloring + 1,
+                self.graph.complex_functions.len(),
+            );
+            status += &format!(" — complex function {i} of {n}, z for the next");
+        }
+        if !self.function_dimensions.is_empty() {
+            let dimensions = self.function_dimensions.iter().map(|d| d.to_string());
+            status += &format!(" — y in {}", dimensions.collect::<Vec<_>>().join(", "));
+        }
+        status + &format!(" — angles in {}", settings.angle_unit)
+    }
+
+    fn to_screen(&self, point: Point) -> Point {
+        let point = point - self.graph.viewport.pos;
+        let point = point * self.window_size.x / self.graph.viewport.width;
+        let point = Affine::FLIP_Y * point.to_point();
+        point + self.window_size / 2.0
+    }
+    fn to_viewport(&self, point: Point) -> Point {
+        let point = Affine::FLIP_Y * (point - self.window_size / 2.0);
+        let point = point.to_vec2() * self.graph.viewport.width / self.window_size.x;
+        self.graph.viewport.pos + point
     }
 
     fn horizontal_line(&self, x: f64) -> Line {
@@ -154,7 +762,7 @@ impl State {
 
     pub fn set_window_size(&mut self, width: u32, height: u32) {
         self.window_size = Vec2::new(width as f64, height as f64);
-        self.sample_functions();
+        self.sample();
     }
 
     pub fn handle_cursor_move(&mut self, pos: PhysicalPosition<f64>) {
@@ -164,11 +772,29 @@ impl State {
             offset *= self.graph.viewport.width / self.window_size.x;
             offset.x = -offset.x;
             self.graph.viewport.pos = click_start.viewport_pos + offset;
-            self.sample_functions();
+            self.sample();
         }
     }
 
+    /// Selects the marker under the cursor when pressing the mouse, or otherwise
+    /// starts dragging the graph.
     pub fn handle_mouse_input(&mut self, mouse_state: ElementState) {
+        if let ElementState::Pressed = mouse_state
+            && let Some(marker) = self
+                .markers
+                .iter()
+                .map(|marker| (marker, self.to_screen(marker.point).distance(self.cursor)))
+                .filter(|&(_, distance)| distance <= MARKER_CLICK_RADIUS)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(marker, _)| *marker)
+        {
+            // clicking the selected marker again deselects it
+            self.selected_marker = match self.selected_marker == Some(marker) {
+                true => None,
+                false => Some(marker),
+            };
+            return;
+        }
         self.click_start = match mouse_state {
             ElementState::Pressed => Some(ClickStartState {
                 cursor: self.cursor,
@@ -193,6 +819,77 @@ impl State {
         if let Some(ClickStartState { viewport_pos, .. }) = &mut self.click_start {
             *viewport_pos = cursor + scale * (*viewport_pos - cursor);
         }
-        self.sample_functions();
+        self.sample();
     }
+
+    pub fn handle_key_press(&mut self, key: Key) {
+        match key {
+            Key::Character(c) if c == "m" => {
+                self.graph.color_fields_by_magnitude = !self.graph.color_fields_by_magnitude;
+                self.sample_fields();
+            }
+            Key::Character(c) if c == "p" => {
+                self.graph.show_markers = !self.graph.show_markers;
+                self.selected_marker = None;
+                self.sample_functions();
+            }
+            Key::Character(c) if c == "c" => {
+                self.graph.show_modulus_contours = !self.graph.show_modulus_contours;
+                self.sample_domain_coloring();
+            }
+            Key::Character(c) if c == "z" && !self.graph.complex_functions.is_empty() => {
+                self.graph.domain_coloring =
+                    (self.graph.domain_coloring + 1) % self.graph.complex_functions.len();
+                self.sample_domain_coloring();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Maps a magnitude relative to the largest one in view onto a blue to red gradient.
+fn magnitude_color(t: f64) -> Color {
+    const STOPS: [[f32; 3]; 4] = [
+        [45.0, 112.0, 179.0],
+        [52.0, 133.0, 67.0],
+        [230.0, 160.0, 30.0],
+        [199.0, 68.0, 64.0],
+    ];
+    let t = t.clamp(0.0, 1.0) as f32 * (STOPS.len() - 1) as f32;
+    let i = (t.floor() as usize).min(STOPS.len() - 2);
+    let frac = t - i as f32;
+    let [r, g, b] = std::array::from_fn(|c| {
+        (STOPS[i][c] * (1.0 - frac) + STOPS[i + 1][c] * frac).round() as u8
+    });
+    Color::from_rgb8(r, g, b)
+}
+
+/// Colors a complex number with its argument as the hue, going from black at zero
+/// to white at infinity.
+fn domain_color(w: Complex, modulus_contours: bool) -> [u8; 4] {
+    let modulus = w.abs();
+    if modulus.is_nan() {
+        return [0; 4];
+    }
+    let hue = (w.arg() / std::f64::consts::TAU).rem_euclid(1.0);
+    let mut lightness = std::f64::consts::FRAC_2_PI * modulus.atan();
+    if modulus_contours && modulus.is_finite() && modulus > 0.0 {
+        lightness *= 0.75 + 0.25 * modulus.log2().rem_euclid(1.0);
+    }
+
+    // convert from HSL with full saturation
+    let chroma = 1.0 - (2.0 * lightness - 1.0).abs();
+    let sector = hue * 6.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let [r, g, b] = [r, g, b].map(|c| ((c + m) * 255.0).round() as u8);
+    [r, g, b, 255]
 }
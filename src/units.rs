@@ -0,0 +1,257 @@
+use std::fmt;
+
+use anyhow::{Result, bail};
+use ecow::EcoString;
+
+/// The SI base units, in the order their exponents are stored in a `Dimension`.
+const BASE_UNITS: [&str; 7] = ["kg", "m", "s", "A", "K", "mol", "cd"];
+
+/// Exponents of the SI base units, like `[0, 1, -1, 0, 0, 0, 0]` for a speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension([i8; 7]);
+
+impl Dimension {
+    pub fn is_none(self) -> bool {
+        self == Self::default()
+    }
+
+    pub fn mul(self, other: Self) -> Self {
+        Self(std::array::from_fn(|i| {
+            self.0[i].saturating_add(other.0[i])
+        }))
+    }
+
+    pub fn div(self, other: Self) -> Self {
+        Self(std::array::from_fn(|i| {
+            self.0[i].saturating_sub(other.0[i])
+        }))
+    }
+
+    /// The `n`th power, if the exponents stay small enough to store.
+    pub fn pow(self, n: i8) -> Option<Self> {
+        let mut exponents = self.0;
+        for exponent in exponents.iter_mut() {
+            *exponent = exponent.checked_mul(n)?;
+        }
+        Some(Self(exponents))
+    }
+
+    /// The `n`th root, if every exponent is a multiple of `n`.
+    pub fn root(self, n: i8) -> Option<Self> {
+        self.0
+            .iter()
+            .all(|exponent| exponent % n == 0)
+            .then(|| Self(self.0.map(|exponent| exponent / n)))
+    }
+}
+
+/// Derived units that results are displayed in when their dimension matches exactly.
+const DERIVED_UNITS: [&str; 7] = ["N", "J", "W", "Pa", "C", "V", "ohm"];
+
+/// Displays the dimension in SI units, like `kg*m/s^2` or the derived `N`.
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = DERIVED_UNITS
+            .into_iter()
+            .find(|name| Unit::named(name).is_some_and(|unit| unit.dimension == *self))
+        {
+            return f.write_str(name);
+        }
+        let factors = |sign: i8| {
+            let factors = std::iter::zip(BASE_UNITS, self.0)
+                .filter(|(_, exponent)| exponent.signum() == sign)
+                .map(|(unit, exponent)| match exponent.abs() {
+                    1 => unit.to_string(),
+                    n => format!("{unit}^{n}"),
+                });
+            factors.collect::<Vec<_>>()
+        };
+        let (numerator, denominator) = (factors(1), factors(-1));
+        if numerator.is_empty() {
+            // `s^-1` rather than `1/s`, which reads oddly after a number
+            let factors = std::iter::zip(BASE_UNITS, self.0)
+                .filter(|(_, exponent)| *exponent < 0)
+                .map(|(unit, exponent)| format!("{unit}^{exponent}"));
+            return f.write_str(&factors.collect::<Vec<_>>().join("*"));
+        }
+        f.write_str(&numerator.join("*"))?;
+        match denominator.as_slice() {
+            [] => Ok(()),
+            [factor] => write!(f, "/{factor}"),
+            factors => write!(f, "/({})", factors.join("*")),
+        }
+    }
+}
+
+/// `(name, size in SI units, exponents of kg, m, s, A, K, mol, cd)`.
+///
+/// `in` and `t` are left out, as they would read ambiguously after a number.
+#[rustfmt::skip]
+const UNITS: &[(&str, f64, [i8; 7])] = &[
+    ("kg", 1.0, [1, 0, 0, 0, 0, 0, 0]),
+    ("g", 1e-3, [1, 0, 0, 0, 0, 0, 0]),
+    ("mg", 1e-6, [1, 0, 0, 0, 0, 0, 0]),
+    ("lb", 0.45359237, [1, 0, 0, 0, 0, 0, 0]),
+    ("m", 1.0, [0, 1, 0, 0, 0, 0, 0]),
+    ("km", 1e3, [0, 1, 0, 0, 0, 0, 0]),
+    ("cm", 1e-2, [0, 1, 0, 0, 0, 0, 0]),
+    ("mm", 1e-3, [0, 1, 0, 0, 0, 0, 0]),
+    ("um", 1e-6, [0, 1, 0, 0, 0, 0, 0]),
+    ("nm", 1e-9, [0, 1, 0, 0, 0, 0, 0]),
+    ("inch", 0.0254, [0, 1, 0, 0, 0, 0, 0]),
+    ("ft", 0.3048, [0, 1, 0, 0, 0, 0, 0]),
+    ("yd", 0.9144, [0, 1, 0, 0, 0, 0, 0]),
+    ("mi", 1609.344, [0, 1, 0, 0, 0, 0, 0]),
+    ("ha", 1e4, [0, 2, 0, 0, 0, 0, 0]),
+    ("L", 1e-3, [0, 3, 0, 0, 0, 0, 0]),
+    ("mL", 1e-6, [0, 3, 0, 0, 0, 0, 0]),
+    ("s", 1.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("ms", 1e-3, [0, 0, 1, 0, 0, 0, 0]),
+    ("us", 1e-6, [0, 0, 1, 0, 0, 0, 0]),
+    ("ns", 1e-9, [0, 0, 1, 0, 0, 0, 0]),
+    ("min", 60.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("h", 3600.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("day", 86400.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("week", 604800.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("yr", 31557600.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("Hz", 1.0, [0, 0, -1, 0, 0, 0, 0]),
+    ("kHz", 1e3, [0, 0, -1, 0, 0, 0, 0]),
+    ("MHz", 1e6, [0, 0, -1, 0, 0, 0, 0]),
+    ("GHz", 1e9, [0, 0, -1, 0, 0, 0, 0]),
+    ("mph", 0.44704, [0, 1, -1, 0, 0, 0, 0]),
+    ("N", 1.0, [1, 1, -2, 0, 0, 0, 0]),
+    ("kN", 1e3, [1, 1, -2, 0, 0, 0, 0]),
+    ("J", 1.0, [1, 2, -2, 0, 0, 0, 0]),
+    ("kJ", 1e3, [1, 2, -2, 0, 0, 0, 0]),
+    ("MJ", 1e6, [1, 2, -2, 0, 0, 0, 0]),
+    ("cal", 4.184, [1, 2, -2, 0, 0, 0, 0]),
+    ("kcal", 4184.0, [1, 2, -2, 0, 0, 0, 0]),
+    ("kWh", 3.6e6, [1, 2, -2, 0, 0, 0, 0]),
+    ("eV", 1.602176634e-19, [1, 2, -2, 0, 0, 0, 0]),
+    ("W", 1.0, [1, 2, -3, 0, 0, 0, 0]),
+    ("kW", 1e3, [1, 2, -3, 0, 0, 0, 0]),
+    ("MW", 1e6, [1, 2, -3, 0, 0, 0, 0]),
+    ("Pa", 1.0, [1, -1, -2, 0, 0, 0, 0]),
+    ("kPa", 1e3, [1, -1, -2, 0, 0, 0, 0]),
+    ("MPa", 1e6, [1, -1, -2, 0, 0, 0, 0]),
+    ("bar", 1e5, [1, -1, -2, 0, 0, 0, 0]),
+    ("atm", 101325.0, [1, -1, -2, 0, 0, 0, 0]),
+    ("psi", 6894.757293168361, [1, -1, -2, 0, 0, 0, 0]),
+    ("A", 1.0, [0, 0, 0, 1, 0, 0, 0]),
+    ("mA", 1e-3, [0, 0, 0, 1, 0, 0, 0]),
+    ("C", 1.0, [0, 0, 1, 1, 0, 0, 0]),
+    ("V", 1.0, [1, 2, -3, -1, 0, 0, 0]),
+    ("mV", 1e-3, [1, 2, -3, -1, 0, 0, 0]),
+    ("kV", 1e3, [1, 2, -3, -1, 0, 0, 0]),
+    ("ohm", 1.0, [1, 2, -3, -2, 0, 0, 0]),
+    ("K", 1.0, [0, 0, 0, 0, 1, 0, 0]),
+    ("mol", 1.0, [0, 0, 0, 0, 0, 1, 0]),
+    ("cd", 1.0, [0, 0, 0, 0, 0, 0, 1]),
+];
+
+/// A unit as written in the input, like `km/h`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub name: EcoString,
+    /// One of this unit is `numerator / denominator` SI units. Dividing by
+    /// another unit multiplies into the denominator, so that conversions to
+    /// units like `km/h` are as exact as the unit sizes.
+    numerator: f64,
+    denominator: f64,
+    pub dimension: Dimension,
+}
+
+impl Unit {
+    pub fn named(name: &str) -> Option<Self> {
+        let &(name, size, exponents) = UNITS.iter().find(|(unit, ..)| *unit == name)?;
+        Some(Self {
+            name: name.into(),
+            numerator: size,
+            denominator: 1.0,
+            dimension: Dimension(exponents),
+        })
+    }
+
+    /// The value in SI units of `x` of this unit.
+    pub fn to_si(&self, x: f64) -> f64 {
+        x * self.numerator / self.denominator
+    }
+
+    /// How many of this unit make up `x` SI units.
+    pub fn amount(&self, x: f64) -> f64 {
+        x * self.denominator / self.numerator
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Self {
+            name: ecow::eco_format!("{}*{}", self.name, other.name),
+            numerator: self.numerator * other.numerator,
+            denominator: self.denominator * other.denominator,
+            dimension: self.dimension.mul(other.dimension),
+        }
+    }
+
+    pub fn div(&self, other: &Self) -> Self {
+        Self {
+            name: ecow::eco_format!("{}/{}", self.name, other.name),
+            numerator: self.numerator * other.denominator,
+            denominator: self.denominator * other.numerator,
+            dimension: self.dimension.div(other.dimension),
+        }
+    }
+
+    pub fn pow(&self, n: i8) -> Option<Self> {
+        let name = match self.name.contains(['*', '/', '^']) {
+            true => ecow::eco_format!("({})^{n}", self.name),
+            false => ecow::eco_format!("{}^{n}", self.name),
+        };
+        let (numerator, denominator) = match n < 0 {
+            true => (self.denominator, self.numerator),
+            false => (self.numerator, self.denominator),
+        };
+        let power = n.unsigned_abs().into();
+        Some(Self {
+            name,
+            numerator: numerator.powi(power),
+            denominator: denominator.powi(power),
+            dimension: self.dimension.pow(n)?,
+        })
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// A real number with a dimension other than none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    /// The value in SI units, which all arithmetic is done in.
+    pub value: f64,
+    pub dimension: Dimension,
+    /// The unit the quantity was written in or converted to, which it is displayed in.
+    pub unit: Option<Unit>,
+}
+
+impl Quantity {
+    /// The value in the quantity's own unit, along with that unit if it has one.
+    pub fn in_unit(&self) -> (f64, Option<&Unit>) {
+        match &self.unit {
+            Some(unit) => (unit.amount(self.value), Some(unit)),
+            None => (self.value, None),
+        }
+    }
+
+    /// The same quantity, displayed in `unit`.
+    pub fn convert(&self, unit: &Unit) -> Result<Self> {
+        if unit.dimension != self.dimension {
+            bail!("Cannot convert {} to {unit}", self.dimension);
+        }
+        Ok(Self {
+            unit: Some(unit.clone()),
+            ..self.clone()
+        })
+    }
+}
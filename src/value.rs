@@ -0,0 +1,623 @@
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use anyhow::{Result, bail};
+use ecow::EcoVec;
+
+use crate::builtins::BuiltinFunction;
+use crate::complex::Complex;
+use crate::matrix::Matrix;
+use crate::number::{Number, check_arity};
+use crate::parse::{ArgList, BinaryOp, Expr, Field, Ident, UnaryOp};
+use crate::rational::Rational;
+use crate::units::{Dimension, Quantity, Unit};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    /// An exact real number, which only comes from literals in exact mode.
+    Rational(Rational),
+    /// A number with a nonzero imaginary part.
+    Complex(Complex),
+    /// A real number with units.
+    Quantity(Quantity),
+    Point(f64, f64),
+    /// A list of either only numbers or only points.
+    List(EcoVec<Value>),
+    Matrix(Matrix),
+    /// A function, which can be called and passed around but not computed with.
+    Function(Function),
+}
+
+/// A function value, made by naming a function or by a lambda like `x -> x^2`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Function {
+    /// A user function or builtin, looked up by its name when it is called.
+    Named(Ident),
+    Closure(Rc<Closure>),
+}
+
+/// A lambda together with the values of the local variables it refers to, which
+/// are captured when the lambda is evaluated.
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    pub args: ArgList<Ident>,
+    pub body: Expr,
+    pub captured: HashMap<Ident, Value>,
+}
+
+impl Value {
+    /// Makes a number from a complex result, dropping the imaginary part if it is zero.
+    pub fn complex(z: Complex) -> Self {
+        match z.im {
+            0.0 => Self::Number(z.re),
+            _ => Self::Complex(z),
+        }
+    }
+
+    /// Makes a number with units, or a plain number if the units cancel out.
+    pub fn quantity(value: f64, dimension: Dimension, unit: Option<Unit>) -> Self {
+        match dimension.is_none() {
+            true => Self::Number(value),
+            false => Self::Quantity(Quantity {
+                value,
+                dimension,
+                unit,
+            }),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Number(_) | Self::Rational(_) | Self::Quantity(_) => "number",
+            Self::Complex(_) => "complex number",
+            Self::Point(..) => "point",
+            Self::List(_) => "list",
+            Self::Matrix(_) => "matrix",
+            Self::Function(_) => "function",
+        }
+    }
+
+    /// Reads a real number, where quantities are read in SI units.
+    pub fn as_number(&self) -> Result<f64> {
+        match self {
+            Self::Number(x) => Ok(*x),
+            Self::Rational(x) => Ok(x.to_f64()),
+            Self::Quantity(x) => Ok(x.value),
+            Self::Complex(_) => bail!("Expected a real number but got a complex number"),
+            _ => bail!("Expected a number but got a {}", self.type_name()),
+        }
+    }
+
+    /// Reads a real number at a point of a curve or search, where numbers with an
+    /// imaginary part are undefined like `sqrt(-1)` is, leaving a gap.
+    pub fn as_sample(&self) -> Result<f64> {
+        match self {
+            Self::Complex(_) => Ok(f64::NAN),
+            _ => self.as_number(),
+        }
+    }
+
+    pub fn as_complex(&self) -> Option<Complex> {
+        match self {
+            Self::Number(x) => Some(Complex::from(*x)),
+            Self::Rational(x) => Some(Complex::from(x.to_f64())),
+            Self::Complex(z) => Some(*z),
+            _ => None,
+        }
+    }
+
+    /// Builds a list value, checking that the elements are all numbers or all points,
+    /// or a matrix from a list of rows.
+    pub fn list(elements: EcoVec<Value>) -> Result<Self> {
+        if let Some(first) = elements.first() {
+            if let Self::List(_) = first {
+                let rows = elements.iter().map(|row| match row {
+                    Self::List(row) => row.iter().map(Self::matrix_entry).collect(),
+                    _ => bail!("Cannot put a {} in a matrix", row.type_name()),
+                });
+                return Ok(Self::Matrix(Matrix::from_rows(
+                    &rows.collect::<Result<Vec<_>>>()?,
+                )?));
+            }
+            if elements
+                .iter()
+                .any(|element| matches!(element, Self::Function(_)))
+            {
+                bail!("Lists cannot contain functions");
+            }
+            if elements
+                .iter()
+                .any(|element| matches!(element, Self::Matrix(_)))
+            {
+                bail!("Lists cannot contain matrices");
+            }
+            for element in elements.iter() {
+                if matches!(element, Self::Point(..)) != matches!(first, Self::Point(..)) {
+                    bail!(
+                        "Cannot put a {} in a list of {}s",
+                        element.type_name(),
+                        first.type_name()
+                    );
+                }
+            }
+        }
+        Ok(Self::List(elements))
+    }
+
+    fn matrix_entry(&self) -> Result<f64> {
+        match self {
+            Self::Number(_) | Self::Rational(_) => self.as_number(),
+            Self::Complex(_) => bail!("Matrices cannot contain complex numbers"),
+            Self::Quantity(_) => bail!("Matrices cannot contain numbers with units"),
+            _ => bail!("Cannot put a {} in a matrix", self.type_name()),
+        }
+    }
+
+    /// The dimension of a number, which is none unless it has units.
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            Self::Quantity(x) => x.dimension,
+            _ => Dimension::default(),
+        }
+    }
+
+    /// Displays a value in `unit`, broadcasting over lists.
+    pub fn convert(&self, unit: &Unit) -> Result<Self> {
+        Ok(match self {
+            Self::Quantity(x) => Self::Quantity(x.convert(unit)?),
+            Self::List(elements) => Self::List(
+                elements
+                    .iter()
+                    .map(|element| element.convert(unit))
+                    .collect::<Result<_>>()?,
+            ),
+            _ => bail!(
+                "Cannot convert a {} without units to {unit}",
+                self.type_name()
+            ),
+        })
+    }
+
+    /// Applies `f` to every real or complex number, broadcasting over lists.
+    pub fn map_numbers(
+        &self,
+        name: &dyn fmt::Display,
+        f: &dyn Fn(&Self) -> Result<Self>,
+    ) -> Result<Self> {
+        Ok(match self {
+            Self::Number(_) | Self::Rational(_) | Self::Complex(_) | Self::Quantity(_) => f(self)?,
+            Self::Point(..) => bail!("Cannot apply {name} to a point"),
+            Self::Matrix(_) => bail!("Cannot apply {name} to a matrix"),
+            Self::Function(_) => bail!("Cannot apply {name} to a function"),
+            Self::List(elements) => Self::List(
+                elements
+                    .iter()
+                    .map(|element| element.map_numbers(name, f))
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+
+    pub fn unary_op(op: UnaryOp, arg: &Self) -> Result<Self> {
+        Ok(match (op, arg) {
+            (UnaryOp::Plus, arg) => arg.clone(),
+            (UnaryOp::Degrees, arg) => {
+                Self::binary_op(BinaryOp::DotProduct, arg, &Self::Number(1f64.to_radians()))?
+            }
+            (UnaryOp::Negate, Self::Number(x)) => Self::Number(-x),
+            (UnaryOp::Negate, Self::Rational(x)) => Self::Rational(-x.clone()),
+            (UnaryOp::Negate, Self::Complex(z)) => Self::Complex(-*z),
+            (UnaryOp::Negate, Self::Quantity(x)) => Self::Quantity(Quantity {
+                value: -x.value,
+                ..x.clone()
+            }),
+            (UnaryOp::Negate, Self::Point(x, y)) => Self::Point(-x, -y),
+            (UnaryOp::Negate, Self::Matrix(m)) => Self::Matrix(m.map(|x| -x)),
+            (UnaryOp::Negate, Self::Function(_)) => bail!("Cannot negate a function"),
+            (UnaryOp::Negate, Self::List(elements)) => Self::List(
+                elements
+                    .iter()
+                    .map(|element| Self::unary_op(op, element))
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+
+    /// Applies a binary operator, broadcasting over lists.
+    ///
+    /// `*` between two points is their dot product. Rational numbers stay exact
+    /// as long as the result is rational, and become floats otherwise. Units
+    /// are tracked through arithmetic, see `quantity_op`.
+    pub fn binary_op(op: BinaryOp, left: &Self, right: &Self) -> Result<Self> {
+        use Value::*;
+        Ok(match (left, right) {
+            (Matrix(_), _) | (_, Matrix(_)) => Self::matrix_op(op, left, right)?,
+
+            (List(left), List(right)) => {
+                if left.len() != right.len() {
+                    bail!(
+                        "Cannot combine lists of lengths {} and {}",
+                        left.len(),
+                        right.len()
+                    );
+                }
+                List(
+                    std::iter::zip(left.iter(), right.iter())
+                        .map(|(left, right)| Self::binary_op(op, left, right))
+                        .collect::<Result<_>>()?,
+                )
+            }
+            (List(left), right) => List(
+                left.iter()
+                    .map(|left| Self::binary_op(op, left, right))
+                    .collect::<Result<_>>()?,
+            ),
+            (left, List(right)) => List(
+                right
+                    .iter()
+                    .map(|right| Self::binary_op(op, left, right))
+                    .collect::<Result<_>>()?,
+            ),
+
+            (Function(_), _) | (_, Function(_)) => bail!("Cannot compute with a function"),
+
+            (Quantity(_), _) | (_, Quantity(_)) => Self::quantity_op(op, left, right)?,
+
+            (Rational(a), Rational(b)) => match crate::rational::Rational::binary_op(op, a, b) {
+                Some(x) => Rational(x),
+                None => Self::binary_op(op, &Number(a.to_f64()), &Number(b.to_f64()))?,
+            },
+            (Rational(a), right) => Self::binary_op(op, &Number(a.to_f64()), right)?,
+            (left, Rational(b)) => Self::binary_op(op, left, &Number(b.to_f64()))?,
+
+            // a negative number to a fractional power has complex values
+            (Number(a), Number(b)) if op == BinaryOp::Power && *a < 0.0 && b.fract() != 0.0 => {
+                Self::complex(
+                    crate::complex::Complex::from(*a).pow(crate::complex::Complex::from(*b)),
+                )
+            }
+            (Number(a), Number(b)) => Number(match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Subtract => a - b,
+                BinaryOp::DotProduct => a * b,
+                BinaryOp::Divide => a / b,
+                BinaryOp::Power => a.powf(*b),
+            }),
+            (Number(_) | Complex(_), Number(_) | Complex(_)) => {
+                let (a, b) = (left.as_complex().unwrap(), right.as_complex().unwrap());
+                Self::complex(match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Subtract => a - b,
+                    BinaryOp::DotProduct => a * b,
+                    BinaryOp::Divide => a / b,
+                    BinaryOp::Power => a.pow(b),
+                })
+            }
+            (Complex(_), Point(..)) | (Point(..), Complex(_)) => {
+                bail!("Cannot combine a point and a complex number")
+            }
+            (Point(..), _) | (_, Point(..)) if op == BinaryOp::Power => {
+                bail!("Cannot raise to a power with points")
+            }
+            (Point(ax, ay), Point(bx, by)) => match op {
+                BinaryOp::Add => Point(ax + bx, ay + by),
+                BinaryOp::Subtract => Point(ax - bx, ay - by),
+                BinaryOp::DotProduct => Number(ax * bx + ay * by),
+                BinaryOp::Divide => bail!("Cannot divide a point by a point"),
+                BinaryOp::Power => unreachable!(),
+            },
+            (Point(x, y), Number(k)) => match op {
+                BinaryOp::DotProduct => Point(x * k, y * k),
+                BinaryOp::Divide => Point(x / k, y / k),
+                BinaryOp::Power => unreachable!(),
+                BinaryOp::Add | BinaryOp::Subtract => {
+                    bail!("Cannot add or subtract a point and a number")
+                }
+            },
+            (Number(k), Point(x, y)) => match op {
+                BinaryOp::DotProduct => Point(k * x, k * y),
+                BinaryOp::Divide => bail!("Cannot divide a number by a point"),
+                BinaryOp::Power => unreachable!(),
+                BinaryOp::Add | BinaryOp::Subtract => {
+                    bail!("Cannot add or subtract a number and a point")
+                }
+            },
+        })
+    }
+
+    /// Applies a binary operator to a matrix and another value.
+    ///
+    /// Matrices multiply other matrices, lists of numbers as column vectors, and
+    /// points or lists of points as transforms, see `Matrix::transform`.
+    fn matrix_op(op: BinaryOp, left: &Self, right: &Self) -> Result<Self> {
+        use Value::*;
+        let real = |x: &Self| match x {
+            Number(_) | Rational(_) => x.as_number().ok(),
+            _ => None,
+        };
+        Ok(match (left, right, op) {
+            (Matrix(a), Matrix(b), BinaryOp::Add) => Matrix(a.zip(b, |a, b| a + b)?),
+            (Matrix(a), Matrix(b), BinaryOp::Subtract) => Matrix(a.zip(b, |a, b| a - b)?),
+            (Matrix(a), Matrix(b), BinaryOp::DotProduct) => Matrix(a.mul(b)?),
+            (Matrix(m), Point(x, y), BinaryOp::DotProduct) => {
+                let (x, y) = m.transform(*x, *y)?;
+                Point(x, y)
+            }
+            (Matrix(_), List(elements), BinaryOp::DotProduct)
+                if matches!(elements.first(), Some(Point(..))) =>
+            {
+                List(
+                    elements
+                        .iter()
+                        .map(|element| Self::binary_op(op, left, element))
+                        .collect::<Result<_>>()?,
+                )
+            }
+            (Matrix(m), List(elements), BinaryOp::DotProduct) => {
+                let vector = elements.iter().map(Self::matrix_entry);
+                let product = m.mul_vector(&vector.collect::<Result<Vec<_>>>()?)?;
+                List(product.into_iter().map(Number).collect())
+            }
+            (Matrix(m), k, BinaryOp::DotProduct) | (k, Matrix(m), BinaryOp::DotProduct)
+                if let Some(k) = real(k) =>
+            {
+                Matrix(m.map(|x| k * x))
+            }
+            (Matrix(m), k, BinaryOp::Divide) if let Some(k) = real(k) => Matrix(m.map(|x| x / k)),
+            (Matrix(m), n, BinaryOp::Power) if let Some(n) = real(n) => Matrix(m.pow(n)?),
+            _ => {
+                let op = match op {
+                    BinaryOp::Add => "add",
+                    BinaryOp::Subtract => "subtract",
+                    BinaryOp::DotProduct => "multiply",
+                    BinaryOp::Divide => "divide",
+                    BinaryOp::Power => "exponentiate",
+                };
+                bail!(
+                    "Cannot {op} a {} and a {}",
+                    left.type_name(),
+                    right.type_name()
+                )
+            }
+        })
+    }
+
+    /// Applies a binary operator to numbers of which at least one has units.
+    ///
+    /// Only numbers of the same dimension can be added or subtracted. Scaling a
+    /// quantity by a plain number keeps its unit, as does adding quantities with
+    /// the same unit; other results are displayed in SI units.
+    fn quantity_op(op: BinaryOp, left: &Self, right: &Self) -> Result<Self> {
+        let quantity = |value: &Self| match value {
+            Self::Quantity(x) => Ok(x.clone()),
+            Self::Number(_) | Self::Rational(_) => Ok(Quantity {
+                value: value.as_number()?,
+                dimension: Dimension::default(),
+                unit: None,
+            }),
+            _ => bail!(
+                "Cannot combine a {} and a number with units",
+                value.type_name()
+            ),
+        };
+        let (a, b) = (quantity(left)?, quantity(right)?);
+        let describe = |x: &Quantity| match x.dimension.is_none() {
+            true => "a plain number".to_string(),
+            false => x.dimension.to_string(),
+        };
+        Ok(match op {
+            BinaryOp::Add | BinaryOp::Subtract => {
+                let (verb, value) = match op {
+                    BinaryOp::Add => ("add", a.value + b.value),
+                    _ => ("subtract", a.value - b.value),
+                };
+                if a.dimension != b.dimension {
+                    bail!("Cannot {verb} {} and {}", describe(&a), describe(&b));
+                }
+                let unit = if a.unit == b.unit { a.unit } else { None };
+                Self::quantity(value, a.dimension, unit)
+            }
+            BinaryOp::DotProduct => {
+                let unit = match (a.dimension.is_none(), b.dimension.is_none()) {
+                    (true, _) => b.unit,
+                    (_, true) => a.unit,
+                    _ => None,
+                };
+                Self::quantity(a.value * b.value, a.dimension.mul(b.dimension), unit)
+            }
+            BinaryOp::Divide => {
+                let unit = if b.dimension.is_none() { a.unit } else { None };
+                Self::quantity(a.value / b.value, a.dimension.div(b.dimension), unit)
+            }
+            BinaryOp::Power => {
+                if !b.dimension.is_none() {
+                    bail!("Cannot raise to a power with units");
+                }
+                let n = b.value;
+                // integer powers and roots like `^0.5` keep the exponents integers
+                let (dimension, unit) = if n.fract() == 0.0 && n.abs() <= i8::MAX.into() {
+                    let dimension = a.dimension.pow(n as i8);
+                    (
+                        dimension,
+                        a.unit.as_ref().and_then(|unit| unit.pow(n as i8)),
+                    )
+                } else if (1.0 / n).fract() == 0.0 && (1.0 / n).abs() <= i8::MAX.into() {
+                    (a.dimension.root((1.0 / n) as i8), None)
+                } else {
+                    (None, None)
+                };
+                let Some(dimension) = dimension else {
+                    bail!("Cannot raise {} to the power {n}", a.dimension);
+                };
+                Self::quantity(a.value.powf(n), dimension, unit)
+            }
+        })
+    }
+
+    /// Reads the `.x` or `.y` coordinate of a point, broadcasting over lists.
+    pub fn access(&self, field: Field) -> Result<Self> {
+        Ok(match (self, field) {
+            (Self::Point(x, _), Field::X) => Self::Number(*x),
+            (Self::Point(_, y), Field::Y) => Self::Number(*y),
+            (Self::List(elements), field) => Self::List(
+                elements
+                    .iter()
+                    .map(|element| element.access(field))
+                    .collect::<Result<_>>()?,
+            ),
+            (
+                Self::Number(_)
+                | Self::Rational(_)
+                | Self::Complex(_)
+                | Self::Quantity(_)
+                | Self::Matrix(_)
+                | Self::Function(_),
+                field,
+            ) => bail!("Cannot access .{field} of a {}", self.type_name()),
+        })
+    }
+
+    /// Returns the points contained in this value, if it is a point or a list of points.
+    pub fn points(&self) -> Option<Vec<(f64, f64)>> {
+        match self {
+            Self::Point(x, y) => Some(vec![(*x, *y)]),
+            Self::List(elements) if matches!(elements.first(), Some(Self::Point(..))) => Some(
+                elements
+                    .iter()
+                    .filter_map(|element| match element {
+                        Self::Point(x, y) => Some((*x, *y)),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Number(x) => write!(f, "{x}"),
+            Self::Rational(x) => write!(f, "{x}"),
+            Self::Complex(z) => write!(f, "{z}"),
+            Self::Quantity(x) => match x.in_unit() {
+                (value, Some(unit)) => write!(f, "{value} {unit}"),
+                (value, None) => write!(f, "{value} {}", x.dimension),
+            },
+            Self::Point(x, y) => write!(f, "({x}, {y})"),
+            Self::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Matrix(m) => write!(f, "{m}"),
+            Self::Function(function) => write!(f, "{function}"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Named(name) => write!(f, "{name}"),
+            Self::Closure(closure) if closure.args.len() == 1 => {
+                write!(f, "{} -> {}", closure.args, closure.body)
+            }
+            Self::Closure(closure) => write!(f, "({}) -> {}", closure.args, closure.body),
+        }
+    }
+}
+
+impl Number for Value {
+    const NAME: &'static str = "value";
+
+    fn from_f64(x: f64) -> Self {
+        Self::Number(x)
+    }
+
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(value.clone())
+    }
+
+    fn from_rational(x: Rational) -> Self {
+        Self::Rational(x)
+    }
+
+    fn from_quantity(x: f64, unit: &Unit) -> Self {
+        Self::quantity(unit.to_si(x), unit.dimension, Some(unit.clone()))
+    }
+
+    fn convert(&self, unit: &Unit) -> Result<Self> {
+        self.convert(unit)
+    }
+
+    fn to_f64(&self) -> Result<f64> {
+        self.as_number()
+    }
+
+    fn unary_op(op: UnaryOp, arg: &Self) -> Result<Self> {
+        Self::unary_op(op, arg)
+    }
+
+    fn binary_op(op: BinaryOp, left: &Self, right: &Self) -> Result<Self> {
+        Self::binary_op(op, left, right)
+    }
+
+    fn apply(builtin: BuiltinFunction, args: &[Self]) -> Result<Self> {
+        check_arity(builtin, args)?;
+        builtin.apply(args)
+    }
+
+    fn point(x: Self, y: Self) -> Result<Self> {
+        if let Self::Quantity(_) = x {
+            bail!("Coordinates of a point cannot have units");
+        }
+        if let Self::Quantity(_) = y {
+            bail!("Coordinates of a point cannot have units");
+        }
+        Ok(Self::Point(x.as_number()?, y.as_number()?))
+    }
+
+    fn list(elements: Vec<Self>) -> Result<Self> {
+        Self::list(elements.into())
+    }
+
+    fn elements(&self) -> Result<Vec<Self>> {
+        match self {
+            Self::List(elements) => Ok(elements.to_vec()),
+            // the elements of a matrix are its rows
+            Self::Matrix(m) => Ok(m
+                .rows()
+                .map(|row| Self::List(row.iter().copied().map(Self::Number).collect()))
+                .collect()),
+            _ => bail!("Expected a list but got a {}", self.type_name()),
+        }
+    }
+
+    fn closure(args: &ArgList<Ident>, body: &Expr, captured: HashMap<Ident, Self>) -> Result<Self> {
+        let (args, body) = (args.clone(), body.clone());
+        let closure = Closure {
+            args,
+            body,
+            captured,
+        };
+        Ok(Self::Function(Function::Closure(Rc::new(closure))))
+    }
+
+    fn as_function(&self) -> Result<&Function> {
+        match self {
+            Self::Function(function) => Ok(function),
+            _ => bail!("Expected a function but got a {}", self.type_name()),
+        }
+    }
+
+    fn access(&self, field: Field) -> Result<Self> {
+        self.access(field)
+    }
+}